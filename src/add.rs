@@ -0,0 +1,569 @@
+use crate::database::{is_database_artifact, parse_sha256sum_line};
+use crate::errors::{FileError, InitError, IoError};
+use crate::files;
+use crate::hash::{self, BlockHashes, HashAlgorithm};
+use crate::models::{Database, FileRecord, HexStirng, HistoryAction};
+use crate::objects;
+use crate::paths::{PathMatch, paths_match};
+use crate::progress::Progress;
+use crate::styled;
+use crate::verify;
+use chrono::{DateTime, Utc};
+use colored::Colorize;
+use compio::fs::{File, Metadata};
+use exn::{Exn, ResultExt};
+use std::path::{Path, PathBuf};
+
+/// Options controlling how a file is added to the database.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AddOptions {
+    /// Replace the file with a hardlink to an identical tracked file (Unix only)
+    pub dedup: bool,
+    /// Re-hash the file even if its size and mtime match the tracked record
+    pub force: bool,
+    /// How the path is matched against tracked records
+    pub matching: PathMatch,
+    /// Also store the digests of the file's blocks of this many bytes
+    pub block_size: Option<u64>,
+    /// Keep a copy of the content in the object store, for `tamashii restore`
+    pub backup: bool,
+}
+
+/// What `add_file` did with a path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddOutcome {
+    /// The file was hashed and a record was committed
+    Added,
+    /// The file was already tracked; its record now holds the new hash
+    Updated,
+    /// The file is already tracked with the same content; if its size and mtime
+    /// matched too, it was not even re-hashed
+    Unchanged,
+    /// A symlink met while walking a directory was not followed, or led back
+    /// into a directory already walked; nothing was tracked
+    SkippedSymlink,
+}
+
+/// Hashes the file at `path` and commits a record for it to `db`.
+///
+/// If `path` is already tracked and its size and modification time match the
+/// stored record, the file is assumed unchanged and is neither re-hashed nor
+/// re-committed, unless `options.force` is set. Otherwise a tracked file's
+/// record is updated in place, keeping its id, rather than duplicated. The
+/// database file and its sidecars are never tracked. The record stores the
+/// path relative to `root_dir` when the file lies inside it.
+///
+/// # Arguments
+///
+/// * `db` - The database to add the record to
+/// * `path` - Path of the file to track
+/// * `options` - Deduplication and fast-path settings
+///
+/// # Returns
+///
+/// * `Ok(AddOutcome)` - Whether the file was added, updated, or skipped as unchanged
+/// * `Err(Exn<InitError>)` - If the database is frozen, the file is a database
+///   file, or it cannot be read, hashed, or committed
+pub async fn add_file(
+    db: &mut Database,
+    path: &Path,
+    options: &AddOptions,
+) -> Result<AddOutcome, Exn<InitError>> {
+    let file = open_for_add(db, path).await?;
+    let meta = files::get_meta(&file).await.map_err(|err| InitError {
+        message: format!("Failed to retrieve metadata: {}", err),
+    })?;
+    add_opened(db, path, &file, &meta, options).await
+}
+
+/// Opens the file at `path` for `add_file`, once it is known to be trackable.
+///
+/// # Returns
+///
+/// * `Ok(File)` - The opened file
+/// * `Err(Exn<InitError>)` - If the database is frozen, the file is a database
+///   file, or it cannot be opened
+async fn open_for_add(db: &Database, path: &Path) -> Result<File, Exn<InitError>> {
+    db.ensure_unfrozen().or_raise(|| InitError {
+        message: format!("Refusing to track {}", path.display()),
+    })?;
+    if is_database_artifact(path, &db.options.db_path) {
+        let err = Exn::new(FileError {
+            message: format!("{} belongs to the Tamashii database", path.display()),
+        });
+        return Err(err.raise(InitError {
+            message: format!(
+                "Refusing to track {}: it changes every time the database is saved",
+                path.display()
+            ),
+        }));
+    }
+    files::get_file(&path).await.or_raise(|| InitError {
+        message: format!(
+            "{}\n\t{}",
+            styled::error(&format!(
+                "Cannot add {} - file does not exist",
+                path.display()
+            ))
+            .bold(),
+            styled::warn("Usage: tamashii add <path-to-exisiting-file>").bold()
+        ),
+    })
+}
+
+/// Does the work of `add_file` for a file that is already open.
+///
+/// `meta` is the file's metadata as already read by the caller, so the file
+/// is stat'ed once per add: its size and times are taken from `meta` and
+/// nothing below reads them again.
+///
+/// # Arguments
+///
+/// * `db` - The database to add the record to
+/// * `path` - Path the file was opened from
+/// * `file` - The opened file, which is hashed
+/// * `meta` - Metadata of the file
+/// * `options` - Deduplication and fast-path settings
+pub async fn add_opened(
+    db: &mut Database,
+    path: &Path,
+    file: &File,
+    meta: &Metadata,
+    options: &AddOptions,
+) -> Result<AddOutcome, Exn<InitError>> {
+    let current_mtime = files::reported_time(meta.modified());
+    let tracked = db.tracked_path(path);
+    if !options.force
+        && db.files.iter().any(|record| {
+            paths_match(&record.path, &tracked, options.matching)
+                && verify::stat_unchanged(record, meta.len(), current_mtime)
+                && options.block_size.is_none_or(|size| {
+                    record
+                        .blocks
+                        .as_ref()
+                        .is_some_and(|blocks| blocks.block_size == size)
+                })
+                && (!options.backup || objects::has_object(&db.root_dir, &record.hash))
+        })
+    {
+        return Ok(AddOutcome::Unchanged);
+    }
+    // hash the contents of the file
+    let (hashed_file_content, blocks) = hash_contents(file, db.hash_algo, options.block_size)
+        .await
+        .map_err(|err| {
+            Exn::new(InitError {
+                message: format!("Failed to hash {:?}'s contents: {}", path, err),
+            })
+        })?;
+    if options.backup {
+        objects::store_object(&db.root_dir, path, &hashed_file_content, db.hash_algo)
+            .await
+            .or_raise(|| InitError {
+                message: format!("Failed to back up {}", path.display()),
+            })?;
+    }
+    let mut linked_to = None;
+    if options.dedup {
+        let original = db
+            .files
+            .iter()
+            .find(|record| {
+                record.hash == hashed_file_content
+                    && !paths_match(&record.path, &tracked, options.matching)
+            })
+            .map(|record| (record.id.clone(), db.resolve(&record.path)));
+        if let Some((id, original_path)) = original {
+            let linked = files::hardlink_duplicate(&original_path, path)
+                .await
+                .or_raise(|| InitError {
+                    message: format!("Failed to deduplicate {}", path.display()),
+                })?;
+            if linked {
+                println!(
+                    "Deduplicated: {} is now a hardlink to {}",
+                    path.display(),
+                    original_path.display()
+                );
+                linked_to = Some(id);
+            }
+        }
+    }
+    let time_stamp = db.now();
+    let modified_at = modified_time(meta.modified(), time_stamp);
+    if let Some(record) = db
+        .files
+        .iter_mut()
+        .find(|record| paths_match(&record.path, &tracked, options.matching))
+    {
+        if linked_to.is_none()
+            && record.algo == db.hash_algo
+            && record.hash.ct_eq(&hashed_file_content)
+        {
+            // same content; only refresh what the size-and-mtime fast path
+            // compares, and the blocks if they were asked for
+            let blocks = blocks.or_else(|| record.blocks.clone());
+            if record.size != meta.len()
+                || record.modified_at != Some(modified_at)
+                || record.blocks != blocks
+            {
+                record.size = meta.len();
+                record.modified_at = Some(modified_at);
+                record.blocks = blocks;
+                db.mark_dirty();
+            }
+            return Ok(AddOutcome::Unchanged);
+        }
+        record.hash = hashed_file_content;
+        record.algo = db.hash_algo;
+        record.size = meta.len();
+        record.time_stamp = time_stamp;
+        record.modified_at = Some(modified_at);
+        record.linked_to = linked_to;
+        record.blocks = blocks;
+        let hash = record.hash.clone();
+        db.log_change(HistoryAction::Updated, &tracked, &hash);
+        db.mark_dirty();
+        return Ok(AddOutcome::Updated);
+    }
+    let mut builder = db
+        .builder()
+        .with_fields(tracked, hashed_file_content, meta.len(), time_stamp)
+        .with_modified(modified_at);
+    if let Some(id) = linked_to {
+        builder = builder.with_link(id);
+    }
+    if let Some(blocks) = blocks {
+        builder = builder.with_blocks(blocks);
+    }
+    builder.commit().map_err(|err| {
+        Exn::new(InitError {
+            message: format!("Failed to commit database changes: {}", err),
+        })
+    })?;
+    Ok(AddOutcome::Added)
+}
+
+/// Hashes an opened file, along with its blocks when `block_size` is set.
+async fn hash_contents(
+    file: &File,
+    algo: HashAlgorithm,
+    block_size: Option<u64>,
+) -> Result<(HexStirng, Option<BlockHashes>), Exn<IoError<PathBuf>>> {
+    match block_size {
+        Some(block_size) => hash::hash_file_blocks(file, algo, block_size)
+            .await
+            .map(|(hash, blocks)| (hash, Some(blocks))),
+        None => hash::hash_file_streaming(file, algo)
+            .await
+            .map(|hash| (hash, None)),
+    }
+}
+
+/// Adds every path in `paths` to `db`, continuing past the ones that fail.
+///
+/// Directories are walked when `walk` is set (`--recursive`), each with the
+/// rules of `walk` followed by those of its own `.tamashiiignore`, and refused
+/// otherwise. A directory that
+/// fails part-way through leaves none of its files tracked; files added for
+/// earlier paths are kept. Nothing is saved, so the caller saves once.
+///
+/// # Arguments
+///
+/// * `db` - The database to add the records to
+/// * `paths` - Files and directories to track
+/// * `options` - Deduplication and fast-path settings
+/// * `walk` - Which entries to skip while walking directories, if directories are allowed
+/// * `progress` - Advanced and redrawn for every file reached
+/// * `on_file` - Called with each file kept as added, updated or unchanged
+///
+/// # Returns
+///
+/// The paths that could not be added, with the reason, in `paths` order.
+pub async fn add_paths(
+    db: &mut Database,
+    paths: &[PathBuf],
+    options: &AddOptions,
+    walk: Option<&files::WalkOptions>,
+    progress: &mut Progress,
+    mut on_file: impl FnMut(&Path, AddOutcome),
+) -> Vec<(PathBuf, Exn<InitError>)> {
+    let mut failed = Vec::new();
+    for path in paths.iter() {
+        // one stat decides file or directory and is then reused for the record
+        let meta = compio::fs::metadata(path).await;
+        if !meta.as_ref().is_ok_and(Metadata::is_dir) {
+            progress.advance(0, path);
+            progress.draw();
+            let added = match meta {
+                Ok(meta) => match open_for_add(db, path).await {
+                    Ok(file) => add_opened(db, path, &file, &meta, options).await,
+                    Err(err) => Err(err),
+                },
+                // let add_file report why the path cannot be added
+                Err(_) => add_file(db, path, options).await,
+            };
+            match added {
+                Ok(outcome) => on_file(path, outcome),
+                Err(err) => failed.push((path.clone(), err)),
+            }
+            continue;
+        }
+        let Some(walk) = walk else {
+            let err = Exn::new(InitError {
+                message: format!(
+                    "{} is a directory, use `tamashii add --recursive` to track its files",
+                    path.display()
+                ),
+            });
+            failed.push((path.clone(), err));
+            continue;
+        };
+        let snapshot = db.snapshot();
+        let mut done = Vec::new();
+        if let Err(err) = add_tree(db, path, walk, options, progress, &mut done).await {
+            db.restore(snapshot);
+            failed.push((path.clone(), err));
+            continue;
+        }
+        for (file_path, outcome) in done {
+            on_file(&file_path, outcome);
+        }
+    }
+    failed
+}
+
+/// Adds every file under the directory `root`, collecting what was done to each in `done`.
+async fn add_tree(
+    db: &mut Database,
+    root: &Path,
+    walk: &files::WalkOptions,
+    options: &AddOptions,
+    progress: &mut Progress,
+    done: &mut Vec<(PathBuf, AddOutcome)>,
+) -> Result<(), Exn<InitError>> {
+    let mut walk = walk.clone();
+    // the directory's own rules come last, so they can re-include what the config ignores
+    walk.ignore
+        .extend(files::load_ignore_patterns(root).or_raise(|| InitError {
+            message: format!("Failed to load the ignore rules of {}", root.display()),
+        })?);
+    // files are hashed as the walk finds them, so no path list is built up
+    let mut walker = files::walk_files(root, &walk);
+    for file_path in walker.by_ref() {
+        let file_path = file_path.or_raise(|| InitError {
+            message: format!("Failed to walk {}", root.display()),
+        })?;
+        progress.advance(0, &file_path);
+        progress.draw();
+        let outcome = add_file(db, &file_path, options).await?;
+        done.push((file_path, outcome));
+    }
+    for link in walker.skipped() {
+        done.push((link.clone(), AddOutcome::SkippedSymlink));
+    }
+    Ok(())
+}
+
+/// Returns the modification time to record for a file indexed at `indexed_at`.
+///
+/// Some platforms and filesystems do not report an mtime, or report it as the
+/// Unix epoch; the index time is recorded instead so every record carries one.
+pub fn modified_time(
+    modified: std::io::Result<std::time::SystemTime>,
+    indexed_at: DateTime<Utc>,
+) -> DateTime<Utc> {
+    files::reported_time(modified).unwrap_or(indexed_at)
+}
+
+/// Re-reads a tracked file and accepts its current content as the new baseline.
+///
+/// The record keeps its id and link; its hash, size, modification time and
+/// `time_stamp` are replaced with the file's current values.
+///
+/// # Arguments
+///
+/// * `db` - The database holding the record
+/// * `path` - Path of the tracked file
+/// * `matching` - How `path` is matched against tracked records
+///
+/// # Returns
+///
+/// * `Ok(true)` - The record was updated to the file's current content
+/// * `Ok(false)` - The stored hash already matched, nothing changed
+/// * `Err(Exn<InitError>)` - If the database is frozen, or `path` is not tracked or
+///   cannot be read or hashed
+pub async fn rehash_file(
+    db: &mut Database,
+    path: &Path,
+    matching: PathMatch,
+) -> Result<bool, Exn<InitError>> {
+    db.ensure_unfrozen().or_raise(|| InitError {
+        message: format!("Cannot rehash {}", path.display()),
+    })?;
+    let tracked = db.tracked_path(path);
+    let Some(index) = db
+        .files
+        .iter()
+        .position(|record| paths_match(&record.path, &tracked, matching))
+    else {
+        return Err(Exn::new(InitError {
+            message: format!("{} is not tracked", path.display()),
+        }));
+    };
+    let file = files::get_file(&path).await.or_raise(|| InitError {
+        message: format!("Cannot rehash {} - file does not exist", path.display()),
+    })?;
+    let meta = files::get_meta(&file).await.map_err(|err| InitError {
+        message: format!("Failed to retrieve metadata: {}", err),
+    })?;
+    let block_size = db.files[index]
+        .blocks
+        .as_ref()
+        .map(|blocks| blocks.block_size);
+    let (current_hash, blocks) = hash_contents(&file, db.files[index].algo, block_size)
+        .await
+        .map_err(|err| {
+            Exn::new(InitError {
+                message: format!("Failed to hash {:?}'s contents: {}", path, err),
+            })
+        })?;
+    Ok(accept_hash(db, index, current_hash, blocks, &meta))
+}
+
+/// Makes `current_hash` the baseline of the record at `index`, with the size
+/// and modification time from `meta`.
+///
+/// # Returns
+///
+/// `false` if the stored hash already matched and nothing changed.
+fn accept_hash(
+    db: &mut Database,
+    index: usize,
+    current_hash: HexStirng,
+    blocks: Option<BlockHashes>,
+    meta: &Metadata,
+) -> bool {
+    let now = db.now();
+    let record = &mut db.files[index];
+    if record.hash.ct_eq(&current_hash) {
+        return false;
+    }
+    record.hash = current_hash.clone();
+    record.blocks = blocks;
+    record.size = meta.len();
+    record.time_stamp = now;
+    record.modified_at = Some(modified_time(meta.modified(), now));
+    let tracked = record.path.clone();
+    db.log_change(HistoryAction::Updated, &tracked, &current_hash);
+    db.mark_dirty();
+    true
+}
+
+/// Accepts the current content of every tracked file that changed since it was hashed.
+///
+/// Each file is hashed once, by the verification that finds the changes, and
+/// that hash becomes the new baseline. Only files tracked with blocks are read
+/// again, since verification keeps just the indexes of the changed blocks.
+/// Missing and unreadable files are left as they are.
+///
+/// # Returns
+///
+/// * `Ok((updated, unchanged))` - The paths whose records were updated, and
+///   those whose records already matched their file
+/// * `Err(Exn<InitError>)` - If the database is frozen or a file cannot be hashed
+pub async fn rehash_changed(
+    db: &mut Database,
+) -> Result<(Vec<PathBuf>, Vec<PathBuf>), Exn<InitError>> {
+    db.ensure_unfrozen().or_raise(|| InitError {
+        message: "Cannot rehash the tracked files".into(),
+    })?;
+    let records: Vec<&FileRecord> = db.files.iter().collect();
+    let report = verify::verify_records(&db.root_dir, &records)
+        .await
+        .or_raise(|| InitError {
+            message: "There was an error hashing the file".into(),
+        })?;
+    let (mut updated, mut unchanged) = (Vec::new(), Vec::new());
+    for (index, result) in report.results.into_iter().enumerate() {
+        match (result.status, result.actual) {
+            (verify::VerifyStatus::Ok, _) => unchanged.push(result.path),
+            (verify::VerifyStatus::Changed, Some(current_hash))
+                if db.files[index].blocks.is_none() =>
+            {
+                let location = db.resolve(&result.path);
+                let file = files::get_file(&location).await.or_raise(|| InitError {
+                    message: format!("Cannot rehash {} - file does not exist", location.display()),
+                })?;
+                let meta = files::get_meta(&file).await.map_err(|err| InitError {
+                    message: format!("Failed to retrieve metadata: {}", err),
+                })?;
+                accept_hash(db, index, current_hash, None, &meta);
+                updated.push(result.path);
+            }
+            (verify::VerifyStatus::Changed, _) => {
+                let location = db.resolve(&result.path);
+                rehash_file(db, &location, PathMatch::Lexical).await?;
+                updated.push(result.path);
+            }
+            _ => {}
+        }
+    }
+    Ok((updated, unchanged))
+}
+
+/// Builds records from the lines of a `sha256sum` file, without hashing anything.
+///
+/// Relative paths are taken relative to `base`, the directory holding the
+/// file. The size of each file is read from disk if it exists, and zero if
+/// it does not, so it shows up as missing on the next verify. No
+/// modification time is stored: the imported hash was never compared with
+/// the file, so `verify --all` must not skip it as unchanged.
+///
+/// # Arguments
+///
+/// * `db` - The database the records are meant for, which decides their stored paths
+/// * `sums` - Contents of the `sha256sum` file
+/// * `base` - Directory relative paths in `sums` are resolved against
+///
+/// # Returns
+///
+/// The records, and the 1-based number and error of every line that could not be parsed.
+pub async fn read_sha256sums(
+    db: &Database,
+    sums: &[u8],
+    base: &Path,
+) -> (Vec<FileRecord>, Vec<(usize, String)>) {
+    let mut records = Vec::new();
+    let mut invalid = Vec::new();
+    for (index, line) in sums.split(|&byte| byte == b'\n').enumerate() {
+        if line.is_empty() {
+            continue;
+        }
+        let (hash, path) = match parse_sha256sum_line(line) {
+            Ok(entry) => entry,
+            Err(message) => {
+                invalid.push((index + 1, message));
+                continue;
+            }
+        };
+        let location = base.join(&path);
+        let time_stamp = db.now();
+        let size = match compio::fs::metadata(&location).await {
+            Ok(meta) => meta.len(),
+            Err(_) => 0,
+        };
+        records.push(FileRecord {
+            id: Database::gen_id(),
+            path: db.tracked_path(&location),
+            hash,
+            algo: HashAlgorithm::Sha256,
+            size,
+            time_stamp,
+            linked_to: None,
+            modified_at: None,
+            blocks: None,
+        });
+    }
+    (records, invalid)
+}
@@ -0,0 +1,95 @@
+use std::cell::RefCell;
+use std::future::poll_fn;
+use std::task::{Poll, Waker};
+
+/// A byte-accounting semaphore bounding how much file data is buffered at once.
+///
+/// Each hashing task reserves the size of the read buffer it is about to
+/// allocate before starting and releases it when its guard is dropped. When
+/// the budget is exhausted, further reservations wait until enough bytes are
+/// released. A single reservation larger than the whole budget is clamped to
+/// the budget, so a task is admitted on its own rather than never.
+///
+/// The budget is meant to be shared by tasks on one compio runtime thread.
+pub struct MemoryBudget {
+    /// Maximum number of bytes that may be reserved at the same time
+    limit: u64,
+    /// Mutable accounting state
+    state: RefCell<BudgetState>,
+}
+
+/// Accounting state of a `MemoryBudget`.
+struct BudgetState {
+    /// Bytes currently reserved
+    in_use: u64,
+    /// Highest value `in_use` has reached
+    peak: u64,
+    /// Tasks waiting for bytes to be released
+    waiters: Vec<Waker>,
+}
+
+/// A reservation of bytes from a `MemoryBudget`, released on drop.
+pub struct BudgetGuard<'a> {
+    /// The budget the bytes were reserved from
+    budget: &'a MemoryBudget,
+    /// Number of bytes reserved
+    bytes: u64,
+}
+
+impl MemoryBudget {
+    /// Creates a budget allowing at most `limit` bytes in flight.
+    pub fn new(limit: u64) -> Self {
+        Self {
+            limit: limit.max(1),
+            state: RefCell::new(BudgetState {
+                in_use: 0,
+                peak: 0,
+                waiters: Vec::new(),
+            }),
+        }
+    }
+
+    /// Creates a budget that never throttles.
+    pub fn unlimited() -> Self {
+        Self::new(u64::MAX)
+    }
+
+    /// Waits until `bytes` can be reserved, then reserves them.
+    pub async fn acquire(&self, bytes: u64) -> BudgetGuard<'_> {
+        let bytes = bytes.min(self.limit);
+        poll_fn(|cx| {
+            let mut state = self.state.borrow_mut();
+            if state.in_use.saturating_add(bytes) <= self.limit {
+                state.in_use += bytes;
+                state.peak = state.peak.max(state.in_use);
+                Poll::Ready(())
+            } else {
+                state.waiters.push(cx.waker().clone());
+                Poll::Pending
+            }
+        })
+        .await;
+        BudgetGuard {
+            budget: self,
+            bytes,
+        }
+    }
+
+    /// Returns the largest number of bytes that were ever reserved at once.
+    pub fn peak(&self) -> u64 {
+        self.state.borrow().peak
+    }
+}
+
+impl Drop for BudgetGuard<'_> {
+    fn drop(&mut self) {
+        let waiters = {
+            let mut state = self.budget.state.borrow_mut();
+            state.in_use -= self.bytes;
+            std::mem::take(&mut state.waiters)
+        };
+        for waker in waiters {
+            waker.wake();
+        }
+    }
+}
@@ -0,0 +1,1274 @@
+use crate::commands::{Cli, Commands, ConfigAction};
+use crate::{add, config, database, errors, files, hash, models, objects, styled, verify, watch};
+use clap::Parser;
+use colored::Colorize;
+use exn::{Exn, ResultExt};
+use files::WalkOptions;
+use futures_util::StreamExt;
+use models::{Database, HistoryAction};
+use std::io::{IsTerminal, Write};
+use std::path::Path;
+use std::sync::mpsc::TryRecvError;
+use std::time::{Duration, Instant};
+
+use crate::{
+    add::{AddOptions, AddOutcome},
+    budget::MemoryBudget,
+    config::Config,
+    database::StoreOptions,
+    errors::{InitError, VerificationError},
+    output::OrderedOutput,
+    paths::PathMatch,
+    profile::Profiler,
+    progress::Progress,
+    verify::{Remediation, StartPoint, VerifyReport, VerifyStatus},
+};
+
+/// Number of files listed in the `verify --profile` slowest-files summary.
+const SLOWEST_FILES: usize = 5;
+
+/// How often `watch` collects file events and verifies the files that settled.
+const WATCH_TICK: Duration = Duration::from_millis(50);
+
+/// Main application logic that handles subcommand routing and execution.
+///
+/// The binary's `main` only answers shell completion requests and turns an
+/// error returned from here into exit code 2.
+pub async fn run() -> Result<(), Exn<InitError>> {
+    let cli = Cli::parse();
+    styled::set_color_choice(cli.color);
+    if cli.readonly && cli.command.mutates_database() {
+        return Err(Exn::new(InitError {
+            message: "This command modifies the database and cannot run with --readonly".into(),
+        }));
+    }
+    let config = Config::load(Path::new(config::CONFIG_PATH))?;
+    let store = StoreOptions {
+        strict_permissions: cli.strict_permissions,
+        durable: !cli.no_durable,
+        write_checksum: cli.write_checksum,
+        check_checksum: cli.check_checksum,
+        readonly: cli.readonly,
+        recover: cli.recover,
+        hash_algo: config.hash_algo(cli.algo),
+        db_path: config.db_path(cli.db.clone()),
+        timestamp_override: match cli.timestamp_override {
+            Some(time_stamp) => Some(time_stamp),
+            None => database::source_date_epoch().or_raise(|| InitError {
+                message: "Invalid SOURCE_DATE_EPOCH".into(),
+            })?,
+        },
+    };
+    let matching = PathMatch::from_flags(cli.path_match, cli.ignore_case);
+    match cli.command {
+        Commands::Init { force } => {
+            let db = Database::init_at(&store.db_path, &store, force).await?;
+            // welcome message
+            println!("{}", styled::success("✨ Tamashii initialized! ✨ ").bold());
+            println!("\n{}", styled::info("File integrity checker ready."));
+
+            // usage examples
+            println!("\n{}", styled::info("Getting started:"));
+            println!("  tamashii add <file>     - Track a file");
+            println!("  tamashii status         - View tracked files");
+            println!("  tamashii verify <file>  - Check if file changed");
+            println!("  tamashii verify --all   - Check all tracked files");
+
+            // database info
+            println!(
+                "\n{} {}",
+                styled::info("Database:"),
+                store.db_path.display()
+            );
+            println!("{} file(s) currently tracked", db.files.len());
+        }
+        Commands::Add {
+            paths,
+            recursive,
+            exclude_vcs,
+            follow_symlinks,
+            dedup,
+            force,
+            and_verify,
+            block_size,
+            backup,
+        } => {
+            let mut test_db = Database::get_or_create_db(&store.db_path, &store).await?;
+            let options = AddOptions {
+                dedup,
+                force,
+                matching,
+                block_size,
+                backup,
+            };
+            let walk = WalkOptions {
+                db_path: store.db_path.clone(),
+                exclude_vcs,
+                ignore: config.ignore_patterns()?,
+                follow_symlinks,
+            };
+            let mut added_paths = Vec::new();
+            let (mut added, mut updated, mut unchanged, mut skipped) = (0, 0, 0, 0);
+            let mut progress = Progress::unbounded().quiet(cli.quiet);
+            let failed = add::add_paths(
+                &mut test_db,
+                &paths,
+                &options,
+                recursive.then_some(&walk),
+                &mut progress,
+                |file_path, outcome| match outcome {
+                    AddOutcome::Added => {
+                        println!("  added {}", file_path.display());
+                        added += 1;
+                        added_paths.push(file_path.to_path_buf());
+                    }
+                    AddOutcome::Updated => {
+                        println!("  updated {}", file_path.display());
+                        updated += 1;
+                        added_paths.push(file_path.to_path_buf());
+                    }
+                    AddOutcome::Unchanged => unchanged += 1,
+                    AddOutcome::SkippedSymlink => {
+                        println!("  skipped symlink {}", file_path.display());
+                        skipped += 1;
+                    }
+                },
+            )
+            .await;
+            progress.finish();
+            // one save for the whole run, keeping every path that did get added
+            test_db.save().await.map_err(|err| {
+                Exn::new(InitError {
+                    message: format!("Failed to save database: {}", err),
+                })
+            })?;
+            println!(
+                "{} file(s) added, {} updated, {} unchanged, {} symlink(s) skipped",
+                added, updated, unchanged, skipped
+            );
+            if and_verify {
+                let report = verify::verify_paths(&test_db, &added_paths, matching)
+                    .await
+                    .or_raise(|| InitError {
+                        message: "Failed to verify added files".to_string(),
+                    })?;
+                let summary = format!(
+                    "==== verified {}: {} ok, {} changed, {} missing, {} unreadable ====",
+                    report.total,
+                    report.ok,
+                    report.changed.len(),
+                    report.missing.len(),
+                    report.unreadable.len()
+                );
+                if report.ok == report.total {
+                    println!("{}", styled::success(&summary).bold());
+                } else {
+                    println!("{}", styled::error(&summary).bold());
+                    let problems = report.changed.iter().chain(report.missing.iter());
+                    for changed in problems.chain(report.unreadable.iter()) {
+                        println!("  {}", changed.display());
+                    }
+                }
+            }
+            if !failed.is_empty() {
+                let summary = format!("==== {} path(s) could not be added ====", failed.len());
+                eprintln!("{}", styled::error(&summary).bold());
+                for (path, err) in &failed {
+                    eprintln!("  {}: {}", path.display(), err.to_string().trim_end());
+                }
+                std::process::exit(1);
+            }
+        }
+        Commands::Verify {
+            path,
+            all,
+            report: report_path,
+            mem_limit,
+            by_dir,
+            start_from,
+            start_index,
+            exit_on_change,
+            max_age,
+            profile,
+            interactive,
+            shuffle,
+            seed,
+            jobs,
+            ignore_unreadable,
+            full,
+            detect_renames,
+        } => match (path, all) {
+            (Some(p), false) => {
+                // load db
+                let db = Database::load(&store.db_path, &store)
+                    .await
+                    .or_raise(|| InitError {
+                        message: " Database failed to load".to_string(),
+                    })?;
+                let tracked = db.tracked_path(&p);
+                let scoped = if p.is_dir() || db.find_record(&tracked, matching).is_none() {
+                    db.records_under(&tracked, matching)
+                } else {
+                    Vec::new()
+                };
+                if !scoped.is_empty() {
+                    let report = verify::verify_records(&db.root_dir, &scoped)
+                        .await
+                        .or_raise(|| InitError {
+                            message: "There was an error hashing the file".into(),
+                        })?;
+                    if !cli.quiet {
+                        for result in report.results.iter() {
+                            let line = format!("{:?}: {}", result.status, result.path.display());
+                            match result.status {
+                                VerifyStatus::Ok => println!("{}", styled::success(&line)),
+                                _ => println!("{}", styled::error(&line)),
+                            }
+                        }
+                        println!(
+                            "{}",
+                            format!(
+                                "==== {}: {} ok, {} changed, {} missing, {} unreadable ====",
+                                p.display(),
+                                report.ok,
+                                report.changed.len(),
+                                report.missing.len(),
+                                report.unreadable.len()
+                            )
+                            .bold()
+                        );
+                        for record in scoped.iter() {
+                            if max_age.is_some_and(|max_age| record.is_stale(max_age)) {
+                                println!("{}", styled::warn(&record.stale_notice()));
+                            }
+                        }
+                    }
+                    if let Some(report_path) = report_path {
+                        write_report(&report, &report_path).await?;
+                    }
+                    let code = report.exit_code(false, ignore_unreadable);
+                    if code != 0 {
+                        std::process::exit(code);
+                    }
+                    return Ok(());
+                }
+                // find file in db if there
+                let (outcome, checked) = match db.find_record(&tracked, matching) {
+                    Some(record) => {
+                        let (outcome, result) = verify::verify_record(&db.root_dir, record).await;
+                        (outcome, Some(result))
+                    }
+                    None => (Err(VerificationError::FileUntracked(p.clone())), None),
+                };
+                if !cli.quiet {
+                    match &outcome {
+                        Ok(()) => println!("Hashes match! The file has not changed."),
+                        Err(VerificationError::HashMissMatched { .. }) => {
+                            let warning = "--- WARNING ---".bold();
+                            let warning_msg =
+                                styled::error("Hash mismatch the files have changed.");
+                            println!("{}", warning);
+                            println!("{}", warning_msg);
+                        }
+                        Err(VerificationError::FileUntracked(_)) => {
+                            println!("There was no matching file in the database.")
+                        }
+                        Err(failure) => {
+                            println!(
+                                "{}",
+                                styled::error(&format!("{}: {}", p.display(), failure))
+                            )
+                        }
+                    }
+                    if let Some(record) = db.find_record(&tracked, matching) {
+                        if outcome.is_err() {
+                            println!(
+                                "Expected ({}...)\n Updated on:\n\t {}",
+                                record.hash.short(),
+                                record.time_stamp,
+                            );
+                        }
+                        let changed_blocks = checked
+                            .as_ref()
+                            .map_or(&[][..], |result| &result.changed_blocks);
+                        if let Some(changed) = verify::changed_blocks_line(record, changed_blocks) {
+                            println!("{}", changed);
+                        }
+                        if max_age.is_some_and(|max_age| record.is_stale(max_age)) {
+                            println!("{}", styled::warn(&record.stale_notice()));
+                        }
+                    }
+                }
+                if let Some(report_path) = report_path {
+                    let report =
+                        VerifyReport::for_single_file(&p, checked).or_raise(|| InitError {
+                            message: format!(
+                                "No verification report written to {}",
+                                report_path.display()
+                            ),
+                        })?;
+                    write_report(&report, &report_path).await?;
+                }
+                let code = verify::verification_exit_code(&outcome, ignore_unreadable);
+                if code != 0 {
+                    std::process::exit(code);
+                }
+            }
+            (None, true) => {
+                let mut db =
+                    Database::load(&store.db_path, &store)
+                        .await
+                        .or_raise(|| InitError {
+                            message: " Database failed to load".to_string(),
+                        })?;
+                if !cli.quiet {
+                    let file_len = styled::success(&format!(
+                        "==== Total of {} files tracked. ====",
+                        db.files.len()
+                    ))
+                    .bold();
+                    println!("{}", file_len);
+                }
+                let start = match (start_from, start_index) {
+                    (Some(path), _) => StartPoint::Path(db.tracked_path(&path)),
+                    (None, Some(index)) => StartPoint::Index(index),
+                    (None, None) => StartPoint::Beginning,
+                };
+                let mut records =
+                    verify::records_from(&db.files, &start, matching).ok_or_else(|| {
+                        Exn::new(InitError {
+                            message: "--start-from does not name a tracked file".to_string(),
+                        })
+                    })?;
+                if records.len() < db.files.len() && !cli.quiet {
+                    println!(
+                        "Skipping {} record(s) before the start point.",
+                        db.files.len() - records.len()
+                    );
+                }
+                if shuffle {
+                    let seed = seed.unwrap_or_else(rand::random);
+                    if !cli.quiet {
+                        println!("Shuffling verification order with --seed {}", seed);
+                    }
+                    verify::shuffle_records(&mut records, seed);
+                }
+                let mut results = Vec::with_capacity(records.len());
+                let total_bytes = records.iter().map(|file| file.size).sum();
+                let mut progress = Progress::new(records.len(), total_bytes).quiet(cli.quiet);
+                let budget = mem_limit.map_or_else(MemoryBudget::unlimited, MemoryBudget::new);
+                let mut profiler = profile.then(Profiler::default);
+                let prompt = interactive && std::io::stdin().is_terminal();
+                if interactive && !prompt {
+                    eprintln!("stdin is not a terminal, skipping every changed file");
+                }
+                let mut accepted = Vec::new();
+                let mut quit = false;
+                let sink: Box<dyn std::io::Write> = if cli.quiet {
+                    Box::new(std::io::sink())
+                } else {
+                    Box::new(std::io::stdout())
+                };
+                let mut output = OrderedOutput::new(sink);
+                let root = db.root_dir.clone();
+                // hash up to `jobs` files at once; `buffered` hands results back in record order
+                let mut checks = futures_util::stream::iter(records)
+                    .map(|file| {
+                        let (root, budget) = (&root, &budget);
+                        async move {
+                            let started = Instant::now();
+                            // trust an unchanged size and mtime unless --full asks otherwise
+                            let skipped = !full && verify::stat_matches(root, file).await;
+                            let result = if skipped {
+                                Ok(verify::assume_unchanged(file))
+                            } else {
+                                verify::check_record_within(root, file, budget).await
+                            };
+                            (file, started.elapsed(), skipped, result)
+                        }
+                    })
+                    .buffered(jobs.max(1));
+                let mut index = 0;
+                let mut skipped_count = 0;
+                // iter throuh files
+                while let Some((file, elapsed, skipped, result)) = checks.next().await {
+                    if skipped {
+                        skipped_count += 1;
+                    }
+                    let result = result.or_raise(|| InitError {
+                        message: "There was an error hashing the file".into(),
+                    })?;
+                    if let Some(profiler) = profiler.as_mut() {
+                        let timing = profiler.record(&file.path, file.size, elapsed);
+                        eprintln!("profile: {}", timing);
+                    }
+                    output
+                        .push(index, verify::render_result(file, &result, max_age))
+                        .or_raise(|| InitError {
+                            message: "Failed to write verification output".into(),
+                        })?;
+                    index += 1;
+                    if prompt && result.status == VerifyStatus::Changed {
+                        let remediation = verify::prompt_remediation(
+                            &mut std::io::stdin().lock(),
+                            &mut std::io::stdout(),
+                            &file.path,
+                        )
+                        .or_raise(|| InitError {
+                            message: "Failed to read the answer".into(),
+                        })?;
+                        match remediation {
+                            Remediation::Accept => accepted.push(file.path.clone()),
+                            Remediation::Skip => {}
+                            Remediation::Quit => quit = true,
+                        }
+                    }
+                    results.push(result);
+                    progress.advance(file.size, &file.path);
+                    progress.draw();
+                    if quit {
+                        break;
+                    }
+                }
+                drop(checks);
+                if !accepted.is_empty() {
+                    for path in accepted.iter() {
+                        add::rehash_file(&mut db, &root.join(path), matching).await?;
+                        println!("Accepted new hash for {}", path.display());
+                    }
+                    db.save().await.or_raise(|| InitError {
+                        message: "Failed to save database".into(),
+                    })?;
+                }
+                let report = VerifyReport::from_results_sorted(results);
+                if !cli.quiet {
+                    println!(
+                        "{}",
+                        format!(
+                            "==== {} ok, {} changed, {} missing, {} unreadable ====",
+                            report.ok,
+                            report.changed.len(),
+                            report.missing.len(),
+                            report.unreadable.len()
+                        )
+                        .bold()
+                    );
+                    println!(
+                        "{} file(s) hashed, {} skipped with unchanged size and mtime",
+                        report.total - skipped_count,
+                        skipped_count
+                    );
+                    for result in report.results.iter() {
+                        if let Some(failure) = result.failure() {
+                            let line = match failure {
+                                VerificationError::HashMissMatched { .. } => {
+                                    format!("  {}", failure)
+                                }
+                                _ => format!("  {}: {}", result.path.display(), failure),
+                            };
+                            println!("{}", styled::error(&line));
+                        }
+                    }
+                }
+                if ignore_unreadable && !report.unreadable.is_empty() {
+                    eprintln!(
+                        "{} ignoring {} unreadable file(s)",
+                        styled::warn("warning:").bold(),
+                        report.unreadable.len()
+                    );
+                }
+                if by_dir {
+                    println!("\n{}", styled::info("Per-directory summary:"));
+                    for rollup in report.by_directory(&db.root_dir) {
+                        let line = format!(
+                            "  {}: {} ok, {} changed, {} missing, {} unreadable",
+                            rollup.directory,
+                            rollup.ok,
+                            rollup.changed,
+                            rollup.missing,
+                            rollup.unreadable
+                        );
+                        if rollup.changed + rollup.missing + rollup.unreadable > 0 {
+                            println!("{}", styled::error(&line));
+                        } else {
+                            println!("{}", line);
+                        }
+                    }
+                }
+                if let Some(profiler) = profiler {
+                    println!("\n{}", styled::info("Slowest files:"));
+                    for timing in profiler.slowest(SLOWEST_FILES) {
+                        println!("  {}", timing);
+                    }
+                }
+                if let Some(limit) = mem_limit {
+                    println!(
+                        "Peak buffered file data: {} of {} byte(s)",
+                        budget.peak(),
+                        limit
+                    );
+                }
+                if detect_renames && !report.missing.is_empty() {
+                    let walk = WalkOptions {
+                        db_path: store.db_path.clone(),
+                        exclude_vcs: true,
+                        ignore: [
+                            config.ignore_patterns()?,
+                            files::load_ignore_patterns(&db.root_dir).or_raise(|| InitError {
+                                message: "Failed to load the ignore rules".into(),
+                            })?,
+                        ]
+                        .concat(),
+                        follow_symlinks: false,
+                    };
+                    let renames = verify::detect_renames(&db, &report.missing, &walk, matching)
+                        .await
+                        .or_raise(|| InitError {
+                            message: "Failed to look for renamed files".into(),
+                        })?;
+                    let prompt = std::io::stdin().is_terminal();
+                    let mut moved = 0;
+                    for rename in renames.iter() {
+                        println!(
+                            "{}",
+                            styled::warn(&format!(
+                                "Likely renamed: {} -> {}",
+                                rename.from.display(),
+                                rename.to.display()
+                            ))
+                        );
+                        let accept = prompt
+                            && verify::prompt_rename(
+                                &mut std::io::stdin().lock(),
+                                &mut std::io::stdout(),
+                                rename,
+                            )
+                            .or_raise(|| InitError {
+                                message: "Failed to read the answer".into(),
+                            })?;
+                        if accept
+                            && db
+                                .rename_record(&rename.from, rename.to.clone(), matching)
+                                .or_raise(|| InitError {
+                                    message: format!("Cannot move {}", rename.from.display()),
+                                })?
+                        {
+                            moved += 1;
+                        }
+                    }
+                    if moved > 0 {
+                        db.save().await.or_raise(|| InitError {
+                            message: "Failed to save database".into(),
+                        })?;
+                        println!("Updated {} renamed record(s)", moved);
+                    } else if !renames.is_empty() && !prompt {
+                        eprintln!(
+                            "stdin is not a terminal, leaving the renamed records as they are"
+                        );
+                    }
+                }
+                if let Some(report_path) = report_path {
+                    write_report(&report, &report_path).await?;
+                }
+                let code = report.exit_code(exit_on_change, ignore_unreadable);
+                if code != 0 {
+                    std::process::exit(code);
+                }
+            }
+            (None, false) => {
+                eprintln!("Error: must provide either <path> or --all");
+                std::process::exit(1);
+            }
+            (Some(_), true) => {
+                eprintln!("Error: cannot use both <path> and --all");
+                std::process::exit(1);
+            }
+        },
+        Commands::Remove { path } => {
+            let mut db = Database::load(&store.db_path, &store).await?;
+            let Some(record) = db.remove_by_path(&db.tracked_path(&path), matching) else {
+                eprintln!(
+                    "{}",
+                    styled::error(&format!("{} is not tracked", path.display()))
+                );
+                std::process::exit(1);
+            };
+            db.save().await.or_raise(|| InitError {
+                message: "Failed to save database".into(),
+            })?;
+            println!("Removed: {}", record.path.display());
+        }
+        Commands::Rehash { paths, all } => {
+            let mut db = Database::load(&store.db_path, &store).await?;
+            let (mut rehashed, mut current) = (0, 0);
+            if all {
+                let (updated, unchanged) = add::rehash_changed(&mut db).await?;
+                for path in updated.iter() {
+                    println!("Rehashed: {}", path.display());
+                }
+                for path in unchanged.iter() {
+                    println!("Unchanged: {}", path.display());
+                }
+                rehashed = updated.len();
+                current = unchanged.len();
+            } else {
+                for path in paths.iter() {
+                    if add::rehash_file(&mut db, path, matching).await? {
+                        println!("Rehashed: {}", path.display());
+                        rehashed += 1;
+                    } else {
+                        println!("Unchanged: {}", path.display());
+                        current += 1;
+                    }
+                }
+            }
+            db.save().await.or_raise(|| InitError {
+                message: "Failed to save database".into(),
+            })?;
+            println!(
+                "{} record(s) rehashed, {} already current",
+                rehashed, current
+            );
+        }
+        Commands::Status {
+            max_age,
+            watch,
+            check,
+            ignore_unreadable,
+            json,
+            since,
+        } => {
+            // loads the database, keeping only the records shown under --since
+            let load = || async {
+                let mut db = Database::load(&store.db_path, &store).await?;
+                if let Some(since) = since {
+                    db.retain_since(since);
+                }
+                Ok::<_, Exn<InitError>>(db)
+            };
+            if json {
+                let db = load().await?;
+                let json_data = db.status_json().or_raise(|| InitError {
+                    message: "Failed to serialize the status".into(),
+                })?;
+                println!("{}", json_data);
+                return Ok(());
+            }
+            let Some(interval) = watch else {
+                println!("Getting the status...");
+                let db = load().await?;
+                if let Some(since) = since.filter(|_| db.files.is_empty()) {
+                    println!("No records added or changed since {}", since);
+                    return Ok(());
+                }
+                if check {
+                    let report = check_all(&db).await?;
+                    print!("{}", db.status_frame(max_age, Some(&report)));
+                    let code = report.exit_code(false, ignore_unreadable);
+                    if code != 0 {
+                        std::process::exit(code);
+                    }
+                } else {
+                    db.db_status(max_age).await;
+                }
+                return Ok(());
+            };
+            let interval = interval.to_std().or_raise(|| InitError {
+                message: "--watch interval must not be negative".into(),
+            })?;
+            loop {
+                // reload so records added or rehashed elsewhere show up
+                let db = load().await?;
+                let report = match check {
+                    true => Some(check_all(&db).await?),
+                    false => None,
+                };
+                let frame = db.status_frame(max_age, report.as_ref());
+                if !std::io::stdout().is_terminal() {
+                    print!("{}", frame);
+                    let code =
+                        report.map_or(0, |report| report.exit_code(false, ignore_unreadable));
+                    if code != 0 {
+                        std::process::exit(code);
+                    }
+                    break;
+                }
+                // clear the screen and move the cursor home before each redraw
+                print!("\x1b[2J\x1b[H{}", frame);
+                println!(
+                    "{}",
+                    styled::info(&format!(
+                        "Refreshing every {}s, press Ctrl-C to stop",
+                        interval.as_secs_f64()
+                    ))
+                );
+                compio::time::sleep(interval).await;
+            }
+        }
+        Commands::List {
+            json,
+            output_format,
+            since,
+        } => {
+            let format = if json {
+                models::OutputFormat::Json
+            } else {
+                output_format
+            };
+            let mut db = Database::load(&store.db_path, &store).await?;
+            if let Some(since) = since {
+                db.retain_since(since);
+                if db.files.is_empty() && format == models::OutputFormat::Plain {
+                    println!("No records added or changed since {}", since);
+                }
+            }
+            let listing = models::format_records(&db.files, format).or_raise(|| InitError {
+                message: "Failed to list the tracked files".into(),
+            })?;
+            print!("{}", listing);
+        }
+        Commands::Log { limit } => {
+            let db = Database::load(&store.db_path, &store).await?;
+            if db.history.is_empty() {
+                println!("No changes recorded yet");
+            }
+            let skip = limit.map_or(0, |limit| db.history.len().saturating_sub(limit));
+            for entry in db.history.iter().skip(skip) {
+                let line = format!(
+                    "{} {:<7} {} ({}...)",
+                    entry.timestamp.format("%Y-%m-%d %H:%M:%S"),
+                    entry.action,
+                    entry.path.display(),
+                    entry.hash.short()
+                );
+                match entry.action {
+                    HistoryAction::Added => println!("{}", styled::success(&line)),
+                    HistoryAction::Updated => println!("{}", styled::warn(&line)),
+                    HistoryAction::Removed => println!("{}", styled::error(&line)),
+                }
+            }
+        }
+        Commands::Stats { group_by } => {
+            let db = Database::load(&store.db_path, &store).await?;
+            println!(
+                "{}",
+                styled::success("======= Database Stats =======").bold()
+            );
+            println!(
+                "Total files: {}\nTotal size: {} bytes",
+                db.files.len(),
+                db.total_size()
+            );
+            if let Some(group_by) = group_by {
+                let groups = db.group_totals(group_by);
+                let width = groups
+                    .iter()
+                    .map(|group| group.key.len())
+                    .max()
+                    .unwrap_or_default()
+                    .max(5);
+                println!("\n{:<width$} {:>8} {:>14}", "GROUP", "FILES", "BYTES");
+                for group in groups.iter() {
+                    println!(
+                        "{:<width$} {:>8} {:>14}",
+                        group.key, group.count, group.total_size
+                    );
+                }
+            }
+        }
+        Commands::Dupes { min_size } => {
+            let db = Database::load(&store.db_path, &store).await?;
+            let groups = db.duplicate_groups(min_size);
+            if groups.is_empty() {
+                println!("No duplicate files found.");
+            }
+            for group in groups.iter() {
+                println!(
+                    "{}",
+                    styled::warn(&format!(
+                        "{} copies of {} bytes ({}...), {} bytes wasted",
+                        group.paths.len(),
+                        group.size,
+                        group.hash.short(),
+                        group.wasted()
+                    ))
+                );
+                for path in group.paths.iter() {
+                    println!("  {}", path.display());
+                }
+            }
+            let wasted: u64 = groups.iter().map(|group| group.wasted()).sum();
+            println!(
+                "{} duplicate group(s), {} bytes wasted",
+                groups.len(),
+                wasted
+            );
+        }
+        Commands::DiffDb { other, json } => {
+            let db = Database::load(&store.db_path, &store).await?;
+            let other_db = Database::load(&other, &StoreOptions::read_only()).await?;
+            let diff = db.diff_records(&other_db);
+            if json {
+                let json_data = serde_json::to_string_pretty(&diff).or_raise(|| InitError {
+                    message: "Failed to serialize the database diff".into(),
+                })?;
+                println!("{}", json_data);
+            } else if diff.is_empty() {
+                println!(
+                    "No differences between {} and {}",
+                    store.db_path.display(),
+                    other.display()
+                );
+            } else {
+                println!("{}", format!("--- {}", store.db_path.display()).bold());
+                println!("{}", format!("+++ {}", other.display()).bold());
+                for record in diff.removed.iter() {
+                    println!(
+                        "{}",
+                        styled::error(&format!("- {} {}", record.path.display(), record.hash))
+                    );
+                }
+                for record in diff.added.iter() {
+                    println!(
+                        "{}",
+                        styled::success(&format!("+ {} {}", record.path.display(), record.hash))
+                    );
+                }
+                for change in diff.changed.iter() {
+                    println!("{}", styled::warn(&format!("~ {}", change.path.display())));
+                    println!("{}", styled::error(&format!("  - {}", change.old_hash)));
+                    println!("{}", styled::success(&format!("  + {}", change.new_hash)));
+                }
+                println!(
+                    "{} added, {} removed, {} changed",
+                    diff.added.len(),
+                    diff.removed.len(),
+                    diff.changed.len()
+                );
+            }
+        }
+        Commands::Diff { untracked } => {
+            let db = Database::load(&store.db_path, &store).await?;
+            let walk = if untracked {
+                Some(WalkOptions {
+                    db_path: store.db_path.clone(),
+                    exclude_vcs: true,
+                    ignore: [
+                        config.ignore_patterns()?,
+                        files::load_ignore_patterns(&db.root_dir).or_raise(|| InitError {
+                            message: "Failed to load the ignore rules".into(),
+                        })?,
+                    ]
+                    .concat(),
+                    follow_symlinks: false,
+                })
+            } else {
+                None
+            };
+            let diff = db
+                .diff_against_disk(walk.as_ref())
+                .await
+                .or_raise(|| InitError {
+                    message: "Failed to compare the database with the disk".into(),
+                })?;
+            if diff.is_clean() {
+                println!(
+                    "{}",
+                    styled::success("Every tracked file matches the database")
+                );
+            } else {
+                let changes = [
+                    ("modified:  ", &diff.modified),
+                    ("missing:   ", &diff.missing),
+                    ("unreadable:", &diff.unreadable),
+                ];
+                if changes.iter().any(|(_, paths)| !paths.is_empty()) {
+                    println!("Changes since the baseline:");
+                    for (label, paths) in changes {
+                        for path in paths.iter() {
+                            let line = format!("\t{} {}", label, path.display());
+                            println!("{}", styled::error(&line));
+                        }
+                    }
+                }
+                if !diff.untracked.is_empty() {
+                    println!("Untracked files:");
+                    for path in diff.untracked.iter() {
+                        let path = path.strip_prefix(&db.root_dir).unwrap_or(path);
+                        println!("{}", styled::warn(&format!("\t{}", path.display())));
+                    }
+                }
+            }
+            println!(
+                "{} unchanged, {} modified, {} missing, {} unreadable{}",
+                diff.unchanged.len(),
+                diff.modified.len(),
+                diff.missing.len(),
+                diff.unreadable.len(),
+                if untracked {
+                    format!(", {} untracked", diff.untracked.len())
+                } else {
+                    String::new()
+                }
+            );
+        }
+        Commands::Gc { dry_run: true } => {
+            let db = Database::load(&store.db_path, &store).await?;
+            let missing = db.missing_records().await;
+            if missing.is_empty() {
+                println!("Nothing to collect, all tracked files exist.");
+            } else {
+                for record in missing.iter() {
+                    println!("Would remove: {}", record.path.display());
+                }
+                println!("{} record(s) would be removed", missing.len());
+            }
+        }
+        Commands::Gc { dry_run: false } => {
+            let mut db = Database::load(&store.db_path, &store).await?;
+            let removed = db.prune_missing().await;
+            db.save().await.or_raise(|| InitError {
+                message: "Failed to save database".into(),
+            })?;
+            if removed.is_empty() {
+                println!("Nothing to collect, all tracked files exist.");
+            } else {
+                for record in removed.iter() {
+                    println!("Removed: {}", record.path.display());
+                }
+                println!("{} record(s) removed", removed.len());
+            }
+        }
+        Commands::Import { file, rebase } => {
+            let mut other = Database::load(&file, &StoreOptions::read_only()).await?;
+            // rebase and re-root the imported paths as absolute ones
+            other.absolutize_paths();
+            if let Some(rebase) = rebase {
+                for path in other.rebase(&rebase) {
+                    eprintln!(
+                        "{} {} is not under {}, imported unchanged",
+                        styled::warn("warning:").bold(),
+                        path.display(),
+                        rebase.old_root.display()
+                    );
+                }
+            }
+            let mut db = Database::get_or_create_db(&store.db_path, &store).await?;
+            for record in other.files.iter_mut() {
+                record.path = db.tracked_path(&record.path);
+            }
+            let summary = db.import_records(other.files, matching);
+            db.save().await.or_raise(|| InitError {
+                message: "Failed to save database".into(),
+            })?;
+            for path in summary.replaced.iter() {
+                eprintln!(
+                    "{} {} has a different hash in {}, replaced with the imported record",
+                    styled::warn("warning:").bold(),
+                    path.display(),
+                    file.display()
+                );
+            }
+            println!(
+                "Imported {} record(s), replaced {}, {} already tracked",
+                summary.added,
+                summary.replaced.len(),
+                summary.unchanged
+            );
+        }
+        Commands::ImportSums { file } => {
+            let sums = compio::fs::read(&file).await.or_raise(|| InitError {
+                message: format!("Failed to read {}", file.display()),
+            })?;
+            let base = match file.parent() {
+                Some(parent) if !parent.as_os_str().is_empty() => parent,
+                _ => Path::new("."),
+            };
+            let mut db = Database::get_or_create_db(&store.db_path, &store).await?;
+            let (records, invalid) = add::read_sha256sums(&db, &sums, base).await;
+            for (line, message) in invalid.iter() {
+                eprintln!(
+                    "{} {}:{}: {}, skipped",
+                    styled::warn("warning:").bold(),
+                    file.display(),
+                    line,
+                    message
+                );
+            }
+            let summary = db.import_records(records, matching);
+            db.save().await.or_raise(|| InitError {
+                message: "Failed to save database".into(),
+            })?;
+            for path in summary.replaced.iter() {
+                eprintln!(
+                    "{} {} has a different hash in {}, replaced with the imported record",
+                    styled::warn("warning:").bold(),
+                    path.display(),
+                    file.display()
+                );
+            }
+            println!(
+                "Imported {} record(s), replaced {}, {} already tracked, {} line(s) skipped",
+                summary.added,
+                summary.replaced.len(),
+                summary.unchanged,
+                invalid.len()
+            );
+        }
+        Commands::Hash { path, stdin } => match path {
+            Some(path) if !stdin && path.as_os_str() != "-" => {
+                let file = files::get_file(&path).await.or_raise(|| InitError {
+                    message: format!("Failed to open {}", path.display()),
+                })?;
+                let digest = hash::hash_file_streaming(&file, store.hash_algo.unwrap_or_default())
+                    .await
+                    .or_raise(|| InitError {
+                        message: "There was an error hashing the file".into(),
+                    })?;
+                println!("{}  {}", digest, path.display());
+            }
+            _ => {
+                hash::print_reader_hash(
+                    &mut std::io::stdin().lock(),
+                    &mut std::io::stdout(),
+                    store.hash_algo.unwrap_or_default(),
+                )
+                .or_raise(|| InitError {
+                    message: "Failed to hash stdin".into(),
+                })?;
+            }
+        },
+        Commands::Config { action } => {
+            let config_path = Path::new(config::CONFIG_PATH);
+            match action {
+                ConfigAction::Get { key } => match config.get(&key)? {
+                    Some(value) => println!("{}", value),
+                    None => std::process::exit(1),
+                },
+                ConfigAction::Set { key, values } => {
+                    let mut config = config;
+                    config.set(&key, &values)?;
+                    config.save(config_path)?;
+                    println!("{} updated in {}", key, config_path.display());
+                }
+            }
+        }
+        Commands::Freeze { unfreeze } => {
+            let mut db = Database::load(&store.db_path, &store).await?;
+            db.set_frozen(!unfreeze, &store.db_path)
+                .await
+                .or_raise(|| InitError {
+                    message: "Failed to save database".into(),
+                })?;
+            if unfreeze {
+                println!("Database unfrozen, changes are allowed again");
+            } else {
+                println!("Database frozen, the baseline can no longer be modified");
+            }
+        }
+        Commands::Export {
+            out,
+            canonical,
+            sort_output,
+            split_by_dir,
+        } => {
+            let mut db = Database::load(&store.db_path, &store).await?;
+            db.absolutize_paths();
+            db.sort_files(sort_output);
+            if let (true, Some(out)) = (split_by_dir, &out) {
+                std::fs::create_dir_all(out).or_raise(|| InitError {
+                    message: format!("Failed to create {}", out.display()),
+                })?;
+                for (name, part) in db.split_by_top_dir() {
+                    let part_path = out.join(format!("{}.json", name));
+                    compio::fs::write(&part_path, export_json(&part, canonical)?)
+                        .await
+                        .0
+                        .or_raise(|| InitError {
+                            message: format!("Failed to write export to {}", part_path.display()),
+                        })?;
+                    println!(
+                        "Exported {} record(s) to {}",
+                        part.files.len(),
+                        part_path.display()
+                    );
+                }
+                return Ok(());
+            }
+            let json_data = export_json(&db, canonical)?;
+            match out {
+                Some(out) => {
+                    compio::fs::write(&out, json_data)
+                        .await
+                        .0
+                        .or_raise(|| InitError {
+                            message: format!("Failed to write export to {}", out.display()),
+                        })?;
+                    println!("Exported {} record(s) to {}", db.files.len(), out.display());
+                }
+                None => println!("{}", json_data),
+            }
+        }
+        Commands::Restore { path } => {
+            let db = Database::load(&store.db_path, &store).await?;
+            let Some(record) = db.find_record(&db.tracked_path(&path), matching) else {
+                eprintln!(
+                    "{}",
+                    styled::error(&format!("{} is not tracked", path.display()))
+                );
+                std::process::exit(1);
+            };
+            let result = verify::check_record(&db.root_dir, record)
+                .await
+                .or_raise(|| InitError {
+                    message: "There was an error hashing the file".into(),
+                })?;
+            if result.status == verify::VerifyStatus::Ok {
+                println!("{} is intact, nothing to restore", path.display());
+                return Ok(());
+            }
+            objects::restore_object(&db.root_dir, &record.hash, record.algo, &path)
+                .await
+                .or_raise(|| InitError {
+                    message: format!("Failed to restore {}", path.display()),
+                })?;
+            println!(
+                "{}",
+                styled::success(&format!(
+                    "Restored {} ({}...)",
+                    path.display(),
+                    record.hash.short()
+                ))
+            );
+        }
+        Commands::Check => {
+            let db = Database::load(&store.db_path, &store).await?;
+            let problems = db.validate_integrity();
+            if problems.is_empty() {
+                println!(
+                    "{}",
+                    styled::success(&format!(
+                        "Database is consistent, {} record(s) checked",
+                        db.files.len()
+                    ))
+                );
+                return Ok(());
+            }
+            for problem in problems.iter() {
+                println!("{} {}", styled::error("problem:").bold(), problem);
+            }
+            eprintln!("==== {} problem(s) found ====", problems.len());
+            std::process::exit(1);
+        }
+        Commands::Manifest { out } => {
+            let db = Database::load(&store.db_path, &store).await?;
+            let manifest = database::sha256sum_manifest(&db).or_raise(|| InitError {
+                message: "Failed to build the manifest".into(),
+            })?;
+            match out {
+                Some(out) => {
+                    compio::fs::write(&out, manifest)
+                        .await
+                        .0
+                        .or_raise(|| InitError {
+                            message: format!("Failed to write manifest to {}", out.display()),
+                        })?;
+                    println!("Wrote {} record(s) to {}", db.files.len(), out.display());
+                }
+                None => std::io::stdout()
+                    .write_all(&manifest)
+                    .or_raise(|| InitError {
+                        message: "Failed to write manifest to stdout".into(),
+                    })?,
+            }
+        }
+        Commands::Watch { debounce } => {
+            let db = Database::load(&store.db_path, &store).await?;
+            let watched = watch::WatchedRecords::new(&db.root_dir, &db.files);
+            let (events, unwatched) =
+                watch::watch_directories(&watched.directories()).or_raise(|| InitError {
+                    message: "Failed to watch the tracked files".into(),
+                })?;
+            let mut debouncer = watch::Debouncer::new(Duration::from_millis(debounce));
+            let mut unwatched_files = 0;
+            for (directory, err) in unwatched.iter() {
+                eprintln!(
+                    "{} cannot watch {}: {}",
+                    styled::warn("warning:").bold(),
+                    directory.display(),
+                    err
+                );
+                for record in watched.in_directory(directory) {
+                    let result = verify::FileResult {
+                        path: record.path.clone(),
+                        status: VerifyStatus::Missing,
+                        expected: record.hash.clone(),
+                        actual: None,
+                        changed_blocks: Vec::new(),
+                    };
+                    print!("{}", verify::render_result(record, &result, None));
+                    unwatched_files += 1;
+                }
+            }
+            println!(
+                "Watching {} tracked file(s), press Ctrl-C to stop",
+                db.files.len() - unwatched_files
+            );
+            loop {
+                loop {
+                    match events.try_recv() {
+                        Ok((location, at)) => {
+                            watched.dispatch(&mut debouncer, location, at);
+                        }
+                        Err(TryRecvError::Empty) => break,
+                        Err(TryRecvError::Disconnected) => {
+                            return Err(Exn::new(InitError {
+                                message: "The file watcher stopped".into(),
+                            }));
+                        }
+                    }
+                }
+                for location in debouncer.ready(Instant::now()) {
+                    let Some(record) = watched.get(&location) else {
+                        continue;
+                    };
+                    let (_, result) = verify::verify_record(&db.root_dir, record).await;
+                    print!("{}", verify::render_result(record, &result, None));
+                }
+                compio::time::sleep(WATCH_TICK).await;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Serializes `db` for `export`, as RFC 8785 canonical JSON or pretty-printed.
+fn export_json(db: &Database, canonical: bool) -> Result<String, Exn<InitError>> {
+    if canonical {
+        database::canonical_json(db)
+    } else {
+        serde_json::to_string_pretty(db).or_raise(|| errors::DatabaseError {
+            message: "Failed to serialize database to JSON".to_string(),
+        })
+    }
+    .or_raise(|| InitError {
+        message: "Failed to export the database".into(),
+    })
+}
+
+/// Verifies every tracked record for `status --check`.
+async fn check_all(db: &Database) -> Result<VerifyReport, Exn<InitError>> {
+    let records: Vec<_> = db.files.iter().collect();
+    verify::verify_records(&db.root_dir, &records)
+        .await
+        .or_raise(|| InitError {
+            message: "There was an error hashing the file".into(),
+        })
+}
+
+/// Writes `report` as the JSON artifact requested with `verify --report`.
+async fn write_report(report: &VerifyReport, path: &Path) -> Result<(), Exn<InitError>> {
+    report.write(path).await.or_raise(|| InitError {
+        message: format!("Failed to write verification report to {}", path.display()),
+    })
+}
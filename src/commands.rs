@@ -1,6 +1,15 @@
 use std::path::PathBuf;
 
+use chrono::{DateTime, Utc};
 use clap::{Parser, Subcommand};
+use clap_complete::engine::ArgValueCompleter;
+
+use crate::completions::complete_tracked_path;
+use crate::hash::HashAlgorithm;
+use crate::models::{GroupBy, OutputFormat, SortOrder, parse_max_age, parse_since};
+use crate::paths::{PathMatch, Rebase};
+use crate::styled::ColorChoice;
+use crate::verify;
 
 /// The top-level command-line interface structure.
 #[derive(Debug, Parser)]
@@ -9,6 +18,46 @@ pub struct Cli {
     /// The subcommand to execute
     #[command(subcommand)]
     pub command: Commands,
+    /// Database file to use instead of `.tamashii.json` in the current directory
+    #[arg(long, global = true, value_name = "PATH")]
+    pub db: Option<PathBuf>,
+    /// Refuse to load a database readable by other users, and save it as 0600 (Unix)
+    #[arg(long, global = true)]
+    pub strict_permissions: bool,
+    /// Never write the database; commands that modify it refuse to run
+    #[arg(long, global = true)]
+    pub readonly: bool,
+    /// Restore a corrupt database from its newest valid `.bak` backup
+    #[arg(long, global = true)]
+    pub recover: bool,
+    /// Digest algorithm for a new database; an existing one must already use it (overrides `algo` in tamashii.toml)
+    #[arg(long, global = true, value_name = "ALGO")]
+    pub algo: Option<HashAlgorithm>,
+    /// Skip fsyncing the database on save (faster, but not crash-durable)
+    #[arg(long, global = true)]
+    pub no_durable: bool,
+    /// Write a `.sha256` sidecar with the hash of the database on every save
+    #[arg(long, global = true)]
+    pub write_checksum: bool,
+    /// Refuse to load the database unless it matches its `.sha256` sidecar
+    #[arg(long, global = true)]
+    pub check_checksum: bool,
+    /// Hide the progress line of recursive adds and `verify --all`, and make `verify`
+    /// print nothing but report through its exit code
+    #[arg(long, short, global = true)]
+    pub quiet: bool,
+    /// When to color output (`auto` honors NO_COLOR and whether stdout is a terminal)
+    #[arg(long, global = true, value_enum, default_value_t)]
+    pub color: ColorChoice,
+    /// Use this instant for every timestamp written (defaults to $SOURCE_DATE_EPOCH)
+    #[arg(long, global = true, value_name = "RFC3339")]
+    pub timestamp_override: Option<DateTime<Utc>>,
+    /// How paths given on the command line are matched against tracked records
+    #[arg(long, global = true, value_enum, default_value_t)]
+    pub path_match: PathMatch,
+    /// Match tracked paths without regard to letter case (same as `--path-match case-insensitive`)
+    #[arg(long, global = true)]
+    pub ignore_case: bool,
 }
 
 /// Available subcommands for the Tamashii CLI.
@@ -16,20 +65,316 @@ pub struct Cli {
 #[clap(author, version, about)]
 pub enum Commands {
     /// Initialize a new Tamashii database in the current directory
-    Init,
-    /// Add a file to be tracked for integrity
+    Init {
+        /// Replace an existing database with an empty one
+        #[arg(long)]
+        force: bool,
+    },
+    /// Add files to be tracked for integrity
     Add {
-        /// Path to the file to track
-        path: PathBuf,
+        /// Files (or, with --recursive, directories) to track
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+        /// Track every file under a directory
+        #[arg(long, short)]
+        recursive: bool,
+        /// Skip version-control metadata directories (.git, .hg, .svn, ...) (requires --recursive)
+        #[arg(long, requires = "recursive")]
+        exclude_vcs: bool,
+        /// Follow symlinks while walking, skipping any that lead back into a walked directory
+        /// (requires --recursive); by default symlinks are skipped and listed
+        #[arg(long, requires = "recursive")]
+        follow_symlinks: bool,
+        /// Replace the file with a hardlink to an identical tracked file (Unix only)
+        #[arg(long)]
+        dedup: bool,
+        /// Re-hash the file even if it is tracked with the same size and mtime
+        #[arg(long)]
+        force: bool,
+        /// Re-read and verify the newly added files after saving
+        #[arg(long)]
+        and_verify: bool,
+        /// Also store a digest of every block of this many bytes, so verify can
+        /// tell which blocks of a large file changed
+        #[arg(long, value_name = "BYTES", value_parser = clap::value_parser!(u64).range(1..))]
+        block_size: Option<u64>,
+        /// Keep a copy of each file's content under .tamashii/objects, so it can be restored
+        #[arg(long)]
+        backup: bool,
     },
     /// Verify the integrity of tracked files
     Verify {
         /// Optional path to a specific file to verify
+        #[arg(add = ArgValueCompleter::new(complete_tracked_path))]
         path: Option<PathBuf>,
         /// Verify all tracked files
         #[arg(long, short)]
         all: bool,
+        /// Write a JSON report of the run to this path
+        #[arg(long, value_name = "PATH")]
+        report: Option<PathBuf>,
+        /// Maximum bytes of file data buffered at once while hashing; each file
+        /// being hashed buffers 64 KiB (requires --all)
+        #[arg(long, value_name = "BYTES", requires = "all")]
+        mem_limit: Option<u64>,
+        /// Summarize results per directory (requires --all)
+        #[arg(long, requires = "all")]
+        by_dir: bool,
+        /// Skip the records sorted before this tracked path (requires --all)
+        #[arg(long, value_name = "PATH", requires = "all")]
+        start_from: Option<PathBuf>,
+        /// Skip this many records in path order (requires --all)
+        #[arg(
+            long,
+            value_name = "N",
+            requires = "all",
+            conflicts_with = "start_from"
+        )]
+        start_index: Option<usize>,
+        /// Invert the exit status: succeed only if files changed or went missing (requires --all)
+        #[arg(long, requires = "all")]
+        exit_on_change: bool,
+        /// Flag records whose baseline is older than this (e.g. 30d, 12h, 2w)
+        #[arg(long, value_name = "DURATION", value_parser = parse_max_age)]
+        max_age: Option<chrono::Duration>,
+        /// Print how long each file took to hash and the slowest files (requires --all)
+        #[arg(long, requires = "all")]
+        profile: bool,
+        /// Ask whether to accept, skip or quit on each changed file (requires --all)
+        #[arg(long, requires = "all")]
+        interactive: bool,
+        /// Verify in random order to spread disk I/O; the report stays sorted by path (requires --all)
+        #[arg(long, requires = "all")]
+        shuffle: bool,
+        /// Seed for --shuffle, to reproduce an order (random if omitted)
+        #[arg(long, requires = "shuffle")]
+        seed: Option<u64>,
+        /// Hash up to N files at once; output stays whole and in order (requires --all)
+        #[arg(long, value_name = "N", requires = "all", default_value_t = verify::default_jobs())]
+        jobs: usize,
+        /// Only warn about files that cannot be read instead of exiting with code 3
+        #[arg(long)]
+        ignore_unreadable: bool,
+        /// Re-hash every file, even those whose size and mtime match the record (requires --all)
+        #[arg(long, visible_alias = "force", requires = "all")]
+        full: bool,
+        /// Look for missing files under a new, untracked path with the same hash (requires --all)
+        #[arg(long, requires = "all")]
+        detect_renames: bool,
+    },
+    /// Stop tracking a file
+    Remove {
+        /// Tracked file to remove from the database
+        #[arg(add = ArgValueCompleter::new(complete_tracked_path))]
+        path: PathBuf,
+    },
+    /// Accept the current content of tracked files as their new baseline
+    #[command(visible_alias = "update")]
+    Rehash {
+        /// Tracked files to rehash
+        #[arg(
+            required_unless_present = "all",
+            add = ArgValueCompleter::new(complete_tracked_path)
+        )]
+        paths: Vec<PathBuf>,
+        /// Rehash every tracked file whose content has changed
+        #[arg(long, short, visible_alias = "changed-only", conflicts_with = "paths")]
+        all: bool,
     },
     /// View the status of the database and tracked files
-    Status,
+    Status {
+        /// Flag records whose baseline is older than this (e.g. 30d, 12h, 2w)
+        #[arg(long, value_name = "DURATION", value_parser = parse_max_age)]
+        max_age: Option<chrono::Duration>,
+        /// Redraw the status every INTERVAL (e.g. 5s, 1m) until interrupted; prints once when not a terminal
+        #[arg(long, value_name = "INTERVAL", value_parser = parse_max_age)]
+        watch: Option<chrono::Duration>,
+        /// Verify the tracked files and summarize the result in the status
+        #[arg(long)]
+        check: bool,
+        /// Only warn about files that cannot be read instead of exiting with code 3 (requires --check)
+        #[arg(long, requires = "check")]
+        ignore_unreadable: bool,
+        /// Print the status as a JSON object instead of colored text
+        #[arg(long, conflicts_with_all = ["watch", "check"])]
+        json: bool,
+        /// Only show records added or changed since this time (RFC 3339, or e.g. 1h, 3d, 2w ago)
+        #[arg(long, value_name = "TIME", value_parser = parse_since)]
+        since: Option<DateTime<Utc>>,
+    },
+    /// Print the full record of every tracked file
+    List {
+        /// Print the records as a JSON array, the same as `--output-format json`
+        #[arg(long, conflicts_with = "output_format")]
+        json: bool,
+        /// How to print the records
+        #[arg(long, value_enum, default_value_t)]
+        output_format: OutputFormat,
+        /// Only list records added or changed since this time (RFC 3339, or e.g. 1h, 3d, 2w ago)
+        #[arg(long, value_name = "TIME", value_parser = parse_since)]
+        since: Option<DateTime<Utc>>,
+    },
+    /// Show when records were added, updated and removed, oldest first
+    Log {
+        /// Only show the N most recent changes
+        #[arg(long, value_name = "N")]
+        limit: Option<usize>,
+    },
+    /// Show totals for the tracked files
+    Stats {
+        /// Break the totals down by extension or directory
+        #[arg(long, value_enum)]
+        group_by: Option<GroupBy>,
+    },
+    /// List tracked files with identical content, most wasted space first
+    Dupes {
+        /// Only report files of at least this many bytes
+        #[arg(long, value_name = "BYTES", default_value_t = 0)]
+        min_size: u64,
+    },
+    /// Compare the database with another database file, record by record
+    DiffDb {
+        /// The database file to compare against
+        other: PathBuf,
+        /// Print the differences as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Show every tracked file that changed, went missing or cannot be read
+    Diff {
+        /// Also list the files under the root directory that are not tracked
+        #[arg(long)]
+        untracked: bool,
+    },
+    /// Remove records for tracked files that no longer exist on disk
+    #[command(visible_alias = "prune")]
+    Gc {
+        /// Only report the records that would be removed
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Import the records of another database file that are not tracked yet
+    Import {
+        /// The database file to import from
+        file: PathBuf,
+        /// Move imported paths from OLD_ROOT to NEW_ROOT
+        #[arg(long, value_name = "OLD_ROOT=NEW_ROOT")]
+        rebase: Option<Rebase>,
+    },
+    /// Track the files listed in a `sha256sum` file, trusting its hashes
+    ImportSums {
+        /// The `sha256sum` file; relative paths in it are taken from its directory
+        file: PathBuf,
+    },
+    /// Print the hash of a file or of piped data without touching the database
+    Hash {
+        /// File to hash; omit it, or pass `-`, to hash everything read from stdin
+        path: Option<PathBuf>,
+        /// Hash everything read from stdin instead of a file
+        #[arg(long, conflicts_with = "path")]
+        stdin: bool,
+    },
+    /// Read or write the project defaults in `tamashii.toml`
+    Config {
+        /// Whether to read or write a key
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+    /// Lock the baseline so commands that would modify the database refuse to run
+    Freeze {
+        /// Unlock a frozen database so it can be modified again
+        #[arg(long)]
+        unfreeze: bool,
+    },
+    /// Export the database as JSON
+    Export {
+        /// File to write the export to (defaults to stdout)
+        #[arg(long, short)]
+        out: Option<PathBuf>,
+        /// Emit RFC 8785 canonical JSON, suitable for hashing or signing
+        #[arg(long)]
+        canonical: bool,
+        /// Order of the exported records (canonical output is always sorted by path)
+        #[arg(long, value_enum, default_value_t, conflicts_with = "canonical")]
+        sort_output: SortOrder,
+        /// Write one `<dir>.json` per top-level directory into the --out directory
+        #[arg(long, requires = "out")]
+        split_by_dir: bool,
+    },
+    /// Restore a changed or missing file from the copy kept by `add --backup`
+    Restore {
+        /// The tracked file to restore
+        #[arg(add = ArgValueCompleter::new(complete_tracked_path))]
+        path: PathBuf,
+    },
+    /// Check the database itself for duplicate IDs or paths, malformed hashes
+    /// and timestamps in the future; exits with code 1 if any are found
+    Check,
+    /// Write a `sha256sum`-compatible manifest of the tracked files
+    Manifest {
+        /// File to write the manifest to (defaults to stdout)
+        #[arg(long, short)]
+        out: Option<PathBuf>,
+    },
+    /// Re-verify tracked files whenever they change, until stopped with Ctrl-C
+    Watch {
+        /// How long a file must stay unchanged before it is verified, in milliseconds
+        #[arg(long, value_name = "MS", default_value_t = 300)]
+        debounce: u64,
+    },
+}
+
+impl Commands {
+    /// Returns `true` if the command writes to the database.
+    ///
+    /// Used to reject such commands up front under `--readonly`.
+    pub fn mutates_database(&self) -> bool {
+        match self {
+            Commands::Init { .. }
+            | Commands::Add { .. }
+            | Commands::Remove { .. }
+            | Commands::Rehash { .. }
+            | Commands::Import { .. }
+            | Commands::ImportSums { .. }
+            | Commands::Freeze { .. } => true,
+            Commands::Verify {
+                interactive,
+                detect_renames,
+                ..
+            } => *interactive || *detect_renames,
+            Commands::Gc { dry_run } => !*dry_run,
+            Commands::Status { .. }
+            | Commands::List { .. }
+            | Commands::Log { .. }
+            | Commands::Stats { .. }
+            | Commands::Dupes { .. }
+            | Commands::DiffDb { .. }
+            | Commands::Diff { .. }
+            | Commands::Hash { .. }
+            | Commands::Config { .. }
+            | Commands::Export { .. }
+            | Commands::Check
+            | Commands::Restore { .. }
+            | Commands::Manifest { .. }
+            | Commands::Watch { .. } => false,
+        }
+    }
+}
+
+/// Actions of the `config` subcommand.
+#[derive(Debug, Subcommand)]
+pub enum ConfigAction {
+    /// Print the value of a key (algo, db or ignore); exits with code 1 if it is not set
+    Get {
+        /// The key to read
+        key: String,
+    },
+    /// Set a key (algo, db or ignore); `ignore` takes any number of rules
+    Set {
+        /// The key to write
+        key: String,
+        /// The new value
+        #[arg(required = true, num_args = 1..)]
+        values: Vec<String>,
+    },
 }
@@ -0,0 +1,37 @@
+use crate::database::DB_PATH;
+use crate::models::Database;
+use clap_complete::engine::CompletionCandidate;
+use std::ffi::OsStr;
+use std::path::PathBuf;
+
+/// Returns the tracked paths that start with `prefix`, in database order.
+///
+/// # Arguments
+///
+/// * `db` - The database to draw paths from
+/// * `prefix` - The partially typed path
+pub fn tracked_paths_matching(db: &Database, prefix: &str) -> Vec<PathBuf> {
+    db.files
+        .iter()
+        .filter(|record| record.path.to_string_lossy().starts_with(prefix))
+        .map(|record| record.path.clone())
+        .collect()
+}
+
+/// Completes a path argument with the files tracked in the current database.
+///
+/// Completion runs synchronously inside the shell's completion hook, so the
+/// database is read with blocking I/O. A missing or unreadable database
+/// simply yields no candidates.
+pub fn complete_tracked_path(current: &OsStr) -> Vec<CompletionCandidate> {
+    let Ok(bytes) = std::fs::read(DB_PATH) else {
+        return vec![];
+    };
+    let Ok(db) = serde_json::from_slice::<Database>(&bytes) else {
+        return vec![];
+    };
+    tracked_paths_matching(&db, &current.to_string_lossy())
+        .into_iter()
+        .map(CompletionCandidate::new)
+        .collect()
+}
@@ -0,0 +1,137 @@
+use crate::database::DB_PATH;
+use crate::errors::InitError;
+use crate::files::{self, IgnorePattern};
+use crate::hash::HashAlgorithm;
+use exn::{Exn, ResultExt};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Default filename of the project config file, read from the current directory.
+pub const CONFIG_PATH: &str = "tamashii.toml";
+
+/// Keys that `tamashii config` can read and write.
+pub const CONFIG_KEYS: [&str; 3] = ["algo", "db", "ignore"];
+
+/// Project defaults read from `tamashii.toml`.
+///
+/// Every setting is optional. A flag given on the command line overrides the
+/// config value, which overrides the built-in default.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Digest algorithm for a new database, as if passed with `--algo`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub algo: Option<HashAlgorithm>,
+    /// Database file to use instead of `.tamashii.json`, as if passed with `--db`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub db: Option<PathBuf>,
+    /// Ignore rules applied to every walk, before those of `.tamashiiignore`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ignore: Vec<String>,
+}
+
+impl Config {
+    /// Reads the config file at `path`.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Config)` - The parsed config, or the defaults if `path` does not exist
+    /// * `Err(Exn<InitError>)` - If the file cannot be read or is not a valid config
+    pub fn load(path: &Path) -> Result<Self, Exn<InitError>> {
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(err) => {
+                return Err(Exn::new(InitError {
+                    message: format!("Failed to read {}: {}", path.display(), err),
+                }));
+            }
+        };
+        toml::from_str(&text).or_raise(|| InitError {
+            message: format!("{} is not a valid config file", path.display()),
+        })
+    }
+
+    /// Writes the config to `path`, replacing what was there.
+    pub fn save(&self, path: &Path) -> Result<(), Exn<InitError>> {
+        let text = toml::to_string_pretty(self).or_raise(|| InitError {
+            message: "Failed to serialize the config".into(),
+        })?;
+        std::fs::write(path, text).or_raise(|| InitError {
+            message: format!("Failed to write {}", path.display()),
+        })
+    }
+
+    /// Returns the value of `key` as text, or `None` if it is not set.
+    ///
+    /// The rules of `ignore` are returned one per line.
+    pub fn get(&self, key: &str) -> Result<Option<String>, Exn<InitError>> {
+        match key {
+            "algo" => Ok(self.algo.map(|algo| algo.to_string())),
+            "db" => Ok(self.db.as_ref().map(|db| db.display().to_string())),
+            "ignore" if self.ignore.is_empty() => Ok(None),
+            "ignore" => Ok(Some(self.ignore.join("\n"))),
+            _ => Err(unknown_key(key)),
+        }
+    }
+
+    /// Sets `key` to `values`.
+    ///
+    /// `ignore` takes any number of rules and replaces the current list; the
+    /// other keys take exactly one value, which is validated before it is stored.
+    pub fn set(&mut self, key: &str, values: &[String]) -> Result<(), Exn<InitError>> {
+        if key == "ignore" {
+            files::parse_ignore_patterns(&values.join("\n")).or_raise(|| InitError {
+                message: "Invalid ignore rule".into(),
+            })?;
+            self.ignore = values.to_vec();
+            return Ok(());
+        }
+        let [value] = values else {
+            return Err(Exn::new(InitError {
+                message: format!("{} takes exactly one value", key),
+            }));
+        };
+        match key {
+            "algo" => {
+                self.algo = Some(value.parse::<HashAlgorithm>().or_raise(|| InitError {
+                    message: format!("Invalid value for algo: {}", value),
+                })?)
+            }
+            "db" => self.db = Some(PathBuf::from(value)),
+            _ => return Err(unknown_key(key)),
+        }
+        Ok(())
+    }
+
+    /// Returns the database path to use: `flag`, else the config value, else `DB_PATH`.
+    pub fn db_path(&self, flag: Option<PathBuf>) -> PathBuf {
+        flag.or_else(|| self.db.clone())
+            .unwrap_or_else(|| PathBuf::from(DB_PATH))
+    }
+
+    /// Returns the algorithm for a new database: `flag`, else the config value.
+    ///
+    /// `None` leaves the choice to the database, which defaults to SHA-256.
+    pub fn hash_algo(&self, flag: Option<HashAlgorithm>) -> Option<HashAlgorithm> {
+        flag.or(self.algo)
+    }
+
+    /// Parses the `ignore` rules of the config.
+    pub fn ignore_patterns(&self) -> Result<Vec<IgnorePattern>, Exn<InitError>> {
+        files::parse_ignore_patterns(&self.ignore.join("\n")).or_raise(|| InitError {
+            message: format!("Invalid ignore rule in {}", CONFIG_PATH),
+        })
+    }
+}
+
+/// Builds the error for a key `tamashii config` does not know.
+fn unknown_key(key: &str) -> Exn<InitError> {
+    Exn::new(InitError {
+        message: format!(
+            "unknown config key {:?}, expected one of: {}",
+            key,
+            CONFIG_KEYS.join(", ")
+        ),
+    })
+}
@@ -1,7 +1,12 @@
 use crate::errors::DatabaseError;
-use crate::models::Database;
+use crate::hash::{self, HashAlgorithm};
+use crate::models::{Database, FileRecord, HexStirng, VERSION};
+use crate::paths::path_from_bytes;
+use chrono::{DateTime, Utc};
+use compio::io::AsyncWriteAtExt;
 use exn::{Exn, ResultExt};
-use serde_json;
+use serde::de::{self, DeserializeSeed, Deserializer, MapAccess, SeqAccess, Visitor};
+use serde_json::Value;
 use std::path::{Path, PathBuf};
 
 /// Default filename for the Tamashii database file.
@@ -10,16 +15,146 @@ use std::path::{Path, PathBuf};
 /// root directory information, and all tracked `FileRecord` entries.
 pub const DB_PATH: &str = ".tamashii.json";
 
-/// Serializes and writes the database to disk as pretty-printed JSON.
+/// Suffixes of files Tamashii keeps next to the database file.
 ///
-/// This function takes a reference to a `Database` instance, serializes it to
-/// pretty-printed JSON format, and writes it to the file specified by `DB_PATH`.
-/// If the file doesn't exist, it will be created. If it does exist, it will be
-/// overwritten.
+/// Covers the staging file used for atomic saves, backups, the lock file, and
+/// checksum/checkpoint/cache sidecars. Rotated backups (`.bak.1`, `.bak.2`, ...)
+/// are matched separately by `is_database_artifact`.
+const ARTIFACT_SUFFIXES: &[&str] = &[
+    "",
+    ".tmp",
+    ".bak",
+    ".lock",
+    ".sha256",
+    ".checkpoint",
+    ".cache",
+];
+
+/// Returns `true` if `path` is the database file or one of its sidecar files.
+///
+/// These files change whenever the database is saved, so tracking them would
+/// produce a mismatch on every verify. Matching is by file name, so the
+/// database files of nested projects are excluded too.
+///
+/// # Arguments
+///
+/// * `path` - The candidate path
+/// * `db_path` - Path of the active database file
+pub fn is_database_artifact(path: &Path, db_path: &Path) -> bool {
+    let (Some(name), Some(db_name)) = (path.file_name(), db_path.file_name()) else {
+        return false;
+    };
+    let (Some(name), Some(db_name)) = (name.to_str(), db_name.to_str()) else {
+        return false;
+    };
+    let Some(suffix) = name.strip_prefix(db_name) else {
+        return false;
+    };
+    ARTIFACT_SUFFIXES.contains(&suffix)
+        || suffix
+            .strip_prefix(".bak.")
+            .is_some_and(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()))
+}
+
+/// Settings controlling how the database file is read and written.
+///
+/// These are runtime choices made on the command line; they are carried on the
+/// loaded `Database` so that saving honors the same settings as loading.
+#[derive(Clone, Debug)]
+pub struct StoreOptions {
+    /// Refuse group/world-readable database files and save them as `0600` (Unix only)
+    pub strict_permissions: bool,
+    /// Fsync the database (and its directory on Unix) when saving
+    pub durable: bool,
+    /// Write a `<db>.sha256` sidecar with the hash of the saved database
+    pub write_checksum: bool,
+    /// Refuse to load a database whose `<db>.sha256` sidecar is missing or does not match
+    pub check_checksum: bool,
+    /// Fixed instant used for every timestamp instead of the current time
+    pub timestamp_override: Option<DateTime<Utc>>,
+    /// Treat every save as an error so the database file is never written
+    pub readonly: bool,
+    /// Restore a corrupt database from its newest valid backup
+    pub recover: bool,
+    /// Digest algorithm new databases use; a loaded database must already use it
+    pub hash_algo: Option<HashAlgorithm>,
+    /// Database file that `Database::save` writes to
+    pub db_path: PathBuf,
+}
+
+impl Default for StoreOptions {
+    fn default() -> Self {
+        Self {
+            strict_permissions: false,
+            durable: true,
+            write_checksum: false,
+            check_checksum: false,
+            timestamp_override: None,
+            readonly: false,
+            recover: false,
+            hash_algo: None,
+            db_path: PathBuf::from(DB_PATH),
+        }
+    }
+}
+
+impl StoreOptions {
+    /// Options for a database the user only asked to read, such as the
+    /// source of `import` or the other side of `diff-db`.
+    ///
+    /// None of the global store flags apply to it: it is never recovered,
+    /// checksum- or permission-checked, or written back.
+    pub fn read_only() -> Self {
+        Self {
+            readonly: true,
+            ..Self::default()
+        }
+    }
+}
+
+/// Reads the `SOURCE_DATE_EPOCH` reproducible-builds timestamp from the environment.
+///
+/// # Returns
+///
+/// * `Ok(Some(DateTime<Utc>))` - The instant named by the variable
+/// * `Ok(None)` - If the variable is unset or empty
+/// * `Err(Exn<DatabaseError>)` - If the variable is not a whole number of seconds
+pub fn source_date_epoch() -> Result<Option<DateTime<Utc>>, Exn<DatabaseError>> {
+    let value = match std::env::var("SOURCE_DATE_EPOCH") {
+        Ok(value) if !value.is_empty() => value,
+        _ => return Ok(None),
+    };
+    value
+        .parse::<i64>()
+        .ok()
+        .and_then(|secs| DateTime::from_timestamp(secs, 0))
+        .map(Some)
+        .ok_or_else(|| {
+            Exn::new(DatabaseError {
+                message: format!("SOURCE_DATE_EPOCH is not a valid timestamp: {}", value),
+            })
+        })
+}
+
+/// Serializes and writes the database to `path` as pretty-printed JSON.
+///
+/// The JSON is written to a temporary sibling file which is then renamed over
+/// `path`, so readers only ever see the old or the new database. With
+/// `durable` set (the default), the temporary file is fsynced before the
+/// rename and, on Unix, the containing directory is fsynced afterwards so the
+/// rename itself survives a power loss. Other platforms only get the file
+/// fsync. A write interrupted by a signal is retried a few times; since the
+/// same bytes are written each attempt, retrying is idempotent.
+///
+/// A new database file is created with mode `0600` on Unix, and so is every
+/// file written when the database was loaded with `strict_permissions`. With `write_checksum`, a
+/// `sha256sum`-compatible sidecar holding the hash of the written bytes is
+/// saved next to it.
 ///
 /// # Arguments
 ///
 /// * `db` - A reference to the `Database` to be written to disk
+/// * `path` - Destination of the database file
 ///
 /// # Returns
 ///
@@ -37,64 +172,685 @@ pub const DB_PATH: &str = ".tamashii.json";
 /// # Examples
 ///
 /// ```rust
+/// use std::path::Path;
+/// use tamashii::database::{DB_PATH, write_database_file};
+/// use tamashii::errors::DatabaseError;
 /// use tamashii::models::Database;
-/// use tamashii::database::write_database_file;
 ///
-/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
-/// let mut db = Database::new();
+/// # async fn example() -> Result<(), exn::Exn<DatabaseError>> {
+/// let db = Database::new().expect("an empty database");
 /// // ... populate database with file entries ...
 ///
-/// write_database_file(&db).await?;
+/// write_database_file(&db, Path::new(DB_PATH)).await?;
 /// println!("Database saved to .tamashii.json");
 /// # Ok(())
 /// # }
 /// ```
-pub async fn serialize_database(db: &Database) -> Result<(), Exn<DatabaseError>> {
-    let json_data = serde_json::to_string_pretty(db).or_raise(|| DatabaseError {
-        message: format!("Failed to serialize database to JSON"),
-    })?;
-    // creates .tamashii.json if it doesnt exist
-    compio::fs::write(PathBuf::from(DB_PATH), json_data)
+pub async fn write_database_file(db: &Database, path: &Path) -> Result<(), Exn<DatabaseError>> {
+    let mut json_data = Vec::new();
+    db.to_writer_at(&mut json_data, path)?;
+    let mut attempt = 1;
+    loop {
+        match write_atomically(path, json_data.clone(), &db.options, true).await {
+            Ok(()) => break,
+            Err(err)
+                if err.kind() == std::io::ErrorKind::Interrupted && attempt < SAVE_ATTEMPTS =>
+            {
+                attempt += 1;
+            }
+            Err(err) => {
+                return Err(Exn::new(DatabaseError {
+                    message: format!("Failed to write to database: {:?}", err),
+                }));
+            }
+        }
+    }
+    if db.options.write_checksum {
+        let checksum_path = checksum_path_for(path);
+        let line = format!(
+            "{}  {}\n",
+            hash::hash_bytes(&json_data, HashAlgorithm::Sha256),
+            path.file_name().unwrap_or_default().to_string_lossy()
+        );
+        write_atomically(&checksum_path, line.into_bytes(), &db.options, false)
+            .await
+            .or_raise(|| DatabaseError {
+                message: format!("Failed to write checksum to {}", checksum_path.display()),
+            })?;
+    }
+    Ok(())
+}
+
+/// Returns the path of the SHA-256 sidecar written next to the database at `path`.
+pub fn checksum_path_for(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".sha256");
+    path.with_file_name(name)
+}
+
+/// Compares `bytes` with the hash recorded in the `.sha256` sidecar of `path`.
+async fn check_checksum(path: &Path, bytes: &[u8]) -> Result<(), Exn<DatabaseError>> {
+    let checksum_path = checksum_path_for(path);
+    let sidecar = compio::fs::read(&checksum_path)
         .await
-        .0
-        .map_err(|err| {
-            let db_error = DatabaseError {
-                message: format!("Failed to write to database: {:?}", err),
-            };
-            Exn::new(db_error)
-        })
+        .or_raise(|| DatabaseError {
+            message: format!("Failed to read checksum {}", checksum_path.display()),
+        })?;
+    let expected = String::from_utf8_lossy(&sidecar)
+        .split_whitespace()
+        .next()
+        .map(str::to_ascii_lowercase)
+        .unwrap_or_default();
+    let actual = hash::hash_bytes(bytes, HashAlgorithm::Sha256);
+    if actual.0 != expected {
+        return Err(Exn::new(DatabaseError {
+            message: format!(
+                "{} does not match its checksum {}; the database may have been tampered with",
+                path.display(),
+                checksum_path.display()
+            ),
+        }));
+    }
+    Ok(())
+}
+
+/// Number of times an interrupted database write is attempted before giving up.
+const SAVE_ATTEMPTS: usize = 3;
+
+/// Returns the temporary path the database is staged at before being renamed into place.
+pub fn temp_path_for(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".tmp");
+    path.with_file_name(name)
+}
+
+/// Number of previous versions of the database kept as backups: `<db>.bak`
+/// plus the rotated `<db>.bak.1` and `<db>.bak.2`.
+const BACKUP_COUNT: usize = 3;
+
+/// Writes `data` to a temporary file and renames it over `path`.
+///
+/// The temporary file is created with mode `0600` on Unix before anything is
+/// written to it, so a new file is never readable by other users. It then
+/// takes the permissions of the file it replaces, unless `strict_permissions`
+/// is set, so a mode the user chose with `chmod` is kept. With `keep_backup`,
+/// the file being replaced is first kept as `<path>.bak`, see
+/// `rotate_backups`.
+async fn write_atomically(
+    path: &Path,
+    data: Vec<u8>,
+    options: &StoreOptions,
+    keep_backup: bool,
+) -> std::io::Result<()> {
+    let tmp_path = temp_path_for(path);
+    let result = async {
+        let mut file = create_private(&tmp_path).await?;
+        file.write_all_at(data, 0).await.0?;
+        if options.durable {
+            file.sync_all().await?;
+        }
+        file.close().await?;
+        if !options.strict_permissions
+            && let Ok(existing) = std::fs::metadata(path)
+        {
+            std::fs::set_permissions(&tmp_path, existing.permissions())?;
+        }
+        if keep_backup {
+            rotate_backups(path)?;
+        }
+        compio::fs::rename(&tmp_path, path).await?;
+        if options.durable {
+            sync_parent_dir(path)?;
+        }
+        Ok(())
+    }
+    .await;
+    if result.is_err() {
+        let _ = compio::fs::remove_file(&tmp_path).await;
+    }
+    result
+}
+
+/// Copies the database at `path` to `<path>.bak`, moving the older backups
+/// up to `<path>.bak.1`, `<path>.bak.2`, ... and dropping the oldest.
+///
+/// These are the backups `--recover` restores from. Nothing is done if
+/// there is no database at `path` yet.
+fn rotate_backups(path: &Path) -> std::io::Result<()> {
+    if !path.is_file() {
+        return Ok(());
+    }
+    let backup = |index: usize| {
+        let mut name = path.as_os_str().to_os_string();
+        name.push(".bak");
+        if index > 0 {
+            name.push(format!(".{}", index));
+        }
+        PathBuf::from(name)
+    };
+    for index in (1..BACKUP_COUNT).rev() {
+        let older = backup(index - 1);
+        if older.exists() {
+            std::fs::rename(&older, backup(index))?;
+        }
+    }
+    std::fs::copy(path, backup(0))?;
+    Ok(())
+}
+
+/// Flushes the directory entry of `path` so a completed rename is durable.
+#[cfg(unix)]
+fn sync_parent_dir(path: &Path) -> std::io::Result<()> {
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    std::fs::File::open(parent)?.sync_all()
+}
+
+#[cfg(not(unix))]
+fn sync_parent_dir(_path: &Path) -> std::io::Result<()> {
+    Ok(())
 }
 
 /// Reads and deserializes a JSON database file from disk.
 ///
 /// This function reads a JSON file from the specified path, validates that it's
-/// valid UTF-8, and deserializes it into a `Database` instance.
+/// valid UTF-8, strips a leading byte order mark if present, and deserializes
+/// it into a `Database` instance.
 ///
 /// # Arguments
 ///
 /// * `json_file` - A reference to the `Path` pointing to the JSON database file
+/// * `options` - Settings to check the file against and attach to the loaded database
 ///
 /// # Returns
 ///
 /// * `Ok(Database)` - Successfully parsed database instance
-/// * `Err(Exn<DatabaseError>)` - An error occurred during reading or parsing
-pub async fn parse_database_file(json_file: &Path) -> Result<Database, Exn<DatabaseError>> {
+/// * `Err(Exn<DatabaseError>)` - An error occurred during reading or parsing, the
+///   file is group/world-readable under `strict_permissions`, or it does not match
+///   its `.sha256` sidecar under `check_checksum`
+///
+/// A file that cannot be parsed is restored from its newest valid backup
+/// under `recover`; otherwise the error lists the backups that could be used.
+pub async fn parse_database_file(
+    json_file: &Path,
+    options: &StoreOptions,
+) -> Result<Database, Exn<DatabaseError>> {
+    check_permissions(json_file, options)?;
     let json_bytes = compio::fs::read(&json_file)
         .await
         .or_raise(|| DatabaseError {
-            message: format!("Unable to parse the json(db) file"),
+            message: "Unable to parse the json(db) file".to_string(),
         })?;
+    if options.check_checksum {
+        check_checksum(json_file, &json_bytes).await?;
+    }
+    let mut database = match Database::from_reader(json_bytes.as_slice()) {
+        Ok(database) => database,
+        Err(_) if options.recover => {
+            return recover_from_backup(json_file, options)
+                .await
+                .or_raise(|| DatabaseError {
+                    message: format!("{} is corrupt", json_file.display()),
+                });
+        }
+        Err(err) => {
+            let backups = backup_paths(json_file);
+            let guidance = match backups.first() {
+                Some(_) => format!(
+                    "backups found: {}; rerun with --recover to restore the newest valid one",
+                    backups
+                        .iter()
+                        .map(|backup| backup.display().to_string())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+                None => "no backups were found next to it".to_string(),
+            };
+            return Err(err.raise(DatabaseError {
+                message: format!("{} is corrupt; {}", json_file.display(), guidance),
+            }));
+        }
+    };
+    check_hash_algo(json_file, &database, options)?;
+    check_schema_version(json_file, &database)?;
+    database.options = options.clone();
+
+    Ok(database)
+}
+
+/// Refuses a database whose records were hashed with a different algorithm than requested.
+///
+/// Hashes from different algorithms never match, so comparing them would
+/// report every file as changed.
+fn check_hash_algo(
+    json_file: &Path,
+    database: &Database,
+    options: &StoreOptions,
+) -> Result<(), Exn<DatabaseError>> {
+    match options.hash_algo {
+        Some(requested) if requested != database.hash_algo => Err(Exn::new(DatabaseError {
+            message: format!(
+                "{} was hashed with {}, not {}; drop --algo or use --algo {}",
+                json_file.display(),
+                database.hash_algo,
+                requested,
+                database.hash_algo
+            ),
+        })),
+        _ => Ok(()),
+    }
+}
 
-    let json_str = std::str::from_utf8(&json_bytes).map_err(|err| {
-        Exn::new(DatabaseError {
-            message: format!("There was an error converting bytes to &str: {}", err),
+/// Refuses a database written with an incompatible schema.
+///
+/// Only the major part of `version` must match `VERSION`; minor and patch
+/// releases keep the schema readable.
+fn check_schema_version(json_file: &Path, database: &Database) -> Result<(), Exn<DatabaseError>> {
+    let major = |version: &str| version.split('.').next()?.trim().parse::<u64>().ok();
+    match (major(&database.version), major(VERSION)) {
+        (Some(found), Some(supported)) if found == supported => Ok(()),
+        _ => Err(Exn::new(DatabaseError {
+            message: format!(
+                "{} uses schema version {:?}, which this build cannot read (supported: {}.x)",
+                json_file.display(),
+                database.version,
+                VERSION.split('.').next().unwrap_or(VERSION)
+            ),
+        })),
+    }
+}
+
+/// Returns the existing backups of the database at `path`, newest first.
+///
+/// That is `<db>.bak` followed by the rotated `<db>.bak.1`, `<db>.bak.2`, ...
+pub fn backup_paths(path: &Path) -> Vec<PathBuf> {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let mut rotated: Vec<(u64, PathBuf)> = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+        .unwrap_or(Path::new("."))
+        .read_dir()
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|entry| {
+            let file_name = entry.file_name();
+            let index = file_name
+                .to_str()?
+                .strip_prefix(name.as_ref())?
+                .strip_prefix(".bak.")?
+                .parse()
+                .ok()?;
+            Some((index, path.with_file_name(file_name)))
         })
+        .collect();
+    rotated.sort();
+    let mut bak = path.as_os_str().to_os_string();
+    bak.push(".bak");
+    let bak = PathBuf::from(bak);
+    bak.exists()
+        .then_some(bak)
+        .into_iter()
+        .chain(rotated.into_iter().map(|(_, path)| path))
+        .collect()
+}
+
+/// Replaces the corrupt database at `path` with its newest backup that parses.
+///
+/// The restored database is written over `path` unless `readonly` is set.
+async fn recover_from_backup(
+    path: &Path,
+    options: &StoreOptions,
+) -> Result<Database, Exn<DatabaseError>> {
+    use colored::Colorize;
+    // backups have no checksum sidecar of their own
+    let backup_options = StoreOptions {
+        check_checksum: false,
+        recover: false,
+        ..options.clone()
+    };
+    for backup in backup_paths(path) {
+        let Ok(mut database) = Box::pin(parse_database_file(&backup, &backup_options)).await else {
+            continue;
+        };
+        database.options = options.clone();
+        if !options.readonly {
+            write_database_file(&database, path).await?;
+        }
+        eprintln!(
+            "{} recovered {} record(s) from {}",
+            crate::styled::warn("warning:").bold(),
+            database.files.len(),
+            backup.display()
+        );
+        return Ok(database);
+    }
+    Err(Exn::new(DatabaseError {
+        message: format!("No valid backup of {} to recover from", path.display()),
+    }))
+}
+
+/// Checks that the database file is not readable by group or others.
+///
+/// A readable file only produces a warning unless `strict_permissions` is set,
+/// in which case it is an error.
+#[cfg(unix)]
+fn check_permissions(path: &Path, options: &StoreOptions) -> Result<(), Exn<DatabaseError>> {
+    use colored::Colorize;
+    use std::os::unix::fs::PermissionsExt;
+    let Ok(meta) = std::fs::metadata(path) else {
+        // a missing file is reported by the read that follows
+        return Ok(());
+    };
+    let mode = meta.permissions().mode() & 0o777;
+    if mode & 0o044 == 0 {
+        return Ok(());
+    }
+    if options.strict_permissions {
+        return Err(Exn::new(DatabaseError {
+            message: format!(
+                "{} has mode {:o} and is readable by other users; run `chmod 600 {}`",
+                path.display(),
+                mode,
+                path.display()
+            ),
+        }));
+    }
+    eprintln!(
+        "{} {} has mode {:o} and is readable by other users",
+        crate::styled::warn("warning:").bold(),
+        path.display(),
+        mode
+    );
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn check_permissions(_path: &Path, _options: &StoreOptions) -> Result<(), Exn<DatabaseError>> {
+    Ok(())
+}
+
+/// Creates a fresh, empty file at `path` that only its owner can read and
+/// write (`0600`).
+///
+/// A file left at `path` by an interrupted write is removed first, since
+/// opening it would keep its old mode.
+async fn create_private(path: &Path) -> std::io::Result<compio::fs::File> {
+    match std::fs::remove_file(path) {
+        Err(err) if err.kind() != std::io::ErrorKind::NotFound => return Err(err),
+        _ => {}
+    }
+    let mut options = compio::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+    options.open(path).await
+}
+
+/// Smallest number of bytes a record takes up in a pretty-printed database file.
+///
+/// The shortest record has a 36-character UUID, a one-byte path, a 16-digit
+/// XXH3 hash, size 0 and a timestamp in whole seconds, and no optional
+/// fields. With its keys, indentation and the `,` before the next record it
+/// takes exactly this many bytes. IDs of older records are 32 plain digits and
+/// so 4 bytes shorter; a database of those is merely reserved a little short.
+const MIN_RECORD_BYTES: usize = 200;
+
+/// Estimates how many records a database file of `len` bytes holds, at most.
+pub fn estimated_record_count(len: usize) -> usize {
+    len / MIN_RECORD_BYTES
+}
+
+/// Deserializes a `Database` with its `files` vector allocated up front.
+///
+/// Loading a large database otherwise grows `files` one reallocation at a
+/// time. The seed reads `files` straight into a vector of `capacity` records
+/// and hands every other field to the derived `Deserialize` impl, so the two
+/// never disagree about the rest of the format.
+pub struct DatabaseSeed {
+    /// Number of records to allocate room for before reading `files`
+    pub capacity: usize,
+}
+
+impl<'de> DeserializeSeed<'de> for DatabaseSeed {
+    type Value = Database;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Database, D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de> Visitor<'de> for DatabaseSeed {
+    type Value = Database;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "a Tamashii database object")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Database, A::Error> {
+        let mut fields = serde_json::Map::new();
+        let mut files = None;
+        while let Some(key) = map.next_key::<String>()? {
+            if key == "files" {
+                files = Some(map.next_value_seed(FilesSeed(self.capacity))?);
+            } else {
+                fields.insert(key, map.next_value()?);
+            }
+        }
+        let files = files.ok_or_else(|| de::Error::missing_field("files"))?;
+        fields.insert("files".to_string(), Value::Array(Vec::new()));
+        let mut db: Database =
+            serde_json::from_value(Value::Object(fields)).map_err(de::Error::custom)?;
+        db.files = files;
+        Ok(db)
+    }
+}
+
+/// Deserializes the `files` array into a vector with room for the given number of records.
+struct FilesSeed(usize);
+
+impl<'de> DeserializeSeed<'de> for FilesSeed {
+    type Value = Vec<FileRecord>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de> Visitor<'de> for FilesSeed {
+    type Value = Vec<FileRecord>;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "a list of file records")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut files = Vec::with_capacity(self.0.max(seq.size_hint().unwrap_or_default()));
+        while let Some(record) = seq.next_element()? {
+            files.push(record);
+        }
+        Ok(files)
+    }
+}
+
+/// Writes the records as a manifest in the format of coreutils `sha256sum`.
+///
+/// Each line is `<hex>  <path>`, sorted by path, with paths as stored in the
+/// database, so `sha256sum -c` run from `root_dir` checks every file. A path
+/// holding a backslash or a line break is escaped and its line prefixed with
+/// `\`, as `sha256sum` itself does. Paths are written as raw bytes, not
+/// converted to UTF-8.
+///
+/// # Returns
+///
+/// * `Ok(Vec<u8>)` - The manifest, one line per record
+/// * `Err(Exn<DatabaseError>)` - If any record was hashed with another algorithm than SHA-256
+pub fn sha256sum_manifest(db: &Database) -> Result<Vec<u8>, Exn<DatabaseError>> {
+    if let Some(record) = db
+        .files
+        .iter()
+        .find(|record| record.algo != HashAlgorithm::Sha256)
+    {
+        return Err(Exn::new(DatabaseError {
+            message: format!(
+                "{} is hashed with {}; a sha256sum manifest needs every record to use sha256",
+                record.path.display(),
+                record.algo
+            ),
+        }));
+    }
+    let mut files: Vec<_> = db.files.iter().collect();
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    let mut out = Vec::new();
+    for record in files {
+        let path = record.path.as_os_str().as_encoded_bytes();
+        let escape = path.iter().any(|b| matches!(b, b'\\' | b'\n' | b'\r'));
+        if escape {
+            out.push(b'\\');
+        }
+        out.extend_from_slice(record.hash.to_string().as_bytes());
+        out.extend_from_slice(b"  ");
+        for &byte in path {
+            match byte {
+                b'\\' if escape => out.extend_from_slice(b"\\\\"),
+                b'\n' if escape => out.extend_from_slice(b"\\n"),
+                b'\r' if escape => out.extend_from_slice(b"\\r"),
+                byte => out.push(byte),
+            }
+        }
+        out.push(b'\n');
+    }
+    Ok(out)
+}
+
+/// Parses one line of a `sha256sum` file into its hash and path.
+///
+/// Accepts the text (`<hex>  <path>`) and binary (`<hex> *<path>`) forms,
+/// and undoes the escaping of lines that start with `\\`.
+///
+/// # Returns
+///
+/// * `Ok((HexStirng, PathBuf))` - The hash and the path as written
+/// * `Err(String)` - Why the line is not a `sha256sum` line
+pub fn parse_sha256sum_line(line: &[u8]) -> Result<(HexStirng, PathBuf), String> {
+    let (escaped, line) = match line.strip_prefix(b"\\") {
+        Some(rest) => (true, rest),
+        None => (false, line),
+    };
+    let (hex, rest) = line.split_at_checked(64).ok_or("line is too short")?;
+    let hex = std::str::from_utf8(hex).map_err(|_| "hash is not hex")?;
+    let hash = HexStirng::parse(hex).map_err(|_| "hash is not a 64-digit SHA-256 hex digest")?;
+    let name = match rest {
+        [b' ', b' ' | b'*', name @ ..] if !name.is_empty() => name,
+        _ => return Err("expected two spaces and a path after the hash".into()),
+    };
+    if !escaped {
+        return Ok((hash, path_from_bytes(name.to_vec())));
+    }
+    let mut path = Vec::with_capacity(name.len());
+    let mut bytes = name.iter();
+    while let Some(&byte) = bytes.next() {
+        if byte != b'\\' {
+            path.push(byte);
+            continue;
+        }
+        match bytes.next() {
+            Some(b'\\') => path.push(b'\\'),
+            Some(b'n') => path.push(b'\n'),
+            Some(b'r') => path.push(b'\r'),
+            _ => return Err("invalid escape in path".into()),
+        }
+    }
+    Ok((hash, path_from_bytes(path)))
+}
+
+/// Serializes the database using the JSON Canonicalization Scheme (RFC 8785).
+///
+/// Object keys are sorted, insignificant whitespace is dropped, and numbers use
+/// the ECMAScript formatting required by the RFC. Since record order is not
+/// meaningful, `files` is additionally sorted by path (then ID) so that two
+/// databases holding the same records serialize to identical bytes. The
+/// change `history` is left out for the same reason.
+///
+/// # Arguments
+///
+/// * `db` - A reference to the `Database` to serialize
+///
+/// # Returns
+///
+/// * `Ok(String)` - The canonical JSON text
+/// * `Err(Exn<DatabaseError>)` - If the database cannot be converted to JSON
+pub fn canonical_json(db: &Database) -> Result<String, Exn<DatabaseError>> {
+    let mut files: Vec<_> = db.files.iter().collect();
+    files.sort_by(|a, b| a.path.cmp(&b.path).then_with(|| a.id.cmp(&b.id)));
+    let mut value = serde_json::to_value(db).or_raise(|| DatabaseError {
+        message: "Failed to convert database to JSON".to_string(),
     })?;
-    let database: Database = serde_json::from_str(json_str).map_err(|err| {
-        Exn::new(DatabaseError {
-            message: format!("Invalid JSON format: {}", err),
-        })
+    value["files"] = serde_json::to_value(files).or_raise(|| DatabaseError {
+        message: "Failed to convert file records to JSON".to_string(),
     })?;
+    if let Some(fields) = value.as_object_mut() {
+        fields.remove("history");
+    }
+    let mut out = String::new();
+    write_canonical(&value, &mut out);
+    Ok(out)
+}
 
-    Ok(database)
+/// Appends the canonical form of `value` to `out`.
+fn write_canonical(value: &Value, out: &mut String) {
+    match value {
+        Value::Null | Value::Bool(_) | Value::String(_) => out.push_str(&value.to_string()),
+        Value::Number(number) => out.push_str(&canonical_number(number)),
+        Value::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_canonical(item, out);
+            }
+            out.push(']');
+        }
+        Value::Object(map) => {
+            // RFC 8785 orders keys by their UTF-16 code units
+            let mut entries: Vec<_> = map.iter().collect();
+            entries.sort_by(|(a, _), (b, _)| a.encode_utf16().cmp(b.encode_utf16()));
+            out.push('{');
+            for (i, (key, item)) in entries.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                out.push_str(&Value::String(key.clone()).to_string());
+                out.push(':');
+                write_canonical(item, out);
+            }
+            out.push('}');
+        }
+    }
+}
+
+/// Formats a JSON number the way ECMAScript's `Number.prototype.toString` does.
+fn canonical_number(number: &serde_json::Number) -> String {
+    if number.is_u64() || number.is_i64() {
+        return number.to_string();
+    }
+    let float = number.as_f64().unwrap_or_default();
+    if float == 0.0 {
+        return "0".to_string();
+    }
+    let magnitude = float.abs();
+    if (1e-6..1e21).contains(&magnitude) {
+        format!("{}", float)
+    } else {
+        let formatted = format!("{:e}", float);
+        match formatted.split_once('e') {
+            Some((mantissa, exponent)) if !exponent.starts_with('-') => {
+                format!("{}e+{}", mantissa, exponent)
+            }
+            _ => formatted,
+        }
+    }
 }
@@ -15,15 +15,19 @@ impl AllowedIO for &PathBuf {}
 ///
 /// * `T` - The type of the path, must implement `AllowedIO`
 pub struct IoError<T: AllowedIO> {
-    /// The path where the I/O error occurred
+    /// The path where the I/O error occurred, if the failing operation had one
     pub path: Option<T>,
     /// A descriptive error message
     pub message: String,
 }
 
+/// Prints the path, or `unknown path` when the failing operation did not have one.
 impl<T: AllowedIO + std::fmt::Debug> std::fmt::Display for IoError<T> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "IO error on {:?}: {}", self.path, self.message)
+        match &self.path {
+            Some(path) => write!(f, "IO error on {:?}: {}", path, self.message),
+            None => write!(f, "IO error on unknown path: {}", self.message),
+        }
     }
 }
 
@@ -52,6 +56,28 @@ impl fmt::Display for InitError {
     }
 }
 
+/// Why `Database::new` could not create an empty database.
+///
+/// Raised as the cause beneath the `InitError` that `Database::new` returns,
+/// so callers can tell it apart from other initialization failures.
+#[derive(Debug)]
+pub enum NewDatabaseError {
+    /// The current directory, which becomes the database root, could not be
+    /// determined, e.g. because it was deleted
+    CurrentDirUnavailable(String),
+}
+
+impl std::error::Error for NewDatabaseError {}
+impl fmt::Display for NewDatabaseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NewDatabaseError::CurrentDirUnavailable(reason) => {
+                write!(f, "Failed to get current directory: {}", reason)
+            }
+        }
+    }
+}
+
 /// Error encountered during file hashing operations.
 #[derive(Debug)]
 pub struct HashError {
@@ -67,6 +93,7 @@ impl fmt::Display for HashError {
 }
 
 /// Specific failure reasons for hashing operations.
+#[allow(dead_code)]
 #[derive(Debug)]
 pub enum HashErrorMessage {
     /// The computation of the hash failed
@@ -120,3 +147,22 @@ pub enum VerificationError {
     /// A hashing failure occurred during the verification process
     HashFailure,
 }
+
+impl std::error::Error for VerificationError {}
+
+impl fmt::Display for VerificationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VerificationError::HashMissMatched { path } => {
+                write!(
+                    f,
+                    "{}: content changed since it was tracked",
+                    path.display()
+                )
+            }
+            VerificationError::FileUntracked(path) => write!(f, "{}: not tracked", path.display()),
+            VerificationError::IoFailure => write!(f, "file could not be found"),
+            VerificationError::HashFailure => write!(f, "file could not be read and hashed"),
+        }
+    }
+}
@@ -1,8 +1,13 @@
+use crate::database::is_database_artifact;
 use crate::errors::IoError;
+use crate::objects;
+use chrono::{DateTime, Utc};
 use compio::fs::File;
 use compio::fs::Metadata;
 use exn::{Exn, ResultExt};
+use std::collections::HashSet;
 use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
 
 /// Opens a file asynchronously and wraps any I/O errors with contextual path information.
 ///
@@ -29,19 +34,21 @@ use std::path::{Path, PathBuf};
 ///
 /// # Examples
 ///
-/// ```rust
-/// use std::path::Path;
+/// ```rust,no_run
+/// use std::path::{Path, PathBuf};
+/// use tamashii::errors::IoError;
+/// use tamashii::files::get_file;
 ///
 /// #[compio::main]
-/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// async fn main() -> Result<(), exn::Exn<IoError<PathBuf>>> {
 ///     // Open an existing file
 ///     let path = Path::new("tamashii.db");
-///     let file = get_file(path).await?;
+///     let file = get_file(&path).await?;
 ///     println!("Successfully opened file: {:?}", path);
 ///     
 ///     // This will error if the file doesn't exist
 ///     let missing = Path::new("nonexistent.txt");
-///     match get_file(missing).await {
+///     match get_file(&missing).await {
 ///         Ok(_) => println!("File found"),
 ///         Err(e) => eprintln!("Error: {}", e), // Prints path info
 ///     }
@@ -81,11 +88,13 @@ pub async fn get_file<P: AsRef<Path>>(file_path: &P) -> Result<File, Exn<IoError
 ///
 /// # Examples
 ///
-/// ```rust
-/// use std::path::Path;
+/// ```rust,no_run
+/// use std::path::{Path, PathBuf};
+/// use tamashii::errors::IoError;
+/// use tamashii::files::{get_file, get_meta};
 ///
 /// #[compio::main]
-/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// async fn main() -> Result<(), exn::Exn<IoError<PathBuf>>> {
 ///     let path = Path::new("tamashii.db");
 ///     let file = get_file(&path).await?;
 ///     
@@ -114,3 +123,355 @@ pub async fn get_meta(file: &File) -> Result<Metadata, Exn<IoError<PathBuf>>> {
         message: format!("Failed to get metadata from file: {:?}", &file),
     })
 }
+
+/// Returns a creation or modification time reported for a file, if it is usable.
+///
+/// Some platforms and filesystems report no such time, or report the Unix
+/// epoch; both are treated as unknown so they never compare equal to a
+/// recorded time.
+pub fn reported_time(time: std::io::Result<SystemTime>) -> Option<DateTime<Utc>> {
+    match time {
+        Ok(time) if time != UNIX_EPOCH => Some(time.into()),
+        _ => None,
+    }
+}
+
+/// Replaces `duplicate` with a hardlink to `original` if both have identical content.
+///
+/// The contents of both files are compared byte-for-byte before anything is
+/// touched. The link is first created under a temporary name next to
+/// `duplicate` and then renamed over it, so `duplicate` is never left missing.
+///
+/// # Arguments
+///
+/// * `original` - The already-tracked file to link to
+/// * `duplicate` - The newly added file to replace with a hardlink
+///
+/// # Returns
+///
+/// * `Ok(true)` - `duplicate` now shares an inode with `original`
+/// * `Ok(false)` - The contents differ, nothing was changed
+/// * `Err(Exn<IoError<PathBuf>>)` - If reading, linking, or renaming fails
+#[cfg(unix)]
+pub async fn hardlink_duplicate(
+    original: &Path,
+    duplicate: &Path,
+) -> Result<bool, Exn<IoError<PathBuf>>> {
+    let original_bytes = compio::fs::read(original).await.or_raise(|| IoError {
+        path: Some(original.to_path_buf()),
+        message: "Failed to read file for deduplication".into(),
+    })?;
+    let duplicate_bytes = compio::fs::read(duplicate).await.or_raise(|| IoError {
+        path: Some(duplicate.to_path_buf()),
+        message: "Failed to read file for deduplication".into(),
+    })?;
+    if original_bytes != duplicate_bytes {
+        return Ok(false);
+    }
+    let tmp_link = duplicate.with_file_name(format!(
+        ".{}.tamashii-link",
+        duplicate
+            .file_name()
+            .map(|name| name.to_string_lossy())
+            .unwrap_or_default()
+    ));
+    compio::fs::hard_link(original, &tmp_link)
+        .await
+        .or_raise(|| IoError {
+            path: Some(tmp_link.clone()),
+            message: format!("Failed to hardlink {}", original.display()),
+        })?;
+    if let Err(err) = compio::fs::rename(&tmp_link, duplicate).await {
+        let _ = compio::fs::remove_file(&tmp_link).await;
+        return Err(Exn::new(IoError {
+            path: Some(duplicate.to_path_buf()),
+            message: format!("Failed to replace file with hardlink: {}", err),
+        }));
+    }
+    Ok(true)
+}
+
+/// Hardlink deduplication relies on Unix inode semantics and is unavailable elsewhere.
+#[cfg(not(unix))]
+pub async fn hardlink_duplicate(
+    _original: &Path,
+    duplicate: &Path,
+) -> Result<bool, Exn<IoError<PathBuf>>> {
+    Err(Exn::new(IoError {
+        path: Some(duplicate.to_path_buf()),
+        message: "Hardlink deduplication is only supported on Unix".into(),
+    }))
+}
+
+/// Names of version-control metadata directories pruned by `--exclude-vcs`.
+pub const VCS_DIRS: &[&str] = &[".git", ".hg", ".svn", ".bzr", "_darcs", "CVS", ".jj"];
+
+/// Name of the file listing paths a recursive add skips, read from the walked directory.
+pub const IGNORE_FILE: &str = ".tamashiiignore";
+
+/// One rule from a `.tamashiiignore` file.
+///
+/// Rules follow `.gitignore` syntax: a rule without a `/` matches at any
+/// depth, one with a `/` is relative to the walked directory, a trailing `/`
+/// matches only directories, and a leading `!` re-includes what an earlier
+/// rule excluded.
+#[derive(Debug, Clone)]
+pub struct IgnorePattern {
+    /// Compiled glob, matched against paths relative to the walked directory
+    matcher: globset::GlobMatcher,
+    /// Whether the rule re-includes paths (`!pattern`)
+    negated: bool,
+    /// Whether the rule only matches directories (`pattern/`)
+    dir_only: bool,
+}
+
+impl IgnorePattern {
+    /// Returns `true` if the rule applies to `relative`.
+    fn matches(&self, relative: &Path, is_dir: bool) -> bool {
+        (is_dir || !self.dir_only) && self.matcher.is_match(relative)
+    }
+}
+
+/// Parses `.tamashiiignore` rules, one per line.
+///
+/// Blank lines and lines starting with `#` are skipped.
+///
+/// # Returns
+///
+/// * `Ok(Vec<IgnorePattern>)` - The rules, in file order
+/// * `Err(Exn<IoError<PathBuf>>)` - If a line is not a valid glob
+pub fn parse_ignore_patterns(text: &str) -> Result<Vec<IgnorePattern>, Exn<IoError<PathBuf>>> {
+    let mut patterns = Vec::new();
+    for line in text.lines() {
+        let line = line.trim_end();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (negated, rule) = match line.strip_prefix('!') {
+            Some(rule) => (true, rule),
+            None => (false, line),
+        };
+        let (dir_only, rule) = match rule.strip_suffix('/') {
+            Some(rule) => (true, rule),
+            None => (false, rule),
+        };
+        let glob = match rule.strip_prefix('/') {
+            Some(anchored) => anchored.to_string(),
+            None if rule.contains('/') => rule.to_string(),
+            None => format!("**/{}", rule),
+        };
+        let matcher = globset::GlobBuilder::new(&glob)
+            .literal_separator(true)
+            .build()
+            .or_raise(|| IoError {
+                path: Some(PathBuf::from(IGNORE_FILE)),
+                message: format!("Invalid ignore pattern {:?}", line),
+            })?
+            .compile_matcher();
+        patterns.push(IgnorePattern {
+            matcher,
+            negated,
+            dir_only,
+        });
+    }
+    Ok(patterns)
+}
+
+/// Reads the `.tamashiiignore` rules of the directory `root`.
+///
+/// # Returns
+///
+/// * `Ok(Vec<IgnorePattern>)` - The rules, or none if `root` has no ignore file
+/// * `Err(Exn<IoError<PathBuf>>)` - If the file cannot be read or has an invalid rule
+pub fn load_ignore_patterns(root: &Path) -> Result<Vec<IgnorePattern>, Exn<IoError<PathBuf>>> {
+    let path = root.join(IGNORE_FILE);
+    match std::fs::read_to_string(&path) {
+        Ok(text) => parse_ignore_patterns(&text),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(err) => Err(Exn::new(IoError {
+            path: Some(path),
+            message: format!("Failed to read ignore file: {}", err),
+        })),
+    }
+}
+
+/// Returns `true` if `relative`, a path under the walked directory, is ignored.
+///
+/// The last rule that matches decides, so a later `!pattern` re-includes
+/// what an earlier rule excluded.
+pub fn is_ignored(patterns: &[IgnorePattern], relative: &Path, is_dir: bool) -> bool {
+    patterns
+        .iter()
+        .rev()
+        .find(|pattern| pattern.matches(relative, is_dir))
+        .is_some_and(|pattern| !pattern.negated)
+}
+
+/// Options controlling which entries `walk_files` visits.
+#[derive(Debug, Clone, Default)]
+pub struct WalkOptions {
+    /// Path of the active database, whose file and sidecars are always skipped
+    pub db_path: PathBuf,
+    /// Skip version-control metadata directories without descending into them
+    pub exclude_vcs: bool,
+    /// Skip entries matching these rules; ignored directories are not descended into
+    pub ignore: Vec<IgnorePattern>,
+    /// Follow symlinks to files and directories instead of skipping them
+    pub follow_symlinks: bool,
+}
+
+/// A depth-first iterator over the regular files under a directory.
+///
+/// Directories are read lazily, one entry at a time, and only the chain of
+/// open directory handles from the root to the current directory is held.
+/// Memory use therefore grows with the depth of the tree, never with the
+/// number of files in it, so trees with millions of entries can be walked
+/// in constant memory. Entries are yielded in the order the filesystem lists
+/// them.
+pub struct WalkFiles {
+    /// Which entries to skip
+    options: WalkOptions,
+    /// The root directory, until it is opened on the first call to `next`
+    root: Option<PathBuf>,
+    /// The root directory, which ignore rules are relative to
+    base: PathBuf,
+    /// Open directories from the root down to the one being read
+    stack: Vec<(PathBuf, std::fs::ReadDir)>,
+    /// Resolved paths of the directories entered so far, when following symlinks
+    visited: HashSet<PathBuf>,
+    /// Symlinks that were not followed
+    skipped: Vec<PathBuf>,
+}
+
+/// Walks the regular files under `root`.
+///
+/// Symlinks are skipped rather than followed unless `options.follow_symlinks`
+/// is set, and are listed by `WalkFiles::skipped`. When following them, a
+/// directory already entered through another path is skipped too, so a
+/// symlink cycle cannot make the walk run forever. The database file and its
+/// sidecars are always skipped.
+///
+/// # Arguments
+///
+/// * `root` - The directory to walk
+/// * `options` - Which entries to skip
+///
+/// # Returns
+///
+/// A `WalkFiles` iterator yielding each file's path, or an
+/// `Exn<IoError<PathBuf>>` for a directory or entry that cannot be read.
+pub fn walk_files(root: &Path, options: &WalkOptions) -> WalkFiles {
+    WalkFiles {
+        options: options.clone(),
+        root: Some(root.to_path_buf()),
+        base: root.to_path_buf(),
+        stack: Vec::new(),
+        visited: HashSet::new(),
+        skipped: Vec::new(),
+    }
+}
+
+impl WalkFiles {
+    /// Returns the symlinks passed over so far: all of them by default, or
+    /// the broken ones and those leading back into a walked directory when
+    /// following symlinks.
+    pub fn skipped(&self) -> &[PathBuf] {
+        &self.skipped
+    }
+
+    /// Opens `dir` and makes it the directory being read.
+    ///
+    /// When following symlinks, a directory that was already entered is
+    /// recorded as skipped instead.
+    fn descend(&mut self, dir: PathBuf) -> Result<(), Exn<IoError<PathBuf>>> {
+        if self.options.follow_symlinks {
+            let resolved = std::fs::canonicalize(&dir).or_raise(|| IoError {
+                path: Some(dir.clone()),
+                message: "Failed to resolve directory".into(),
+            })?;
+            if !self.visited.insert(resolved) {
+                self.skipped.push(dir);
+                return Ok(());
+            }
+        }
+        let entries = std::fs::read_dir(&dir).or_raise(|| IoError {
+            path: Some(dir.clone()),
+            message: "Failed to read directory".into(),
+        })?;
+        self.stack.push((dir, entries));
+        Ok(())
+    }
+}
+
+impl Iterator for WalkFiles {
+    type Item = Result<PathBuf, Exn<IoError<PathBuf>>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(root) = self.root.take()
+            && let Err(err) = self.descend(root)
+        {
+            return Some(Err(err));
+        }
+        loop {
+            let (dir, entries) = self.stack.last_mut()?;
+            let entry = match entries.next() {
+                Some(Ok(entry)) => entry,
+                Some(Err(err)) => {
+                    let dir = dir.clone();
+                    return Some(Err(Exn::new(IoError {
+                        path: Some(dir),
+                        message: format!("Failed to read directory entry: {}", err),
+                    })));
+                }
+                None => {
+                    self.stack.pop();
+                    continue;
+                }
+            };
+            let path = entry.path();
+            let mut file_type = match entry.file_type() {
+                Ok(file_type) => file_type,
+                Err(err) => {
+                    return Some(Err(Exn::new(IoError {
+                        path: Some(path),
+                        message: format!("Failed to read file type: {}", err),
+                    })));
+                }
+            };
+            let relative = path.strip_prefix(&self.base).unwrap_or(&path);
+            if file_type.is_symlink() {
+                // a broken link has no target to follow
+                match std::fs::metadata(&path) {
+                    Ok(target) if self.options.follow_symlinks => file_type = target.file_type(),
+                    _ => {
+                        if !is_ignored(&self.options.ignore, relative, false) {
+                            self.skipped.push(path);
+                        }
+                        continue;
+                    }
+                }
+            }
+            if is_ignored(&self.options.ignore, relative, file_type.is_dir()) {
+                continue;
+            }
+            if file_type.is_dir() {
+                let is_vcs = entry
+                    .file_name()
+                    .to_str()
+                    .is_some_and(|name| VCS_DIRS.contains(&name));
+                if self.options.exclude_vcs && is_vcs {
+                    continue;
+                }
+                // the object store only holds copies of tracked files
+                if entry.file_name() == objects::STORE_DIR {
+                    continue;
+                }
+                if let Err(err) = self.descend(path) {
+                    return Some(Err(err));
+                }
+            } else if file_type.is_file() && !is_database_artifact(&path, &self.options.db_path) {
+                return Some(Ok(path));
+            }
+        }
+    }
+}
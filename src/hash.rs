@@ -1,61 +1,333 @@
-use crate::errors::IoError;
-use crate::files;
+use crate::errors::{HashError, HashErrorMessage, IoError};
 use crate::models::HexStirng;
-use compio::{fs::File, io::AsyncReadAtExt};
+use compio::{fs::File, io::AsyncReadAt};
 use exn::{Exn, ResultExt};
+use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
+use std::str::FromStr;
 
-/// Reads all bytes from a file asynchronously.
+/// A digest algorithm that can be used to hash tracked files.
+///
+/// Parsed case-insensitively from and displayed as `sha256`, `sha512`,
+/// `blake3`, or `xxh3`, which is also its serialized form.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum HashAlgorithm {
+    /// SHA-256, the default
+    #[default]
+    Sha256,
+    /// SHA-512
+    Sha512,
+    /// BLAKE3
+    Blake3,
+    /// XXH3 (64-bit), fast but not cryptographic
+    Xxh3,
+}
+
+impl HashAlgorithm {
+    /// Every supported algorithm, in display order.
+    pub const ALL: [HashAlgorithm; 4] = [
+        HashAlgorithm::Sha256,
+        HashAlgorithm::Sha512,
+        HashAlgorithm::Blake3,
+        HashAlgorithm::Xxh3,
+    ];
+
+    /// Returns the canonical lowercase name of the algorithm.
+    pub fn name(&self) -> &'static str {
+        match self {
+            HashAlgorithm::Sha256 => "sha256",
+            HashAlgorithm::Sha512 => "sha512",
+            HashAlgorithm::Blake3 => "blake3",
+            HashAlgorithm::Xxh3 => "xxh3",
+        }
+    }
+
+    /// Returns the number of hex digits in a digest of this algorithm.
+    pub fn hex_len(&self) -> usize {
+        match self {
+            HashAlgorithm::Sha256 | HashAlgorithm::Blake3 => 64,
+            HashAlgorithm::Sha512 => 128,
+            HashAlgorithm::Xxh3 => 16,
+        }
+    }
+
+    /// Starts an incremental digest with this algorithm.
+    fn digester(&self) -> Digester {
+        match self {
+            HashAlgorithm::Sha256 => Digester::Sha256(sha2::Sha256::default()),
+            HashAlgorithm::Sha512 => Digester::Sha512(sha2::Sha512::default()),
+            HashAlgorithm::Blake3 => Digester::Blake3(Box::default()),
+            HashAlgorithm::Xxh3 => Digester::Xxh3(Box::default()),
+        }
+    }
+}
+
+/// The running state of a digest started by `HashAlgorithm::digester`.
+enum Digester {
+    /// SHA-256 state
+    Sha256(sha2::Sha256),
+    /// SHA-512 state
+    Sha512(sha2::Sha512),
+    /// BLAKE3 state, boxed because it is large
+    Blake3(Box<blake3::Hasher>),
+    /// XXH3 state, boxed because it is large
+    Xxh3(Box<xxhash_rust::xxh3::Xxh3>),
+}
+
+impl Digester {
+    /// Feeds the next chunk of data into the digest.
+    fn update(&mut self, bytes: &[u8]) {
+        use sha2::Digest;
+        match self {
+            Digester::Sha256(hasher) => hasher.update(bytes),
+            Digester::Sha512(hasher) => hasher.update(bytes),
+            Digester::Blake3(hasher) => {
+                hasher.update(bytes);
+            }
+            Digester::Xxh3(hasher) => hasher.update(bytes),
+        }
+    }
+
+    /// Completes the digest and hex-encodes it.
+    fn finish(self) -> HexStirng {
+        use sha2::Digest;
+        HexStirng(match self {
+            Digester::Sha256(hasher) => format!("{:x}", hasher.finalize()),
+            Digester::Sha512(hasher) => format!("{:x}", hasher.finalize()),
+            Digester::Blake3(hasher) => hasher.finalize().to_hex().to_string(),
+            Digester::Xxh3(hasher) => format!("{:016x}", hasher.digest()),
+        })
+    }
+}
+
+impl std::fmt::Display for HashAlgorithm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for HashAlgorithm {
+    type Err = HashError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|algo| algo.name().eq_ignore_ascii_case(input.trim()))
+            .ok_or_else(|| HashError {
+                message: HashErrorMessage::InvalidFormat(format!(
+                    "unknown hash algorithm {:?}, expected one of: {}",
+                    input,
+                    Self::ALL.map(|algo| algo.name()).join(", ")
+                )),
+            })
+    }
+}
+
+impl serde::Serialize for HashAlgorithm {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.name())
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for HashAlgorithm {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let raw = String::deserialize(deserializer)?;
+        raw.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// Size of the chunks a file is read in while it is hashed.
+pub const HASH_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Computes the hash of a byte slice.
 ///
 /// # Arguments
 ///
-/// * `file` - A reference to the opened `File` handle
+/// * `bytes` - The byte slice to hash
+/// * `algo` - The digest algorithm to use
 ///
 /// # Returns
 ///
-/// * `Ok(Vec<u8>)` - The binary content of the file
-/// * `Err(Exn<IoError<PathBuf>>)` - If metadata retrieval or file reading fails
-pub async fn read_file_bytes(file: &File) -> Result<Vec<u8>, Exn<IoError<PathBuf>>> {
-    let file_meta = files::get_meta(&file).await.or_raise(|| IoError {
-        path: None,
-        message: format!("Unable to retrieve meta data from: {:?}", &file),
-    })?;
-    let (_, buffer) = file
-        .read_to_end_at(Vec::with_capacity(file_meta.len() as usize), 0)
-        .await
-        .expect("Unable to read file");
-    Ok(buffer)
+/// * `HexStirng` - The hex-encoded hash
+pub fn hash_bytes(bytes: &[u8], algo: HashAlgorithm) -> HexStirng {
+    let mut digester = algo.digester();
+    digester.update(bytes);
+    digester.finish()
 }
 
-/// Computes the SHA-256 hash of a byte slice.
+/// Computes the hash of a file's content asynchronously.
+///
+/// The file is read in `HASH_CHUNK_SIZE` chunks that are fed to the hasher
+/// one at a time, so memory use stays flat however large the file is.
 ///
 /// # Arguments
 ///
-/// * `bytes` - The byte slice to hash
+/// * `file` - A reference to the opened `File` handle
+/// * `algo` - The digest algorithm to use
 ///
 /// # Returns
 ///
-/// * `HexStirng` - The hex-encoded SHA-256 hash
-pub fn hash_bytes(bytes: &[u8]) -> HexStirng {
-    use sha2::{Digest, Sha256};
-    let mut hasher = Sha256::new();
-    hasher.update(bytes);
-    HexStirng(format!("{:x}", hasher.finalize()))
+/// * `Ok(HexStirng)` - The hex-encoded hash
+/// * `Err(Exn<IoError<PathBuf>>)` - If reading the file fails
+pub async fn hash_file_streaming(
+    file: &File,
+    algo: HashAlgorithm,
+) -> Result<HexStirng, Exn<IoError<PathBuf>>> {
+    let mut digester = algo.digester();
+    let mut buffer = Vec::with_capacity(HASH_CHUNK_SIZE);
+    let mut position = 0;
+    loop {
+        buffer.clear();
+        let (result, filled) = file.read_at(buffer, position).await.into();
+        let read = result.or_raise(|| IoError {
+            path: None,
+            message: format!("Unable to read file: {:?}", &file),
+        })?;
+        if read == 0 {
+            break;
+        }
+        digester.update(&filled[..read]);
+        position += read as u64;
+        buffer = filled;
+    }
+    Ok(digester.finish())
+}
+
+/// Digests of the fixed-size blocks of a file, kept on records tracked with `add --block-size`.
+///
+/// A changed file can then be compared block by block to tell which parts
+/// of it differ, instead of only that it changed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BlockHashes {
+    /// Size of every block in bytes; the last block may be shorter
+    pub block_size: u64,
+    /// Digest of each block, in file order
+    pub hashes: Vec<HexStirng>,
+    /// Digest of the concatenated hex digests of the blocks, the root of a
+    /// one-level Merkle tree over them
+    pub root: HexStirng,
+}
+
+impl BlockHashes {
+    /// Builds the block list and its root from the digests of the blocks.
+    fn from_hashes(block_size: u64, hashes: Vec<HexStirng>, algo: HashAlgorithm) -> Self {
+        let joined: String = hashes.iter().map(|hash| hash.0.as_str()).collect();
+        Self {
+            block_size,
+            root: hash_bytes(joined.as_bytes(), algo),
+            hashes,
+        }
+    }
+
+    /// Returns the indexes of the blocks that differ between `self` and `current`.
+    ///
+    /// Blocks present in only one of them, because the file grew or shrank,
+    /// count as changed.
+    pub fn changed_blocks(&self, current: &BlockHashes) -> Vec<u64> {
+        if self.root.ct_eq(&current.root) {
+            return Vec::new();
+        }
+        let len = self.hashes.len().max(current.hashes.len());
+        (0..len)
+            .filter(
+                |&index| match (self.hashes.get(index), current.hashes.get(index)) {
+                    (Some(old), Some(new)) => !old.ct_eq(new),
+                    _ => true,
+                },
+            )
+            .map(|index| index as u64)
+            .collect()
+    }
 }
 
-/// Computes the SHA-256 hash of a file's content asynchronously.
+/// Computes the hash of a file and of each of its `block_size`-byte blocks in one read.
 ///
-/// This function reads the entire file content and computes its SHA-256 digest.
+/// The whole-file hash is the same `hash_file_streaming` returns, so a
+/// record tracked with blocks verifies like any other.
 ///
 /// # Arguments
 ///
 /// * `file` - A reference to the opened `File` handle
+/// * `algo` - The digest algorithm to use for the file and every block
+/// * `block_size` - Size of the blocks in bytes
 ///
 /// # Returns
 ///
-/// * `Ok(HexStirng)` - The hex-encoded SHA-256 hash
-/// * `Err(Exn<IoError<PathBuf>>)` - If reading the file fails
-pub async fn hash_file(file: &File) -> Result<HexStirng, Exn<IoError<PathBuf>>> {
-    let bytes = read_file_bytes(file).await?;
-    Ok(hash_bytes(&bytes))
+/// * `Ok((HexStirng, BlockHashes))` - The hash of the whole file and of its blocks
+/// * `Err(Exn<IoError<PathBuf>>)` - If reading the file fails or `block_size` is zero
+pub async fn hash_file_blocks(
+    file: &File,
+    algo: HashAlgorithm,
+    block_size: u64,
+) -> Result<(HexStirng, BlockHashes), Exn<IoError<PathBuf>>> {
+    if block_size == 0 {
+        return Err(Exn::new(IoError {
+            path: None,
+            message: "Block size must not be zero".into(),
+        }));
+    }
+    let mut digester = algo.digester();
+    let mut block = algo.digester();
+    let mut block_filled = 0;
+    let mut hashes = Vec::new();
+    let mut buffer = Vec::with_capacity(HASH_CHUNK_SIZE);
+    let mut position = 0;
+    loop {
+        buffer.clear();
+        let (result, filled) = file.read_at(buffer, position).await.into();
+        let read = result.or_raise(|| IoError {
+            path: None,
+            message: format!("Unable to read file: {:?}", &file),
+        })?;
+        if read == 0 {
+            break;
+        }
+        digester.update(&filled[..read]);
+        let mut chunk = &filled[..read];
+        while !chunk.is_empty() {
+            let take = (chunk.len() as u64).min(block_size - block_filled) as usize;
+            block.update(&chunk[..take]);
+            block_filled += take as u64;
+            chunk = &chunk[take..];
+            if block_filled == block_size {
+                hashes.push(std::mem::replace(&mut block, algo.digester()).finish());
+                block_filled = 0;
+            }
+        }
+        position += read as u64;
+        buffer = filled;
+    }
+    if block_filled > 0 {
+        hashes.push(block.finish());
+    }
+    Ok((
+        digester.finish(),
+        BlockHashes::from_hashes(block_size, hashes, algo),
+    ))
+}
+
+/// Hashes everything read from `input` and writes the hex digest to `output`.
+///
+/// Used by `hash --stdin`; nothing is read from or written to the filesystem.
+///
+/// # Arguments
+///
+/// * `input` - Where the data is read from, normally stdin
+/// * `output` - Where the digest line is written, normally stdout
+/// * `algo` - The digest algorithm to use
+///
+/// # Returns
+///
+/// * `Ok(HexStirng)` - The hex-encoded hash of the data
+/// * `Err(std::io::Error)` - If reading or writing fails
+pub fn print_reader_hash(
+    input: &mut impl std::io::Read,
+    output: &mut impl std::io::Write,
+    algo: HashAlgorithm,
+) -> std::io::Result<HexStirng> {
+    let mut bytes = Vec::new();
+    input.read_to_end(&mut bytes)?;
+    let digest = hash_bytes(&bytes, algo);
+    writeln!(output, "{}", digest)?;
+    Ok(digest)
 }
@@ -0,0 +1,48 @@
+//! Tamashii tracks the hashes of files in a JSON database and reports the
+//! ones whose content has changed since they were tracked.
+//!
+//! The `tamashii` binary is a thin wrapper around [`cli::run`]; the database
+//! and hashing logic can be used on their own:
+//!
+//! ```rust
+//! use tamashii::{Database, HashAlgorithm, InitError, hash_bytes};
+//!
+//! # fn main() -> Result<(), exn::Exn<InitError>> {
+//! let db = Database::new()?;
+//! assert!(db.files.is_empty());
+//!
+//! let hash = hash_bytes(b"hello", HashAlgorithm::Sha256);
+//! assert_eq!(hash.short(), "2cf24dba");
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod add;
+mod budget;
+pub mod cli;
+pub mod commands;
+mod completions;
+pub mod config;
+pub mod database;
+pub mod errors;
+pub mod files;
+pub mod hash;
+mod macros;
+pub mod models;
+pub mod objects;
+mod output;
+pub mod paths;
+mod profile;
+mod progress;
+mod styled;
+pub mod verify;
+mod watch;
+
+pub use errors::{
+    DatabaseError, FileError, HashError, HashErrorMessage, InitError, IoError, VerificationError,
+};
+pub use hash::{HashAlgorithm, hash_bytes, hash_file_streaming};
+pub use models::{Database, FileRecord, HexStirng};
+
+#[cfg(test)]
+mod test;
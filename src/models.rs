@@ -1,19 +1,87 @@
-use crate::database::{parse_database_file, serialize_database};
-use crate::errors::{DatabaseError, InitError};
+use crate::database::{
+    DatabaseSeed, StoreOptions, estimated_record_count, parse_database_file, write_database_file,
+};
+use crate::errors::{
+    DatabaseError, HashError, HashErrorMessage, InitError, IoError, NewDatabaseError,
+};
+use crate::files::{WalkOptions, walk_files};
+use crate::hash::{BlockHashes, HashAlgorithm};
+use crate::paths::{PathMatch, Rebase, is_under, lexical, paths_match, relative_to};
+use crate::styled;
+use crate::verify::{VerifyReport, VerifyStatus};
 use chrono::{DateTime, Utc};
-use exn::Exn;
+use exn::{Exn, ResultExt};
+use serde::de::DeserializeSeed;
 use serde::{Deserialize, Serialize};
 use std::hash::Hash;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 pub const VERSION: &str = "1.0.0";
 
 /// A wrapper around `String` representing a hex-encoded hash value.
 ///
 /// Provides custom `Hash`, `PartialEq`, and `Display` implementations
-/// tailored for hex strings.
-#[derive(Clone, Debug, Serialize, Deserialize)]
+/// tailored for hex strings. Hashes are always stored lowercase: `parse` and
+/// the deserializer normalize their input, so comparisons are effectively
+/// case-insensitive.
+#[derive(Clone, Debug, Serialize)]
 pub struct HexStirng(pub String);
 
+impl HexStirng {
+    /// Parses a hex string, normalizing it to lowercase.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - The hex digits, in any case
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(HexStirng)` - The lowercase hash
+    /// * `Err(Exn<HashError>)` - If `input` is empty or contains non-hex characters
+    pub fn parse(input: &str) -> Result<Self, Exn<HashError>> {
+        if input.is_empty() || !input.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(Exn::new(HashError {
+                message: HashErrorMessage::InvalidFormat(format!(
+                    "{:?} is not a hex-encoded hash",
+                    input
+                )),
+            }));
+        }
+        Ok(Self(input.to_ascii_lowercase()))
+    }
+
+    /// Returns the first 8 characters of the hash, for display.
+    ///
+    /// Shorter hashes, such as a truncated one from a hand-edited database,
+    /// are returned whole instead of panicking.
+    pub fn short(&self) -> &str {
+        self.0
+            .char_indices()
+            .nth(8)
+            .map_or(self.0.as_str(), |(end, _)| &self.0[..end])
+    }
+
+    /// Compares two hashes in time that depends only on their length.
+    ///
+    /// Unlike `==`, every byte is examined even after a mismatch, so the
+    /// comparison leaks no timing signal about where two hashes differ.
+    pub fn ct_eq(&self, other: &Self) -> bool {
+        let (left, right) = (self.0.as_bytes(), other.0.as_bytes());
+        left.len() == right.len()
+            && left
+                .iter()
+                .zip(right)
+                .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+                == 0
+    }
+}
+
+impl<'de> Deserialize<'de> for HexStirng {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let raw = String::deserialize(deserializer)?;
+        Self::parse(&raw).map_err(serde::de::Error::custom)
+    }
+}
+
 impl Hash for HexStirng {
     fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
         self.0.hash(state);
@@ -34,22 +102,150 @@ impl std::fmt::Display for HexStirng {
 
 /// A permanent record of a file stored in the database.
 ///
-/// Includes a unique identifier, path, hash, size, and creation timestamp.
+/// Includes a unique identifier, path, hash, size, and index timestamp.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct FileRecord {
-    /// Unique 128-bit identifier (hex-encoded)
+    /// Unique identifier, a random (version 4) UUID such as
+    /// `9b2f6c1e-4d3a-4f8b-a1c2-3e4d5f6a7b8c`; older records may hold 32 plain hex digits
     pub id: String,
-    /// Absolute path to the file
+    /// Path to the file, relative to `Database::root_dir` when it lies inside it
+    #[serde(with = "crate::paths::raw_path")]
     pub path: std::path::PathBuf,
     /// Content hash of the file
     pub hash: HexStirng,
-    /// Size of the file in bytes (up to 255 bytes for this specific implementation)
-    pub size: u8,
+    /// Algorithm `hash` was computed with; records written before it was
+    /// stored are SHA-256
+    #[serde(default)]
+    pub algo: HashAlgorithm,
+    /// Size of the file in bytes
+    pub size: u64,
     /// Time when the file was indexed
     pub time_stamp: DateTime<Utc>,
+    /// ID of the record this file was hardlinked to by `add --dedup`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub linked_to: Option<String>,
+    /// Modification time of the file when it was hashed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modified_at: Option<DateTime<Utc>>,
+    /// Digests of the file's blocks, for files tracked with `add --block-size`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blocks: Option<BlockHashes>,
+}
+
+impl FileRecord {
+    /// Returns how long ago the baseline for this record was taken.
+    pub fn age(&self) -> chrono::Duration {
+        Utc::now() - self.time_stamp
+    }
+
+    /// Returns `true` if the baseline is older than `max_age`.
+    pub fn is_stale(&self, max_age: chrono::Duration) -> bool {
+        self.age() > max_age
+    }
+
+    /// Describes a stale baseline, e.g. `stale baseline: notes.txt was tracked 40 day(s) ago`.
+    pub fn stale_notice(&self) -> String {
+        format!(
+            "stale baseline: {} was tracked {} day(s) ago",
+            self.path.display(),
+            self.age().num_days()
+        )
+    }
+}
+
+/// Returns `true` if nothing exists at `path`.
+///
+/// Paths that fail to stat for other reasons, such as permission denied, are
+/// not considered missing.
+async fn is_missing(path: &Path) -> bool {
+    matches!(
+        compio::fs::metadata(path).await,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound
+    )
+}
+
+/// Parses a `--max-age` duration such as `90s`, `45m`, `12h`, `30d` or `2w`.
+///
+/// # Returns
+///
+/// * `Ok(chrono::Duration)` - The parsed duration
+/// * `Err(String)` - If the number or unit is invalid
+pub fn parse_max_age(input: &str) -> Result<chrono::Duration, String> {
+    let input = input.trim();
+    let split = input
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(input.len());
+    let (amount, unit) = input.split_at(split);
+    let amount: i64 = amount.parse().map_err(|_| {
+        format!(
+            "expected a number followed by s, m, h, d or w, got `{}`",
+            input
+        )
+    })?;
+    let duration = match unit {
+        "s" => chrono::Duration::try_seconds(amount),
+        "m" => chrono::Duration::try_minutes(amount),
+        "h" => chrono::Duration::try_hours(amount),
+        "d" | "" => chrono::Duration::try_days(amount),
+        "w" => chrono::Duration::try_weeks(amount),
+        _ => return Err(format!("unknown unit `{}`, use s, m, h, d or w", unit)),
+    };
+    duration.ok_or_else(|| format!("duration `{}` is too large", input))
+}
+
+/// Parses a `--since` point in time: an RFC 3339 timestamp such as
+/// `2024-05-01T12:00:00Z`, or a duration before now in the form of
+/// `parse_max_age`, such as `1h`, `3d` or `2w`.
+///
+/// # Returns
+///
+/// * `Ok(DateTime<Utc>)` - The point in time
+/// * `Err(String)` - If the input is neither a timestamp nor a duration
+pub fn parse_since(input: &str) -> Result<DateTime<Utc>, String> {
+    if let Ok(timestamp) = DateTime::parse_from_rfc3339(input.trim()) {
+        return Ok(timestamp.with_timezone(&Utc));
+    }
+    let age = parse_max_age(input)
+        .map_err(|err| format!("expected an RFC 3339 timestamp or a duration: {}", err))?;
+    Utc::now()
+        .checked_sub_signed(age)
+        .ok_or_else(|| format!("duration `{}` is too large", input))
 }
 
-impl FileRecord {}
+/// Describes how long ago `dt` was, e.g. `just now`, `5m ago`, `3d ago` or `2y ago`.
+///
+/// Instants less than a minute old read `just now` and instants after the
+/// current time read `in the future`, so clock skew never yields a negative age.
+pub fn format_relative(dt: DateTime<Utc>) -> String {
+    let elapsed = Utc::now().signed_duration_since(dt);
+    if elapsed < chrono::Duration::zero() {
+        return "in the future".to_string();
+    }
+    let secs = elapsed.num_seconds();
+    let (amount, unit) = match secs {
+        0..60 => return "just now".to_string(),
+        60..3_600 => (secs / 60, "m"),
+        3_600..86_400 => (secs / 3_600, "h"),
+        86_400..31_536_000 => (secs / 86_400, "d"),
+        _ => (secs / 31_536_000, "y"),
+    };
+    format!("{}{} ago", amount, unit)
+}
+
+/// Formats a byte count with binary units, e.g. `512 B`, `1.5 KiB` or `2.0 GiB`.
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
 
 impl std::fmt::Display for FileRecord {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -61,7 +257,14 @@ impl std::fmt::Display for FileRecord {
             self.hash,
             self.size,
             formatted_time
-        )
+        )?;
+        if let Some(modified_at) = self.modified_at {
+            write!(f, "\nModified: {}", modified_at.format("%Y-%m-%d %H:%M:%S"))?;
+        }
+        if let Some(linked_to) = &self.linked_to {
+            write!(f, "\nLinked to: {}", linked_to)?;
+        }
+        Ok(())
     }
 }
 
@@ -77,10 +280,18 @@ pub struct FileRecordBuilder<'db> {
     pub path: Option<PathBuf>,
     /// Optional file hash
     pub hash: Option<HexStirng>,
+    /// Algorithm the hash was computed with, the database's by default
+    pub algo: HashAlgorithm,
     /// Optional file size
-    pub size: Option<u8>,
+    pub size: Option<u64>,
     /// Optional timestamp
     pub time_stamp: Option<DateTime<Utc>>,
+    /// Optional ID of the record this file is hardlinked to
+    pub linked_to: Option<String>,
+    /// Optional modification time of the file
+    pub modified_at: Option<DateTime<Utc>>,
+    /// Optional digests of the file's blocks
+    pub blocks: Option<BlockHashes>,
 }
 
 impl<'db> FileRecordBuilder<'db> {
@@ -91,12 +302,12 @@ impl<'db> FileRecordBuilder<'db> {
     /// * `path` - The absolute path of the file to record
     /// * `hash` - The computed hash of the file
     /// * `size` - The size of the file in bytes
-    /// * `time_stamp` - The creation or indexing timestamp
+    /// * `time_stamp` - The time the file was indexed
     pub fn with_fields(
         mut self,
         path: PathBuf,
         hash: HexStirng,
-        size: u8,
+        size: u64,
         time_stamp: DateTime<Utc>,
     ) -> Self {
         self.id = Some(Database::gen_id());
@@ -107,6 +318,36 @@ impl<'db> FileRecordBuilder<'db> {
         self
     }
 
+    /// Records that the file is a hardlink to the record with the given ID.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID of the already-tracked record sharing this file's content
+    pub fn with_link(mut self, id: String) -> Self {
+        self.linked_to = Some(id);
+        self
+    }
+
+    /// Records the file's modification time at the moment it was hashed.
+    ///
+    /// # Arguments
+    ///
+    /// * `modified_at` - The file's mtime
+    pub fn with_modified(mut self, modified_at: DateTime<Utc>) -> Self {
+        self.modified_at = Some(modified_at);
+        self
+    }
+
+    /// Records the digests of the file's blocks.
+    ///
+    /// # Arguments
+    ///
+    /// * `blocks` - The block digests computed along with the file's hash
+    pub fn with_blocks(mut self, blocks: BlockHashes) -> Self {
+        self.blocks = Some(blocks);
+        self
+    }
+
     /// Validates the current builder state.
     ///
     /// Ensures that all required fields (ID, path, hash, size, and timestamp)
@@ -150,27 +391,366 @@ impl<'db> FileRecordBuilder<'db> {
 
     /// Validates and appends the record to the database.
     ///
+    /// If a record with the same path is already tracked, it is updated in
+    /// place instead, keeping its id, so a path is never tracked twice.
+    ///
     /// # Returns
     ///
-    /// * `Ok(&FileRecord)` - A reference to the newly added record
-    /// * `Err(Exn<DatabaseError>)` - If validation or insertion fails
+    /// * `Ok(&FileRecord)` - A reference to the added or updated record
+    /// * `Err(Exn<DatabaseError>)` - If the database is frozen, or validation or insertion fails
     pub fn commit(self) -> Result<&'db FileRecord, Exn<DatabaseError>> {
+        self.db.ensure_unfrozen()?;
         self.validate()?;
 
         let record = FileRecord {
             id: self.id.unwrap(),
             path: self.path.unwrap(),
             hash: self.hash.unwrap(),
+            algo: self.algo,
             size: self.size.unwrap(),
             time_stamp: self.time_stamp.unwrap(),
+            linked_to: self.linked_to,
+            modified_at: self.modified_at,
+            blocks: self.blocks,
         };
 
-        self.db.files.push(record);
-        self.db.updated_at = chrono::Utc::now();
-        Ok(self.db.files.last().unwrap())
+        self.db.mark_dirty();
+        let existing = self
+            .db
+            .files
+            .iter()
+            .position(|tracked| tracked.path == record.path);
+        let action = match existing {
+            Some(_) => HistoryAction::Updated,
+            None => HistoryAction::Added,
+        };
+        self.db.log_change(action, &record.path, &record.hash);
+        match existing {
+            Some(index) => {
+                let tracked = &mut self.db.files[index];
+                tracked.hash = record.hash;
+                tracked.algo = record.algo;
+                tracked.size = record.size;
+                tracked.time_stamp = record.time_stamp;
+                tracked.linked_to = record.linked_to;
+                tracked.modified_at = record.modified_at;
+                tracked.blocks = record.blocks;
+                Ok(&self.db.files[index])
+            }
+            None => {
+                self.db.files.push(record);
+                Ok(self.db.files.last().unwrap())
+            }
+        }
+    }
+
+    /// Like `commit`, but returns a copy of the record so the database is free to use again.
+    ///
+    /// Lets a caller keep the new record while going on to `save` or otherwise
+    /// mutate the database, without first dropping a borrow of it.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(FileRecord)` - A clone of the newly added record
+    /// * `Err(Exn<DatabaseError>)` - If the database is frozen, or validation or insertion fails
+    pub fn commit_owned(self) -> Result<FileRecord, Exn<DatabaseError>> {
+        self.commit().cloned()
+    }
+}
+
+/// Key used to bucket records in `Database::group_totals`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum GroupBy {
+    /// Group by file extension; files without one fall under "(none)"
+    Extension,
+    /// Group by parent directory, relative to the database root
+    Directory,
+}
+
+/// Order in which records are emitted by exports.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum SortOrder {
+    /// Keep the database's own order
+    None,
+    /// Sort by path
+    #[default]
+    Path,
+    /// Sort by hash, then path
+    Hash,
+}
+
+/// How `list` prints the records, selected with `--output-format`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// One block of `Field: value` lines per record
+    #[default]
+    Plain,
+    /// A pretty-printed JSON array of the records
+    Json,
+    /// `path,hash,size,time_stamp` rows under a header, quoted as in RFC 4180
+    Csv,
+}
+
+/// Renders `records` in the given format, ending with a newline unless empty.
+///
+/// # Returns
+///
+/// * `Ok(String)` - The formatted records
+/// * `Err(Exn<DatabaseError>)` - If the records cannot be serialized to JSON
+pub fn format_records(
+    records: &[FileRecord],
+    format: OutputFormat,
+) -> Result<String, Exn<DatabaseError>> {
+    use std::fmt::Write;
+    let mut out = String::new();
+    match format {
+        OutputFormat::Plain => {
+            for (index, record) in records.iter().enumerate() {
+                if index > 0 {
+                    out.push('\n');
+                }
+                let _ = writeln!(out, "{}", record);
+            }
+        }
+        OutputFormat::Json => {
+            out = serde_json::to_string_pretty(records).or_raise(|| DatabaseError {
+                message: "Failed to serialize file records to JSON".to_string(),
+            })?;
+            out.push('\n');
+        }
+        OutputFormat::Csv => {
+            out.push_str("path,hash,size,time_stamp\n");
+            for record in records {
+                let _ = writeln!(
+                    out,
+                    "{},{},{},{}",
+                    csv_field(&record.path.to_string_lossy()),
+                    record.hash,
+                    record.size,
+                    record.time_stamp.to_rfc3339()
+                );
+            }
+        }
+    }
+    Ok(out)
+}
+
+/// Quotes a CSV field if it holds a comma, quote or line break, doubling any quotes.
+fn csv_field(field: &str) -> std::borrow::Cow<'_, str> {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\"")).into()
+    } else {
+        field.into()
+    }
+}
+
+/// Returns the parent directory of `path` relative to `root`, or `"."` for files
+/// directly in `root`. Paths outside `root` keep their own parent directory.
+pub fn directory_key(root: &Path, path: &Path) -> String {
+    let relative = path.strip_prefix(root).unwrap_or(path);
+    match relative.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.display().to_string(),
+        _ => ".".to_string(),
+    }
+}
+
+/// Name of the `export --split-by-dir` group holding files that are not inside a
+/// top-level directory of the root.
+pub const ROOT_GROUP: &str = "_root";
+
+/// Returns the top-level directory under `root` containing `path`, or
+/// `ROOT_GROUP` for files directly in `root` or outside it. Relative paths
+/// are taken to be relative to `root`.
+pub fn top_level_key(root: &Path, path: &Path) -> String {
+    let relative = match path.strip_prefix(root) {
+        Ok(relative) => relative,
+        Err(_) if path.is_relative() => path,
+        Err(_) => return ROOT_GROUP.to_string(),
+    };
+    let mut components = relative.components();
+    match (components.next(), components.next()) {
+        (Some(std::path::Component::Normal(dir)), Some(_)) => dir.to_string_lossy().into_owned(),
+        _ => ROOT_GROUP.to_string(),
+    }
+}
+
+/// Aggregated count and size of the records sharing one group key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GroupTotal {
+    /// The extension or directory shared by the group
+    pub key: String,
+    /// Number of records in the group
+    pub count: usize,
+    /// Combined size in bytes of the group's files
+    pub total_size: u64,
+}
+
+/// Tracked files with identical content, as listed by `dupes`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DuplicateGroup {
+    /// Hash shared by every file in the group
+    pub hash: HexStirng,
+    /// Size in bytes of each file
+    pub size: u64,
+    /// Paths of the identical files, sorted
+    pub paths: Vec<PathBuf>,
+}
+
+impl DuplicateGroup {
+    /// Returns the bytes that would be freed by keeping only one copy.
+    pub fn wasted(&self) -> u64 {
+        self.size
+            .saturating_mul(self.paths.len().saturating_sub(1) as u64)
+    }
+}
+
+/// A tracked path whose stored hash differs between two databases.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct HashChange {
+    /// Path tracked by both databases
+    pub path: PathBuf,
+    /// Hash stored in the base database
+    pub old_hash: HexStirng,
+    /// Hash stored in the other database
+    pub new_hash: HexStirng,
+}
+
+/// Record-level differences between two databases, keyed by path.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct DatabaseDiff {
+    /// Records only present in the other database
+    pub added: Vec<FileRecord>,
+    /// Records only present in the base database
+    pub removed: Vec<FileRecord>,
+    /// Paths present in both with different hashes
+    pub changed: Vec<HashChange>,
+}
+
+impl DatabaseDiff {
+    /// Returns `true` if the two databases track the same paths with the same hashes.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// How the tracked files differ from the files on disk, as printed by `diff`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DiffReport {
+    /// Tracked files whose hash matched
+    pub unchanged: Vec<PathBuf>,
+    /// Tracked files whose content changed
+    pub modified: Vec<PathBuf>,
+    /// Tracked files that could not be found
+    pub missing: Vec<PathBuf>,
+    /// Tracked files that exist but could not be read
+    pub unreadable: Vec<PathBuf>,
+    /// Files under `root_dir` that are not tracked, if they were looked for
+    pub untracked: Vec<PathBuf>,
+}
+
+impl DiffReport {
+    /// Returns `true` if every tracked file matched and no untracked file was found.
+    pub fn is_clean(&self) -> bool {
+        self.modified.is_empty()
+            && self.missing.is_empty()
+            && self.unreadable.is_empty()
+            && self.untracked.is_empty()
     }
 }
 
+/// What `Database::import_records` did with the imported records.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ImportSummary {
+    /// Records for paths that were not tracked yet
+    pub added: usize,
+    /// Paths whose tracked record had a different hash and was replaced
+    pub replaced: Vec<PathBuf>,
+    /// Records already tracked with the same hash, which were skipped
+    pub unchanged: usize,
+}
+
+/// The machine-readable status printed by `status --json`.
+#[derive(Debug, Serialize)]
+pub struct StatusSummary<'db> {
+    /// Schema version
+    pub version: &'db str,
+    /// Root directory of the tracked files
+    pub root_dir: &'db Path,
+    /// Database creation timestamp
+    pub created_at: DateTime<Utc>,
+    /// Database last update timestamp
+    pub updated_at: DateTime<Utc>,
+    /// Number of tracked files
+    pub file_count: usize,
+    /// Combined size in bytes of the tracked files
+    pub total_size: u64,
+    /// One entry per tracked file
+    pub files: Vec<StatusEntry<'db>>,
+}
+
+/// One tracked file in a `StatusSummary`.
+#[derive(Debug, Serialize)]
+pub struct StatusEntry<'db> {
+    /// Path of the file
+    pub path: &'db Path,
+    /// Content hash of the file
+    pub hash: &'db HexStirng,
+    /// Size of the file in bytes
+    pub size: u64,
+}
+
+/// What happened to a record, as listed by `log`.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum HistoryAction {
+    /// A new path started being tracked
+    Added,
+    /// A tracked path got a new hash
+    Updated,
+    /// A path stopped being tracked
+    Removed,
+}
+
+impl std::fmt::Display for HistoryAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            HistoryAction::Added => "added",
+            HistoryAction::Updated => "updated",
+            HistoryAction::Removed => "removed",
+        };
+        f.pad(name)
+    }
+}
+
+/// One change to the tracked records, appended to `Database::history`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct HistoryEntry {
+    /// When the change was made
+    pub timestamp: DateTime<Utc>,
+    /// What was done to the record
+    pub action: HistoryAction,
+    /// Path of the record
+    #[serde(with = "crate::paths::raw_path")]
+    pub path: PathBuf,
+    /// Hash of the record after the change, or before it for a removal
+    pub hash: HexStirng,
+}
+
+/// A point-in-time copy of a database's records, taken by `Database::snapshot`.
+#[derive(Debug, Clone)]
+pub struct DatabaseSnapshot {
+    /// Tracked file records at snapshot time
+    files: Vec<FileRecord>,
+    /// Change history at snapshot time
+    history: Vec<HistoryEntry>,
+    /// Creation timestamp at snapshot time
+    created_at: DateTime<Utc>,
+    /// Last update timestamp at snapshot time
+    updated_at: DateTime<Utc>,
+    /// Dirty flag at snapshot time
+    dirty: bool,
+}
+
 /// The main database structure storing file tracking information.
 ///
 /// Persisted as a JSON file, typically `.tamashii.json`.
@@ -179,6 +759,7 @@ pub struct Database {
     /// Schema version
     pub version: String,
     /// Root directory of the tracked files
+    #[serde(with = "crate::paths::raw_path")]
     pub root_dir: PathBuf,
     /// Database creation timestamp
     pub created_at: DateTime<Utc>,
@@ -186,71 +767,560 @@ pub struct Database {
     pub updated_at: DateTime<Utc>,
     /// List of tracked file records
     pub files: Vec<FileRecord>,
+    /// Whether the baseline is locked against changes by `tamashii freeze`
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub frozen: bool,
+    /// Algorithm every record's hash was computed with; databases written
+    /// before it was recorded are SHA-256
+    #[serde(default)]
+    pub hash_algo: HashAlgorithm,
+    /// Every record added, updated or removed, oldest first; only ever appended to
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub history: Vec<HistoryEntry>,
+    /// Whether the database file was saved in `root_dir` itself. Such a
+    /// database is re-rooted onto whatever directory it is loaded from, so a
+    /// copied or moved tree checks its own files. Written by
+    /// `write_database_file`, which knows where the file goes.
+    #[serde(default, skip_serializing)]
+    root_at_database: bool,
+    /// Runtime settings used when loading and saving, not persisted
+    #[serde(skip)]
+    pub options: StoreOptions,
+    /// Whether the in-memory state differs from what was loaded or last saved
+    #[serde(skip)]
+    dirty: bool,
 }
 
 impl Database {
     /// Prints the current status of the database and its tracked files.
     ///
+    /// See `status_frame` for what is shown.
+    pub async fn db_status(&self, max_age: Option<chrono::Duration>) {
+        print!("{}", self.status_frame(max_age, None));
+    }
+
+    /// Renders the status display printed by `status`, one refresh of `status --watch`.
+    ///
     /// If no files are tracked, it suggests the usage command.
-    /// Otherwise, it displays total files, creation date, last update,
-    /// and a summarized list of tracked files with their hash prefixes.
-    pub async fn db_status(&self) {
+    /// Otherwise, it displays total files, total size, creation date, last
+    /// update, and a summarized list of tracked files with their hash prefixes.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_age` - Flag records whose baseline is older than this
+    /// * `check` - Result of verifying the records, summarized under the header
+    pub fn status_frame(
+        &self,
+        max_age: Option<chrono::Duration>,
+        check: Option<&VerifyReport>,
+    ) -> String {
         use colored::Colorize;
+        use std::fmt::Write;
+        let mut frame = String::new();
         if self.files.is_empty() {
             let no_file =
-                "No files tracked yet. Use 'tamashii add <file>' to start tracking.".red();
-            println!("{}", no_file);
-        } else {
-            let db_stats = "======= Database Status =======".bold().green();
-            let status_display = format!(
-                "Total files: {}\nCreated: {}\nLast updated: {}",
-                self.files.len(),
-                self.created_at,
-                self.updated_at
+                styled::error("No files tracked yet. Use 'tamashii add <file>' to start tracking.");
+            let _ = writeln!(frame, "{}", no_file);
+            return frame;
+        }
+        let db_stats = styled::success("======= Database Status =======").bold();
+        let _ = writeln!(frame, "{}", db_stats);
+        let _ = writeln!(
+            frame,
+            "Total files: {}\nTotal size: {}\nCreated: {} ({})\nLast updated: {} ({})",
+            self.files.len(),
+            format_size(self.total_size()),
+            format_relative(self.created_at),
+            self.created_at,
+            format_relative(self.updated_at),
+            self.updated_at
+        );
+        if let Some(report) = check {
+            let line = format!(
+                "Check: {} ok, {} changed, {} missing, {} unreadable",
+                report.ok,
+                report.changed.len(),
+                report.missing.len(),
+                report.unreadable.len()
+            );
+            if !report.has_changes() && report.unreadable.is_empty() {
+                let _ = writeln!(frame, "{}", styled::success(&line));
+            } else {
+                let _ = writeln!(frame, "{}", styled::error(&line));
+            }
+        }
+
+        for file in self.files.iter() {
+            let _ = write!(
+                frame,
+                "File: {} Hash: ({}...)",
+                file.path.display(),
+                file.hash.short()
             );
-            println!("{}", db_stats);
-            println!("{}", status_display);
-
-            let files = self.files.iter();
-            for file in files {
-                let str_hash = file.hash.to_string();
-                let part = &str_hash[0..8];
-                println!("File: {} Hash: ({}...)", file.path.display(), part)
+            if let Some(modified_at) = file.modified_at {
+                let _ = write!(
+                    frame,
+                    " Modified: {}",
+                    modified_at.format("%Y-%m-%d %H:%M:%S")
+                );
+            }
+            let _ = writeln!(frame);
+            if max_age.is_some_and(|max_age| file.is_stale(max_age)) {
+                let _ = writeln!(frame, "  {}", styled::warn(&file.stale_notice()));
             }
-            let db_stats1 = "======= Database Status =======".bold().green();
-            println!("{}", db_stats1);
         }
+        let db_stats1 = styled::success("======= Database Status =======").bold();
+        let _ = writeln!(frame, "{}", db_stats1);
+        frame
     }
     /// Returns an existing database from the specified path or creates a new one if it doesn't exist.
     ///
     /// # Arguments
     ///
     /// * `path` - The file system path where the database file is located
+    /// * `options` - Settings applied to the loaded or created database
     ///
     /// # Returns
     ///
     /// * `Ok(Database)` - The loaded or newly created database instance
     /// * `Err(Exn<InitError>)` - If loading or initialization fails
-    pub async fn get_or_create_db(path: &str) -> Result<Database, Exn<InitError>> {
-        let path_ = std::path::Path::new(path);
-        if !path_.exists() {
-            Self::new()
+    pub async fn get_or_create_db(
+        path: &Path,
+        options: &StoreOptions,
+    ) -> Result<Database, Exn<InitError>> {
+        if !path.exists() {
+            let mut db = Self::new()?;
+            db.options = StoreOptions {
+                db_path: path.to_path_buf(),
+                ..options.clone()
+            };
+            db.hash_algo = options.hash_algo.unwrap_or_default();
+            let now = db.now();
+            db.created_at = now;
+            db.updated_at = now;
+            Ok(db)
         } else {
-            Self::load(&path_.to_path_buf()).await
+            Self::load(path, options).await
         }
     }
+    /// Creates an empty database and saves it to `path`.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Where to write the new database file
+    /// * `options` - Settings applied to the new database and its save
+    /// * `force` - Overwrite an existing database instead of refusing
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Database)` - The newly saved database
+    /// * `Err(Exn<InitError>)` - If a database already exists at `path` (without
+    ///   `force`, or when it is frozen), or the database cannot be saved
+    pub async fn init_at(
+        path: &Path,
+        options: &StoreOptions,
+        force: bool,
+    ) -> Result<Database, Exn<InitError>> {
+        if path.exists() {
+            if !force {
+                return Err(Exn::new(InitError {
+                    message: format!(
+                        "{} already exists; use `tamashii init --force` to replace it",
+                        path.display()
+                    ),
+                }));
+            }
+            if parse_database_file(path, options)
+                .await
+                .is_ok_and(|existing| existing.frozen)
+            {
+                return Err(Exn::new(InitError {
+                    message: format!(
+                        "{} is frozen; run `tamashii freeze --unfreeze` before replacing it",
+                        path.display()
+                    ),
+                }));
+            }
+        }
+        let mut db = Self::new()?;
+        db.options = StoreOptions {
+            db_path: path.to_path_buf(),
+            ..options.clone()
+        };
+        db.hash_algo = options.hash_algo.unwrap_or_default();
+        let now = db.now();
+        db.created_at = now;
+        db.updated_at = now;
+        db.save_to(path).await.or_raise(|| InitError {
+            message: format!("Failed to save {}", path.display()),
+        })?;
+        Ok(db)
+    }
+
+    /// Drops the records whose `time_stamp` is before `since`, for `--since`.
+    ///
+    /// Meant for commands that only display records: the database is not
+    /// marked dirty, so it must not be saved afterwards.
+    pub fn retain_since(&mut self, since: DateTime<Utc>) {
+        self.files.retain(|record| record.time_stamp >= since);
+    }
+
+    /// Returns the combined size in bytes of all tracked files.
+    pub fn total_size(&self) -> u64 {
+        self.files
+            .iter()
+            .fold(0u64, |total, file| total.saturating_add(file.size))
+    }
+
+    /// Buckets records by `group_by` and totals their counts and sizes.
+    ///
+    /// # Returns
+    ///
+    /// One entry per group, largest total size first (ties broken by key).
+    pub fn group_totals(&self, group_by: GroupBy) -> Vec<GroupTotal> {
+        let mut groups: std::collections::HashMap<String, GroupTotal> =
+            std::collections::HashMap::new();
+        for file in self.files.iter() {
+            let key = match group_by {
+                GroupBy::Extension => file
+                    .path
+                    .extension()
+                    .map(|ext| ext.to_string_lossy().into_owned())
+                    .unwrap_or_else(|| "(none)".to_string()),
+                GroupBy::Directory => directory_key(&self.root_dir, &file.path),
+            };
+            let group = groups.entry(key.clone()).or_insert(GroupTotal {
+                key,
+                count: 0,
+                total_size: 0,
+            });
+            group.count += 1;
+            group.total_size = group.total_size.saturating_add(file.size);
+        }
+        let mut totals: Vec<GroupTotal> = groups.into_values().collect();
+        totals.sort_by(|a, b| {
+            b.total_size
+                .cmp(&a.total_size)
+                .then_with(|| a.key.cmp(&b.key))
+        });
+        totals
+    }
+
+    /// Checks the records for problems the database format does not rule out.
+    ///
+    /// Finds duplicate IDs, paths tracked by more than one record, hashes that
+    /// are not hex of the length their algorithm produces, and timestamps in
+    /// the future. Files on disk are not looked at.
+    ///
+    /// # Returns
+    ///
+    /// One message per problem, in record order; empty if the database is consistent.
+    pub fn validate_integrity(&self) -> Vec<String> {
+        use std::collections::HashMap;
+        let now = Utc::now();
+        let mut problems = Vec::new();
+        let mut ids: HashMap<&str, &Path> = HashMap::new();
+        let mut paths: HashMap<&Path, &str> = HashMap::new();
+        for record in self.files.iter() {
+            let path = record.path.as_path();
+            if let Some(first) = ids.insert(&record.id, path) {
+                problems.push(format!(
+                    "duplicate id {}: {} and {}",
+                    record.id,
+                    first.display(),
+                    path.display()
+                ));
+            }
+            if let Some(first) = paths.insert(path, &record.id) {
+                problems.push(format!(
+                    "{} is tracked twice, by {} and {}",
+                    path.display(),
+                    first,
+                    record.id
+                ));
+            }
+            let hash = &record.hash.0;
+            if hash.len() != record.algo.hex_len() || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+                problems.push(format!(
+                    "{} has an invalid {} hash {:?}: expected {} hex digits",
+                    path.display(),
+                    record.algo,
+                    hash,
+                    record.algo.hex_len()
+                ));
+            }
+            if record.time_stamp > now {
+                problems.push(format!(
+                    "{} was tracked in the future, at {}",
+                    path.display(),
+                    record.time_stamp
+                ));
+            }
+            if let Some(modified_at) = record.modified_at.filter(|modified_at| *modified_at > now) {
+                problems.push(format!(
+                    "{} was modified in the future, at {}",
+                    path.display(),
+                    modified_at
+                ));
+            }
+        }
+        problems
+    }
+
+    /// Groups records with identical content, skipping files smaller than `min_size` bytes.
+    ///
+    /// # Returns
+    ///
+    /// One entry per hash tracked by two or more records, most wasted space
+    /// first (ties broken by hash).
+    pub fn duplicate_groups(&self, min_size: u64) -> Vec<DuplicateGroup> {
+        let mut groups: std::collections::HashMap<&str, DuplicateGroup> =
+            std::collections::HashMap::new();
+        for file in self.files.iter().filter(|file| file.size >= min_size) {
+            groups
+                .entry(file.hash.0.as_str())
+                .or_insert_with(|| DuplicateGroup {
+                    hash: file.hash.clone(),
+                    size: file.size,
+                    paths: Vec::new(),
+                })
+                .paths
+                .push(file.path.clone());
+        }
+        let mut duplicates: Vec<DuplicateGroup> = groups
+            .into_values()
+            .filter(|group| group.paths.len() > 1)
+            .collect();
+        for group in duplicates.iter_mut() {
+            group.paths.sort();
+        }
+        duplicates.sort_by(|a, b| {
+            b.wasted()
+                .cmp(&a.wasted())
+                .then_with(|| a.hash.0.cmp(&b.hash.0))
+        });
+        duplicates
+    }
+
+    /// Reorders the tracked records. Record order carries no meaning, so this
+    /// does not count as a modification.
+    pub fn sort_files(&mut self, order: SortOrder) {
+        match order {
+            SortOrder::None => {}
+            SortOrder::Path => self.files.sort_by(|a, b| a.path.cmp(&b.path)),
+            SortOrder::Hash => self
+                .files
+                .sort_by(|a, b| a.hash.0.cmp(&b.hash.0).then_with(|| a.path.cmp(&b.path))),
+        }
+    }
+
+    /// Compares this database's records with `other`'s, matching them by path.
+    ///
+    /// # Returns
+    ///
+    /// The records added in and removed from `other` relative to `self`, and
+    /// the paths whose hash changed, each sorted by path.
+    pub fn diff_records(&self, other: &Database) -> DatabaseDiff {
+        use std::collections::HashMap;
+        let ours: HashMap<&PathBuf, &FileRecord> =
+            self.files.iter().map(|file| (&file.path, file)).collect();
+        let theirs: HashMap<&PathBuf, &FileRecord> =
+            other.files.iter().map(|file| (&file.path, file)).collect();
+        let mut diff = DatabaseDiff::default();
+        for (path, record) in theirs.iter() {
+            match ours.get(path) {
+                None => diff.added.push((*record).clone()),
+                Some(old) if old.hash != record.hash => diff.changed.push(HashChange {
+                    path: (*path).clone(),
+                    old_hash: old.hash.clone(),
+                    new_hash: record.hash.clone(),
+                }),
+                Some(_) => {}
+            }
+        }
+        for (path, record) in ours.iter() {
+            if !theirs.contains_key(path) {
+                diff.removed.push((*record).clone());
+            }
+        }
+        diff.added.sort_by(|a, b| a.path.cmp(&b.path));
+        diff.removed.sort_by(|a, b| a.path.cmp(&b.path));
+        diff.changed.sort_by(|a, b| a.path.cmp(&b.path));
+        diff
+    }
+
+    /// Classifies every tracked file by comparing it with its current content on disk.
+    ///
+    /// # Arguments
+    ///
+    /// * `untracked` - When set, also walk `root_dir` with these options and
+    ///   list the files that are not tracked
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(DiffReport)` - Each category sorted by path
+    /// * `Err(Exn<IoError<PathBuf>>)` - If an opened file cannot be hashed or a
+    ///   directory cannot be walked
+    pub async fn diff_against_disk(
+        &self,
+        untracked: Option<&WalkOptions>,
+    ) -> Result<DiffReport, Exn<IoError<PathBuf>>> {
+        let records: Vec<&FileRecord> = self.files.iter().collect();
+        let verified = crate::verify::verify_records(&self.root_dir, &records).await?;
+        let mut report = DiffReport::default();
+        for result in verified.results {
+            let category = match result.status {
+                VerifyStatus::Ok => &mut report.unchanged,
+                VerifyStatus::Changed => &mut report.modified,
+                VerifyStatus::Missing => &mut report.missing,
+                VerifyStatus::Unreadable => &mut report.unreadable,
+            };
+            category.push(result.path);
+        }
+        if let Some(walk) = untracked {
+            for path in walk_files(&self.root_dir, walk) {
+                let path = self.tracked_path(&path?);
+                if self.find_record(&path, PathMatch::Lexical).is_none() {
+                    report.untracked.push(path);
+                }
+            }
+        }
+        for category in [
+            &mut report.unchanged,
+            &mut report.modified,
+            &mut report.missing,
+            &mut report.unreadable,
+            &mut report.untracked,
+        ] {
+            category.sort();
+        }
+        Ok(report)
+    }
+
+    /// Returns the records `prune_missing` would remove, without removing them.
+    ///
+    /// Used by `gc --dry-run`.
+    pub async fn missing_records(&self) -> Vec<&FileRecord> {
+        let mut missing = Vec::new();
+        for record in self.files.iter() {
+            if is_missing(&self.resolve(&record.path)).await {
+                missing.push(record);
+            }
+        }
+        missing
+    }
+
+    /// Removes records whose files no longer exist on disk.
+    ///
+    /// Only files that are genuinely not found are pruned; records that fail
+    /// to stat for other reasons (e.g. permission denied) are kept. `updated_at`
+    /// is bumped only if at least one record was removed.
+    ///
+    /// # Returns
+    ///
+    /// The records that were removed, in their original order.
+    pub async fn prune_missing(&mut self) -> Vec<FileRecord> {
+        let mut kept = Vec::with_capacity(self.files.len());
+        let mut removed = Vec::new();
+        let files = std::mem::take(&mut self.files);
+        for record in files {
+            if is_missing(&self.resolve(&record.path)).await {
+                removed.push(record);
+            } else {
+                kept.push(record);
+            }
+        }
+        self.files = kept;
+        for record in removed.iter() {
+            self.log_change(HistoryAction::Removed, &record.path, &record.hash);
+        }
+        if !removed.is_empty() {
+            self.mark_dirty();
+        }
+        removed
+    }
+
+    /// Serializes a summary of the database as pretty-printed JSON, for `status --json`.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(String)` - The JSON text
+    /// * `Err(Exn<DatabaseError>)` - If the summary cannot be serialized
+    pub fn status_json(&self) -> Result<String, Exn<DatabaseError>> {
+        let summary = StatusSummary {
+            version: &self.version,
+            root_dir: &self.root_dir,
+            created_at: self.created_at,
+            updated_at: self.updated_at,
+            file_count: self.files.len(),
+            total_size: self.total_size(),
+            files: self
+                .files
+                .iter()
+                .map(|record| StatusEntry {
+                    path: &record.path,
+                    hash: &record.hash,
+                    size: record.size,
+                })
+                .collect(),
+        };
+        serde_json::to_string_pretty(&summary).or_raise(|| DatabaseError {
+            message: "Failed to serialize the status to JSON".to_string(),
+        })
+    }
+
+    /// Serializes the tracked records as a pretty-printed JSON array, for `list --json`.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(String)` - The JSON text
+    /// * `Err(Exn<DatabaseError>)` - If the records cannot be serialized
+    pub fn files_json(&self) -> Result<String, Exn<DatabaseError>> {
+        serde_json::to_string_pretty(&self.files).or_raise(|| DatabaseError {
+            message: "Failed to serialize file records to JSON".to_string(),
+        })
+    }
+
+    /// Stops tracking `path` by removing its record.
+    ///
+    /// `updated_at` is bumped only if a record was removed.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The tracked path to remove
+    /// * `matching` - How stored paths are compared with `path`
+    ///
+    /// # Returns
+    ///
+    /// The removed record, or `None` if `path` is not tracked.
+    pub fn remove_by_path(&mut self, path: &Path, matching: PathMatch) -> Option<FileRecord> {
+        let index = self
+            .files
+            .iter()
+            .position(|record| paths_match(&record.path, path, matching))?;
+        let removed = self.files.remove(index);
+        self.log_change(HistoryAction::Removed, &removed.path, &removed.hash);
+        self.mark_dirty();
+        Some(removed)
+    }
+
     /// Returns a new `FileRecordBuilder` associated with this database.
     ///
     /// The builder is used to create and validate `FileRecord` instances before
     /// adding them to the database.
     pub fn builder(&mut self) -> FileRecordBuilder<'_> {
+        let algo = self.hash_algo;
         FileRecordBuilder {
             db: self,
             id: None,
             path: None,
             hash: None,
+            algo,
             size: None,
             time_stamp: None,
+            linked_to: None,
+            modified_at: None,
+            blocks: None,
         }
     }
 
@@ -259,19 +1329,101 @@ impl Database {
     /// # Returns
     ///
     /// * `Ok(Self)` - Initialized database instance
-    /// * `Err(Exn<DatabaseError>)` - If the current directory cannot be determined
+    /// * `Err(Exn<InitError>)` - If the current directory cannot be determined;
+    ///   the cause beneath it is `NewDatabaseError::CurrentDirUnavailable`
     pub fn new() -> Result<Self, Exn<InitError>> {
         let current_dir = std::env::current_dir().map_err(|err| {
-            Exn::new(InitError {
-                message: format!("Failed to get current directory: {}", err),
+            Exn::new(NewDatabaseError::CurrentDirUnavailable(err.to_string())).raise(InitError {
+                message: "Cannot create a database without a root directory".into(),
             })
         })?;
         Ok(Self {
             version: VERSION.to_string(),
-            root_dir: PathBuf::from(current_dir),
+            root_dir: current_dir,
             created_at: Utc::now(),
             updated_at: Utc::now(),
             files: vec![],
+            frozen: false,
+            hash_algo: HashAlgorithm::default(),
+            history: Vec::new(),
+            root_at_database: false,
+            options: StoreOptions::default(),
+            dirty: true,
+        })
+    }
+
+    /// Reads a database from any stream of JSON, such as a socket or stdin.
+    ///
+    /// The input must be UTF-8; a leading byte order mark is ignored. The
+    /// `files` vector is sized from the input length up front, so loading a
+    /// large database does not reallocate it record by record.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Database)` - The parsed database, with default `options`
+    /// * `Err(Exn<DatabaseError>)` - If reading fails or the input is not a valid database
+    pub fn from_reader(mut reader: impl std::io::Read) -> Result<Self, Exn<DatabaseError>> {
+        let mut json_bytes = Vec::new();
+        reader
+            .read_to_end(&mut json_bytes)
+            .or_raise(|| DatabaseError {
+                message: "Failed to read the database".to_string(),
+            })?;
+        let json_str = std::str::from_utf8(&json_bytes).map_err(|err| {
+            Exn::new(DatabaseError {
+                message: format!("There was an error converting bytes to &str: {}", err),
+            })
+        })?;
+        // editors on Windows may prefix the file with a UTF-8 byte order mark
+        let json_str = json_str.strip_prefix('\u{feff}').unwrap_or(json_str);
+        let seed = DatabaseSeed {
+            capacity: estimated_record_count(json_str.len()),
+        };
+        let mut deserializer = serde_json::Deserializer::from_str(json_str);
+        seed.deserialize(&mut deserializer)
+            .and_then(|db| deserializer.end().map(|()| db))
+            .map_err(|err| {
+                Exn::new(DatabaseError {
+                    message: format!("Invalid JSON format: {}", err),
+                })
+            })
+    }
+
+    /// Writes the database as pretty-printed JSON to any stream.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - The database was written
+    /// * `Err(Exn<DatabaseError>)` - If serialization or the write fails
+    pub fn to_writer(&self, writer: impl std::io::Write) -> Result<(), Exn<DatabaseError>> {
+        serde_json::to_writer_pretty(writer, self).or_raise(|| DatabaseError {
+            message: "Failed to serialize database to JSON".to_string(),
+        })
+    }
+
+    /// Writes the database as the file at `path`, recording whether `path`
+    /// lies directly in `root_dir`; see `relocate`.
+    pub(crate) fn to_writer_at(
+        &self,
+        writer: impl std::io::Write,
+        path: &Path,
+    ) -> Result<(), Exn<DatabaseError>> {
+        #[derive(Serialize)]
+        struct Stored<'db> {
+            #[serde(flatten)]
+            db: &'db Database,
+            root_at_database: bool,
+        }
+        let database_dir = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+            .unwrap_or(Path::new("."));
+        let stored = Stored {
+            db: self,
+            root_at_database: lexical(database_dir) == lexical(&self.root_dir),
+        };
+        serde_json::to_writer_pretty(writer, &stored).or_raise(|| DatabaseError {
+            message: "Failed to serialize database to JSON".to_string(),
         })
     }
 
@@ -279,34 +1431,409 @@ impl Database {
     ///
     /// # Arguments
     ///
-    /// * `path` - Path to the `.tamashii.json` database file
+    /// * `path` - Path to the `.tamashii.json` database file; `save` writes back to it
+    /// * `options` - Settings applied while loading and kept for later saves
     ///
     /// # Returns
     ///
     /// * `Ok(Database)` - The loaded database instance
-    /// * `Err(Exn<InitError>)` - If loading or parsing fails
-    pub async fn load(path: &PathBuf) -> Result<Self, Exn<InitError>> {
-        parse_database_file(path).await.map_err(|db_err| {
+    /// * `Err(Exn<InitError>)` - If loading, parsing, or a permission check fails
+    pub async fn load(path: &Path, options: &StoreOptions) -> Result<Self, Exn<InitError>> {
+        let mut db = parse_database_file(path, options).await.map_err(|db_err| {
             let err_msg = format!("Failed to load DB file: {}", db_err);
             db_err.raise(InitError { message: err_msg })
-        })
+        })?;
+        db.options.db_path = path.to_path_buf();
+        let database_dir = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+            .unwrap_or(Path::new("."));
+        db.relocate(lexical(database_dir));
+        Ok(db)
     }
 
-    /// Saves the current database state to disk.
+    /// Records that the database was modified and bumps `updated_at`.
+    ///
+    /// Called by every mutating method; code that edits `files` directly must
+    /// call it too, or `save` will skip the write.
+    pub fn mark_dirty(&mut self) {
+        self.updated_at = self.now();
+        self.dirty = true;
+    }
+
+    /// Appends an entry to `history`, stamped with `now`.
+    ///
+    /// Called by every method that adds, updates or removes a record; code that
+    /// edits `files` directly must call it too.
+    pub fn log_change(&mut self, action: HistoryAction, path: &Path, hash: &HexStirng) {
+        self.history.push(HistoryEntry {
+            timestamp: self.now(),
+            action,
+            path: path.to_path_buf(),
+            hash: hash.clone(),
+        });
+    }
+
+    /// Returns the time to stamp changes with.
+    ///
+    /// This is `options.timestamp_override` when set, so reproducible runs
+    /// produce identical databases, and the current time otherwise.
+    pub fn now(&self) -> DateTime<Utc> {
+        self.options.timestamp_override.unwrap_or_else(Utc::now)
+    }
+
+    /// Saves the current database state to `options.db_path`.
+    ///
+    /// That is the file the database was loaded from or created for, or the
+    /// default `.tamashii.json` for a database built with `new`.
     ///
     /// # Returns
     ///
-    /// * `Ok(())` - Successfully saved the database
+    /// * `Ok(())` - Successfully saved the database, or nothing changed
     /// * `Err(Exn<DatabaseError>)` - If serialization or writing fails
-    pub async fn save(&self) -> Result<(), Exn<DatabaseError>> {
-        serialize_database(self).await
+    pub async fn save(&mut self) -> Result<(), Exn<DatabaseError>> {
+        let path = self.options.db_path.clone();
+        self.save_to(&path).await
     }
-    /// Generates a random 128-bit hex-encoded ID used for unique file identification.
-    fn gen_id() -> String {
+
+    /// Saves the current database state to `path`.
+    ///
+    /// The write is skipped when nothing changed since the database was loaded
+    /// or last saved, so an unchanged database file keeps its mtime.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - Successfully saved the database, or nothing changed
+    /// * `Err(Exn<DatabaseError>)` - If the database is read-only or frozen, or
+    ///   serialization or writing fails
+    pub async fn save_to(&mut self, path: &Path) -> Result<(), Exn<DatabaseError>> {
+        if self.dirty {
+            self.ensure_unfrozen()?;
+        }
+        self.write_to(path).await
+    }
+
+    /// Freezes or unfreezes the database and saves it to `path`.
+    ///
+    /// This is the only change a frozen database accepts, so the baseline can
+    /// be unlocked deliberately but never modified by accident.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - The flag was saved, or already had this value
+    /// * `Err(Exn<DatabaseError>)` - If the database is read-only, or writing fails
+    pub async fn set_frozen(
+        &mut self,
+        frozen: bool,
+        path: &Path,
+    ) -> Result<(), Exn<DatabaseError>> {
+        if self.frozen != frozen {
+            self.frozen = frozen;
+            self.mark_dirty();
+        }
+        self.write_to(path).await
+    }
+
+    /// Returns an error if the database is frozen.
+    pub fn ensure_unfrozen(&self) -> Result<(), Exn<DatabaseError>> {
+        if self.frozen {
+            return Err(Exn::new(DatabaseError {
+                message:
+                    "The database is frozen; run `tamashii freeze --unfreeze` to allow changes"
+                        .to_string(),
+            }));
+        }
+        Ok(())
+    }
+
+    /// Writes the database to `path` if it changed, honoring `--readonly`.
+    async fn write_to(&mut self, path: &Path) -> Result<(), Exn<DatabaseError>> {
+        if self.options.readonly {
+            return Err(Exn::new(DatabaseError {
+                message: format!("Refusing to write {} in --readonly mode", path.display()),
+            }));
+        }
+        if !self.dirty {
+            return Ok(());
+        }
+        write_database_file(self, path).await?;
+        self.dirty = false;
+        Ok(())
+    }
+
+    /// Splits the records into one database per top-level directory under `root_dir`.
+    ///
+    /// Each part keeps this database's header and holds only the records of its
+    /// subtree; files outside any top-level directory go to `ROOT_GROUP`.
+    ///
+    /// # Returns
+    ///
+    /// The parts keyed by directory name, sorted by name.
+    pub fn split_by_top_dir(&self) -> Vec<(String, Database)> {
+        let mut parts: std::collections::BTreeMap<String, Vec<FileRecord>> =
+            std::collections::BTreeMap::new();
+        for file in self.files.iter() {
+            parts
+                .entry(top_level_key(&self.root_dir, &file.path))
+                .or_default()
+                .push(file.clone());
+        }
+        parts
+            .into_iter()
+            .map(|(name, files)| {
+                let history = self
+                    .history
+                    .iter()
+                    .filter(|entry| top_level_key(&self.root_dir, &entry.path) == name)
+                    .cloned()
+                    .collect();
+                let part = Database {
+                    version: self.version.clone(),
+                    root_dir: self.root_dir.clone(),
+                    created_at: self.created_at,
+                    updated_at: self.updated_at,
+                    files,
+                    frozen: self.frozen,
+                    hash_algo: self.hash_algo,
+                    history,
+                    root_at_database: false,
+                    options: self.options.clone(),
+                    dirty: false,
+                };
+                (name, part)
+            })
+            .collect()
+    }
+
+    /// Captures the current records and timestamps so they can be restored later.
+    ///
+    /// Lets a handler attempt a multi-step mutation and roll it back on error
+    /// without reloading the database from disk.
+    pub fn snapshot(&self) -> DatabaseSnapshot {
+        DatabaseSnapshot {
+            files: self.files.clone(),
+            history: self.history.clone(),
+            created_at: self.created_at,
+            updated_at: self.updated_at,
+            dirty: self.dirty,
+        }
+    }
+
+    /// Rolls the database back to the state captured by `snapshot`.
+    ///
+    /// # Arguments
+    ///
+    /// * `snapshot` - A snapshot previously taken from this database
+    pub fn restore(&mut self, snapshot: DatabaseSnapshot) {
+        self.files = snapshot.files;
+        self.history = snapshot.history;
+        self.created_at = snapshot.created_at;
+        self.updated_at = snapshot.updated_at;
+        self.dirty = snapshot.dirty;
+    }
+
+    /// Returns the records tracking files inside the directory `dir`.
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - The directory to look in, at any depth
+    /// * `matching` - How stored paths are compared with `dir`
+    pub fn records_under(&self, dir: &Path, matching: PathMatch) -> Vec<&FileRecord> {
+        self.files
+            .iter()
+            .filter(|record| is_under(&record.path, dir, matching))
+            .collect()
+    }
+
+    /// Rewrites every record's path from `rebase.old_root` to `rebase.new_root`.
+    ///
+    /// # Returns
+    ///
+    /// The paths that are not under `old_root` and were left unchanged.
+    pub fn rebase(&mut self, rebase: &Rebase) -> Vec<PathBuf> {
+        let mut unchanged = Vec::new();
+        for record in self.files.iter_mut() {
+            match rebase.apply(&record.path) {
+                Some(path) => record.path = path,
+                None => unchanged.push(record.path.clone()),
+            }
+        }
+        if unchanged.len() < self.files.len() {
+            self.mark_dirty();
+        }
+        unchanged
+    }
+
+    /// Merges the records of another database into this one.
+    ///
+    /// Records for untracked paths are added. A record for a tracked path with
+    /// a different hash replaces the tracked one; one with the same hash is skipped.
+    ///
+    /// # Arguments
+    ///
+    /// * `records` - Records to import
+    /// * `matching` - How imported paths are matched against tracked records
+    ///
+    /// # Returns
+    ///
+    /// How many records were added, replaced, or already tracked.
+    pub fn import_records(
+        &mut self,
+        records: Vec<FileRecord>,
+        matching: PathMatch,
+    ) -> ImportSummary {
+        let mut summary = ImportSummary::default();
+        for record in records {
+            let tracked = self
+                .files
+                .iter()
+                .position(|tracked| paths_match(&tracked.path, &record.path, matching));
+            match tracked {
+                None => {
+                    self.log_change(HistoryAction::Added, &record.path, &record.hash);
+                    self.files.push(record);
+                    summary.added += 1;
+                }
+                Some(index) if !self.files[index].hash.ct_eq(&record.hash) => {
+                    self.log_change(HistoryAction::Updated, &record.path, &record.hash);
+                    summary.replaced.push(record.path.clone());
+                    self.files[index] = record;
+                }
+                Some(_) => summary.unchanged += 1,
+            }
+        }
+        if summary.added > 0 || !summary.replaced.is_empty() {
+            self.mark_dirty();
+        }
+        summary
+    }
+
+    /// Moves the record tracking `from` to `to`, keeping its id, hash and times.
+    ///
+    /// Used when a tracked file was renamed or moved without changing its content.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(true)` - The record now tracks `to`
+    /// * `Ok(false)` - `from` is not tracked
+    /// * `Err(Exn<DatabaseError>)` - If the database is frozen
+    pub fn rename_record(
+        &mut self,
+        from: &Path,
+        to: PathBuf,
+        matching: PathMatch,
+    ) -> Result<bool, Exn<DatabaseError>> {
+        self.ensure_unfrozen()?;
+        let Some(index) = self
+            .files
+            .iter()
+            .position(|record| paths_match(&record.path, from, matching))
+        else {
+            return Ok(false);
+        };
+        let (old_path, hash) = (
+            self.files[index].path.clone(),
+            self.files[index].hash.clone(),
+        );
+        self.log_change(HistoryAction::Removed, &old_path, &hash);
+        self.log_change(HistoryAction::Added, &to, &hash);
+        self.files[index].path = to;
+        self.mark_dirty();
+        Ok(true)
+    }
+
+    /// Returns the form a path given on the command line is tracked under.
+    ///
+    /// Paths inside `root_dir` are stored relative to it, so the database keeps
+    /// working when the whole tree is moved; paths outside it stay absolute.
+    pub fn tracked_path(&self, path: &Path) -> PathBuf {
+        relative_to(&self.root_dir, path)
+    }
+
+    /// Returns where the file of a tracked path is on disk.
+    pub fn resolve(&self, path: &Path) -> PathBuf {
+        self.root_dir.join(path)
+    }
+
+    /// Points `root_dir` at `database_dir` if the database was saved at its
+    /// root or the recorded root no longer exists, and stores every record
+    /// path in the form of `tracked_path`.
+    ///
+    /// Either way the tree was copied or moved along with the database, so
+    /// relative paths keep their meaning and absolute paths under the old root
+    /// are carried over to the new one; a copy never checks the original's
+    /// files. Absolute paths written by older versions are made relative
+    /// here. Nothing is marked dirty: the converted paths are written with
+    /// the next change.
+    fn relocate(&mut self, database_dir: PathBuf) {
+        let old_root = self.root_dir.clone();
+        if old_root != database_dir && (self.root_at_database || !old_root.exists()) {
+            self.root_dir = database_dir;
+        }
+        for record in self.files.iter_mut() {
+            let path = record.path.strip_prefix(&old_root).unwrap_or(&record.path);
+            record.path = relative_to(&self.root_dir, &self.root_dir.join(path));
+        }
+    }
+
+    /// Makes every relative record path absolute by joining it onto `root_dir`.
+    ///
+    /// Used by `export` so the exported database does not depend on the
+    /// directory it is read from.
+    pub fn absolutize_paths(&mut self) {
+        for record in self.files.iter_mut() {
+            if record.path.is_relative() {
+                record.path = self.root_dir.join(&record.path);
+            }
+        }
+    }
+
+    /// Looks up the record tracking `path`.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path to look up
+    /// * `matching` - How stored paths are compared with `path`
+    ///
+    /// # Returns
+    ///
+    /// The first matching record, or `None` if `path` is not tracked.
+    pub fn find_record(&self, path: &Path, matching: PathMatch) -> Option<&FileRecord> {
+        self.files
+            .iter()
+            .find(|record| paths_match(&record.path, path, matching))
+    }
+
+    /// Returns every record whose stored hash is `hash`.
+    ///
+    /// Records hashed with a different algorithm can only match by accident;
+    /// callers that care compare `algo` as well.
+    pub fn find_by_hash(&self, hash: &HexStirng) -> Vec<&FileRecord> {
+        self.files
+            .iter()
+            .filter(|record| record.hash.ct_eq(hash))
+            .collect()
+    }
+
+    /// Generates a random RFC 4122 version 4 UUID used for unique file identification.
+    ///
+    /// Formatted as lowercase hex in the usual `8-4-4-4-12` groups.
+    pub(crate) fn gen_id() -> String {
         use rand::RngCore;
         let mut rng = rand::rng();
         let mut bytes = [0; 16];
         rng.fill_bytes(&mut bytes);
-        hex::encode(bytes)
+        // version 4 (random) in the high nibble of byte 6, RFC 4122 variant in byte 8
+        bytes[6] = (bytes[6] & 0x0f) | 0x40;
+        bytes[8] = (bytes[8] & 0x3f) | 0x80;
+        let hex = hex::encode(bytes);
+        format!(
+            "{}-{}-{}-{}-{}",
+            &hex[0..8],
+            &hex[8..12],
+            &hex[12..16],
+            &hex[16..20],
+            &hex[20..32]
+        )
     }
 }
@@ -0,0 +1,142 @@
+use crate::errors::IoError;
+use crate::files;
+use crate::hash::{self, HashAlgorithm};
+use crate::models::HexStirng;
+use exn::{Exn, ResultExt};
+use std::path::{Path, PathBuf};
+
+/// Name of the directory under the database root holding Tamashii's own
+/// data; recursive adds never walk into it.
+pub const STORE_DIR: &str = ".tamashii";
+
+/// Returns the directory of the content-addressed backups kept by `add --backup`.
+pub fn objects_dir(root: &Path) -> PathBuf {
+    root.join(STORE_DIR).join("objects")
+}
+
+/// Returns where the backup of content hashing to `hash` is stored.
+pub fn object_path(root: &Path, hash: &HexStirng) -> PathBuf {
+    objects_dir(root).join(&hash.0)
+}
+
+/// Returns `true` if a backup of content hashing to `hash` is stored.
+pub fn has_object(root: &Path, hash: &HexStirng) -> bool {
+    object_path(root, hash).is_file()
+}
+
+/// Stores a copy of the file at `path` as the backup of `hash`.
+///
+/// The copy is written under a temporary name, re-hashed, and only renamed
+/// into place if it still hashes to `hash`, so a file that changed after it
+/// was hashed never leaves a wrong backup behind. Content that is already
+/// stored is not copied again.
+///
+/// # Arguments
+///
+/// * `root` - The database's `root_dir`, which holds the object store
+/// * `path` - The file to back up
+/// * `hash` - Hash of the file's content, as computed with `algo`
+/// * `algo` - Algorithm `hash` was computed with
+///
+/// # Returns
+///
+/// * `Ok(())` - The backup is stored
+/// * `Err(Exn<IoError<PathBuf>>)` - If copying fails or the copy does not match `hash`
+pub async fn store_object(
+    root: &Path,
+    path: &Path,
+    hash: &HexStirng,
+    algo: HashAlgorithm,
+) -> Result<(), Exn<IoError<PathBuf>>> {
+    let object = object_path(root, hash);
+    if object.is_file() {
+        return Ok(());
+    }
+    let dir = objects_dir(root);
+    std::fs::create_dir_all(&dir).or_raise(|| IoError {
+        path: Some(dir.clone()),
+        message: "Failed to create the object store".into(),
+    })?;
+    let staging = dir.join(format!("{}.tmp", hash));
+    copy_verified(path, &staging, hash, algo).await?;
+    std::fs::rename(&staging, &object).or_raise(|| IoError {
+        path: Some(object.clone()),
+        message: "Failed to move the backup into place".into(),
+    })
+}
+
+/// Replaces the file at `path` with the stored backup of `hash`.
+///
+/// The backup is copied next to `path` and checked against `hash` before it
+/// is renamed over the file, so a damaged backup is reported instead of
+/// restored. Missing parent directories are created.
+///
+/// # Arguments
+///
+/// * `root` - The database's `root_dir`, which holds the object store
+/// * `hash` - Hash of the content to restore
+/// * `algo` - Algorithm `hash` was computed with
+/// * `path` - The file to restore
+///
+/// # Returns
+///
+/// * `Ok(())` - The file holds the backed-up content again
+/// * `Err(Exn<IoError<PathBuf>>)` - If there is no backup of `hash`, it is
+///   damaged, or the file cannot be written
+pub async fn restore_object(
+    root: &Path,
+    hash: &HexStirng,
+    algo: HashAlgorithm,
+    path: &Path,
+) -> Result<(), Exn<IoError<PathBuf>>> {
+    let object = object_path(root, hash);
+    if !object.is_file() {
+        return Err(Exn::new(IoError {
+            path: Some(object),
+            message: format!("No backup is stored for hash {}", hash.short()),
+        }));
+    }
+    if let Some(parent) = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        std::fs::create_dir_all(parent).or_raise(|| IoError {
+            path: Some(parent.to_path_buf()),
+            message: "Failed to create the file's directory".into(),
+        })?;
+    }
+    let mut staging = path.as_os_str().to_owned();
+    staging.push(".tamashii-restore");
+    let staging = PathBuf::from(staging);
+    copy_verified(&object, &staging, hash, algo).await?;
+    std::fs::rename(&staging, path).or_raise(|| IoError {
+        path: Some(path.to_path_buf()),
+        message: "Failed to move the restored file into place".into(),
+    })
+}
+
+/// Copies `from` to `to` and checks that the copy hashes to `hash`,
+/// removing it if it does not.
+async fn copy_verified(
+    from: &Path,
+    to: &Path,
+    hash: &HexStirng,
+    algo: HashAlgorithm,
+) -> Result<(), Exn<IoError<PathBuf>>> {
+    std::fs::copy(from, to).or_raise(|| IoError {
+        path: Some(from.to_path_buf()),
+        message: format!("Failed to copy to {}", to.display()),
+    })?;
+    let copied = hash::hash_file_streaming(&files::get_file(&to).await?, algo).await?;
+    if copied.ct_eq(hash) {
+        return Ok(());
+    }
+    let _ = std::fs::remove_file(to);
+    Err(Exn::new(IoError {
+        path: Some(from.to_path_buf()),
+        message: format!(
+            "Content does not match hash {}; it changed or is damaged",
+            hash.short()
+        ),
+    }))
+}
@@ -0,0 +1,44 @@
+use std::collections::BTreeMap;
+use std::io::Write;
+
+/// Emits the output of concurrent tasks whole and in task order.
+///
+/// Each task renders everything it has to say into one block and hands it
+/// over with its index. Blocks are held until every earlier task has been
+/// written, and each block goes out in a single write, so output from
+/// `verify --jobs` never interleaves no matter how tasks finish.
+pub struct OrderedOutput<W: Write> {
+    /// Where blocks are written
+    out: W,
+    /// Index of the next block to write
+    next: usize,
+    /// Blocks that finished ahead of an earlier one
+    pending: BTreeMap<usize, String>,
+}
+
+impl<W: Write> OrderedOutput<W> {
+    /// Starts coordinating output to `out`, beginning with task `0`.
+    pub fn new(out: W) -> Self {
+        Self {
+            out,
+            next: 0,
+            pending: BTreeMap::new(),
+        }
+    }
+
+    /// Queues the complete output of task `index` and writes every block now in order.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - The block was written or queued
+    /// * `Err(std::io::Error)` - If writing to the output fails
+    pub fn push(&mut self, index: usize, block: String) -> std::io::Result<()> {
+        self.pending.insert(index, block);
+        while let Some(block) = self.pending.remove(&self.next) {
+            self.out.write_all(block.as_bytes())?;
+            self.out.flush()?;
+            self.next += 1;
+        }
+        Ok(())
+    }
+}
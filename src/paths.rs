@@ -0,0 +1,212 @@
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+
+/// How two paths are compared when looking up or matching tracked records.
+///
+/// Chosen once per run from the global `--path-match` and `--ignore-case`
+/// flags and passed to every lookup, so all commands agree on what counts as
+/// the same file.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum PathMatch {
+    /// Component-wise equality of the paths as written
+    Exact,
+    /// Equality after making both paths absolute and resolving `.` and `..`
+    /// without touching the filesystem
+    #[default]
+    Lexical,
+    /// Like `Lexical`, ignoring letter case
+    CaseInsensitive,
+    /// Equality after resolving symlinks on disk, falling back to `Lexical`
+    /// for paths that do not exist
+    Canonical,
+}
+
+impl PathMatch {
+    /// Picks the strategy selected by the command-line flags.
+    pub fn from_flags(path_match: PathMatch, ignore_case: bool) -> Self {
+        if ignore_case {
+            PathMatch::CaseInsensitive
+        } else {
+            path_match
+        }
+    }
+}
+
+/// Returns whether `a` and `b` refer to the same path under `strategy`.
+///
+/// # Arguments
+///
+/// * `a` - First path
+/// * `b` - Second path
+/// * `strategy` - The comparison rules to apply
+pub fn paths_match(a: &Path, b: &Path, strategy: PathMatch) -> bool {
+    match strategy {
+        PathMatch::Exact => a == b,
+        PathMatch::Lexical => lexical(a) == lexical(b),
+        PathMatch::CaseInsensitive => {
+            lexical(a).to_string_lossy().to_lowercase()
+                == lexical(b).to_string_lossy().to_lowercase()
+        }
+        PathMatch::Canonical => canonical(a) == canonical(b),
+    }
+}
+
+/// Returns whether `path` lies inside the directory `dir` under `strategy`.
+///
+/// # Arguments
+///
+/// * `path` - The candidate path
+/// * `dir` - The directory
+/// * `strategy` - The comparison rules to apply
+pub fn is_under(path: &Path, dir: &Path, strategy: PathMatch) -> bool {
+    match strategy {
+        PathMatch::Exact => path.starts_with(dir),
+        PathMatch::Lexical => lexical(path).starts_with(lexical(dir)),
+        PathMatch::CaseInsensitive => {
+            let lower = |path: &Path| PathBuf::from(lexical(path).to_string_lossy().to_lowercase());
+            lower(path).starts_with(lower(dir))
+        }
+        PathMatch::Canonical => canonical(path).starts_with(canonical(dir)),
+    }
+}
+
+/// Makes `path` absolute against the current directory and resolves `.` and
+/// `..` components lexically.
+pub fn lexical(path: &Path) -> PathBuf {
+    let absolute = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    let mut normalized = PathBuf::new();
+    for component in absolute.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
+/// Returns `path` relative to the directory `root` if it lies inside it, or
+/// else absolute.
+///
+/// Both are made absolute and normalized with `lexical` first, so `./a.txt`
+/// and `/project/a.txt` both become `a.txt` under the root `/project`.
+pub fn relative_to(root: &Path, path: &Path) -> PathBuf {
+    let path = lexical(path);
+    match path.strip_prefix(lexical(root)) {
+        Ok(relative) if !relative.as_os_str().is_empty() => relative.to_path_buf(),
+        _ => path,
+    }
+}
+
+/// Resolves `path` on disk, or lexically if it cannot be resolved.
+fn canonical(path: &Path) -> PathBuf {
+    std::fs::canonicalize(path).unwrap_or_else(|_| lexical(path))
+}
+
+/// A `OLD_ROOT=NEW_ROOT` mapping that moves paths from one root directory to another.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Rebase {
+    /// Root the paths were recorded under
+    pub old_root: PathBuf,
+    /// Root the paths are moved to
+    pub new_root: PathBuf,
+}
+
+impl Rebase {
+    /// Moves `path` from `old_root` to `new_root`.
+    ///
+    /// # Returns
+    ///
+    /// * `Some(PathBuf)` - The rebased path
+    /// * `None` - If `path` is not under `old_root`
+    pub fn apply(&self, path: &Path) -> Option<PathBuf> {
+        path.strip_prefix(&self.old_root)
+            .ok()
+            .map(|rest| self.new_root.join(rest))
+    }
+}
+
+impl FromStr for Rebase {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input.split_once('=') {
+            Some((old_root, new_root)) if !old_root.is_empty() => Ok(Rebase {
+                old_root: PathBuf::from(old_root),
+                new_root: PathBuf::from(new_root),
+            }),
+            _ => Err(format!("expected OLD_ROOT=NEW_ROOT, got `{}`", input)),
+        }
+    }
+}
+
+/// Serde support for paths that may not be valid UTF-8.
+///
+/// A UTF-8 path is written as a plain string, exactly as serde writes a
+/// `PathBuf`, so existing databases read unchanged. Any other path, such as
+/// a Linux filename holding arbitrary bytes, is written as an object with a
+/// lossy `display` string for people and the raw bytes hex-encoded in `bytes`,
+/// which is what is read back. Use with `#[serde(with = "crate::paths::raw_path")]`.
+pub mod raw_path {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer, de};
+    use std::path::{Path, PathBuf};
+
+    /// The object form of a path that is not valid UTF-8.
+    #[derive(Serialize, Deserialize)]
+    struct RawPath {
+        /// The path with invalid sequences replaced, for display only
+        display: String,
+        /// Hex encoding of the path's raw bytes
+        bytes: String,
+    }
+
+    /// Either form a path may be stored in.
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Stored {
+        Text(PathBuf),
+        Raw(RawPath),
+    }
+
+    pub fn serialize<S: Serializer>(path: &Path, serializer: S) -> Result<S::Ok, S::Error> {
+        match path.to_str() {
+            Some(text) => serializer.serialize_str(text),
+            None => RawPath {
+                display: path.to_string_lossy().into_owned(),
+                bytes: hex::encode(path.as_os_str().as_encoded_bytes()),
+            }
+            .serialize(serializer),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<PathBuf, D::Error> {
+        match Stored::deserialize(deserializer)? {
+            Stored::Text(path) => Ok(path),
+            Stored::Raw(raw) => {
+                let bytes = hex::decode(&raw.bytes).map_err(de::Error::custom)?;
+                Ok(super::path_from_bytes(bytes))
+            }
+        }
+    }
+}
+
+/// Rebuilds a path from its raw bytes, as read from a file.
+///
+/// On Unix any bytes make a path; other platforms replace sequences that
+/// are not valid UTF-8.
+#[cfg(unix)]
+pub fn path_from_bytes(bytes: Vec<u8>) -> PathBuf {
+    use std::os::unix::ffi::OsStringExt;
+    PathBuf::from(std::ffi::OsString::from_vec(bytes))
+}
+
+/// Rebuilds a path from its raw bytes, as read from a file.
+///
+/// On Unix any bytes make a path; other platforms replace sequences that
+/// are not valid UTF-8.
+#[cfg(not(unix))]
+pub fn path_from_bytes(bytes: Vec<u8>) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(&bytes).into_owned())
+}
@@ -0,0 +1,64 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// How long hashing one file took.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileTiming {
+    /// Path of the hashed file
+    pub path: PathBuf,
+    /// Size of the file in bytes
+    pub bytes: u64,
+    /// Time spent reading and hashing the file
+    pub elapsed: Duration,
+}
+
+impl FileTiming {
+    /// Returns the hashing throughput in megabytes (10^6 bytes) per second.
+    pub fn mb_per_sec(&self) -> f64 {
+        let secs = self.elapsed.as_secs_f64();
+        if secs == 0.0 {
+            return 0.0;
+        }
+        self.bytes as f64 / 1_000_000.0 / secs
+    }
+}
+
+impl std::fmt::Display for FileTiming {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} bytes {:.1} ms {:.1} MB/s {}",
+            self.bytes,
+            self.elapsed.as_secs_f64() * 1000.0,
+            self.mb_per_sec(),
+            self.path.display()
+        )
+    }
+}
+
+/// Collects per-file hashing times for `verify --profile`.
+#[derive(Debug, Default)]
+pub struct Profiler {
+    /// Timings in the order the files were hashed
+    timings: Vec<FileTiming>,
+}
+
+impl Profiler {
+    /// Records that hashing the `bytes` bytes of the file at `path` took `elapsed`.
+    pub fn record(&mut self, path: &Path, bytes: u64, elapsed: Duration) -> &FileTiming {
+        self.timings.push(FileTiming {
+            path: path.to_path_buf(),
+            bytes,
+            elapsed,
+        });
+        &self.timings[self.timings.len() - 1]
+    }
+
+    /// Returns the `n` slowest files, slowest first.
+    pub fn slowest(&self, n: usize) -> Vec<&FileTiming> {
+        let mut sorted: Vec<&FileTiming> = self.timings.iter().collect();
+        sorted.sort_by_key(|timing| std::cmp::Reverse(timing.elapsed));
+        sorted.truncate(n);
+        sorted
+    }
+}
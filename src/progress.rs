@@ -0,0 +1,164 @@
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// Width of the bar drawn by `Progress::render`, in characters.
+const BAR_WIDTH: usize = 20;
+
+/// Tracks how far a long-running add or verification has progressed.
+///
+/// Throughput is measured in bytes per second from the start of the run, and
+/// the remaining time is extrapolated from the bytes still left to hash. A
+/// run whose size is not known up front, like a recursive add that hashes
+/// files as the walk finds them, only counts files.
+pub struct Progress {
+    /// When the run started
+    started: Instant,
+    /// Number of files in the run, if known
+    total_files: Option<usize>,
+    /// Sum of the sizes of all files in the run
+    total_bytes: u64,
+    /// Number of files processed so far
+    done_files: usize,
+    /// Bytes processed so far
+    done_bytes: u64,
+    /// The file processed last
+    current: Option<PathBuf>,
+    /// Never draw, as requested with `--quiet`
+    quiet: bool,
+}
+
+impl Progress {
+    /// Starts tracking a run over `total_files` files totalling `total_bytes` bytes.
+    pub fn new(total_files: usize, total_bytes: u64) -> Self {
+        Self {
+            started: Instant::now(),
+            total_files: Some(total_files),
+            total_bytes,
+            done_files: 0,
+            done_bytes: 0,
+            current: None,
+            quiet: false,
+        }
+    }
+
+    /// Starts tracking a run whose number of files is not known up front.
+    pub fn unbounded() -> Self {
+        Self {
+            total_files: None,
+            ..Self::new(0, 0)
+        }
+    }
+
+    /// Suppresses drawing when `quiet` is set.
+    pub fn quiet(mut self, quiet: bool) -> Self {
+        self.quiet = quiet;
+        self
+    }
+
+    /// Marks one more file, `path` of `bytes` bytes, as processed.
+    pub fn advance(&mut self, bytes: u64, path: &Path) {
+        self.done_files += 1;
+        self.done_bytes = self.done_bytes.saturating_add(bytes);
+        self.current = Some(path.to_path_buf());
+    }
+
+    /// Renders a single status line.
+    ///
+    /// That is `[3/10] [######--------------] 30% ETA 00:12 notes.txt`, or
+    /// `[3] notes.txt` when the number of files is not known.
+    pub fn render(&self) -> String {
+        let current = self
+            .current
+            .as_deref()
+            .map(|path| format!(" {}", path.display()))
+            .unwrap_or_default();
+        let Some(total_files) = self.total_files else {
+            return format!("[{}]{}", self.done_files, current);
+        };
+        let percent = (self.done_bytes.min(self.total_bytes) * 100)
+            .checked_div(self.total_bytes)
+            .unwrap_or(100);
+        let remaining =
+            estimate_remaining(self.started.elapsed(), self.done_bytes, self.total_bytes);
+        format!(
+            "[{}/{}] {} {}% {}{}",
+            self.done_files,
+            total_files,
+            render_bar(percent),
+            percent,
+            format_eta(remaining),
+            current
+        )
+    }
+
+    /// Returns `true` if `draw` writes anything: not quiet, and stderr is a terminal.
+    ///
+    /// Piped and CI runs therefore never see the status line.
+    pub fn is_visible(&self) -> bool {
+        !self.quiet && std::io::stderr().is_terminal()
+    }
+
+    /// Redraws the status line on stderr, if it is visible.
+    pub fn draw(&self) {
+        if self.is_visible() {
+            // clear the line first, the previous file name may have been longer
+            eprint!("\r\x1b[2K{}", self.render());
+            if self.total_files == Some(self.done_files) {
+                eprintln!();
+            }
+        }
+    }
+
+    /// Ends the status line of an unbounded run, if it is visible.
+    pub fn finish(&self) {
+        if self.is_visible() && self.total_files.is_none() && self.done_files > 0 {
+            eprintln!();
+        }
+    }
+}
+
+/// Renders a bar `BAR_WIDTH` characters wide, filled to `percent`.
+pub fn render_bar(percent: u64) -> String {
+    let filled = (percent.min(100) as usize * BAR_WIDTH) / 100;
+    format!("[{}{}]", "#".repeat(filled), "-".repeat(BAR_WIDTH - filled))
+}
+
+/// Extrapolates the time left from the average throughput observed so far.
+///
+/// # Arguments
+///
+/// * `elapsed` - Time spent so far
+/// * `bytes_done` - Bytes processed so far
+/// * `bytes_total` - Bytes in the whole run
+///
+/// # Returns
+///
+/// * `Some(Duration)` - The estimated time remaining
+/// * `None` - If no bytes have been processed yet, so there is no throughput to go on
+pub fn estimate_remaining(
+    elapsed: Duration,
+    bytes_done: u64,
+    bytes_total: u64,
+) -> Option<Duration> {
+    if bytes_done >= bytes_total {
+        return Some(Duration::ZERO);
+    }
+    if bytes_done == 0 || elapsed.is_zero() {
+        return None;
+    }
+    let bytes_per_sec = bytes_done as f64 / elapsed.as_secs_f64();
+    let remaining = (bytes_total - bytes_done) as f64 / bytes_per_sec;
+    Some(Duration::from_secs_f64(remaining))
+}
+
+/// Formats an estimate as `ETA mm:ss`, or `ETA --:--` when unknown.
+pub fn format_eta(remaining: Option<Duration>) -> String {
+    match remaining {
+        Some(remaining) => {
+            let secs = remaining.as_secs();
+            format!("ETA {:02}:{:02}", secs / 60, secs % 60)
+        }
+        None => "ETA --:--".to_string(),
+    }
+}
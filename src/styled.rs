@@ -0,0 +1,76 @@
+use colored::{ColoredString, Colorize};
+use std::ffi::OsString;
+use std::io::IsTerminal;
+
+/// When to color terminal output, selected with `--color`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ColorChoice {
+    /// Color when stdout is a terminal, honoring `NO_COLOR` and `CLICOLOR_FORCE`
+    #[default]
+    Auto,
+    /// Always color
+    Always,
+    /// Never color
+    Never,
+}
+
+/// Applies the color policy for the rest of the process.
+///
+/// Every helper in this module goes through `colored`, so this single switch
+/// decides whether any output is styled.
+pub fn set_color_choice(choice: ColorChoice) {
+    let enabled = match choice {
+        ColorChoice::Auto => auto_color(
+            |name| std::env::var_os(name),
+            std::io::stdout().is_terminal(),
+        ),
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+    };
+    colored::control::set_override(enabled);
+}
+
+/// Decides whether `--color auto` colors output.
+///
+/// A non-empty `NO_COLOR` always wins (see <https://no-color.org>), then a
+/// `CLICOLOR_FORCE` other than `0` forces color on and `CLICOLOR=0` turns it
+/// off. Otherwise output is colored only when stdout is a terminal, so piped
+/// or redirected output carries no escape codes.
+///
+/// # Arguments
+///
+/// * `var` - Looks up an environment variable
+/// * `is_terminal` - Whether stdout is a terminal
+pub fn auto_color(var: impl Fn(&str) -> Option<OsString>, is_terminal: bool) -> bool {
+    let set = |name: &str| var(name).filter(|value| !value.is_empty());
+    if set("NO_COLOR").is_some() {
+        return false;
+    }
+    if set("CLICOLOR_FORCE").is_some_and(|value| value != "0") {
+        return true;
+    }
+    if set("CLICOLOR").is_some_and(|value| value == "0") {
+        return false;
+    }
+    is_terminal
+}
+
+/// Styles a neutral heading or informational line.
+pub fn info(message: &str) -> ColoredString {
+    message.cyan().bold()
+}
+
+/// Styles a message reporting that something succeeded or matched.
+pub fn success(message: &str) -> ColoredString {
+    message.green()
+}
+
+/// Styles a message that needs attention but is not a failure.
+pub fn warn(message: &str) -> ColoredString {
+    message.yellow()
+}
+
+/// Styles a message reporting a failure, mismatch or missing file.
+pub fn error(message: &str) -> ColoredString {
+    message.red()
+}
@@ -2,8 +2,17 @@ use exn::ResultExt;
 use tempfile::NamedTempFile;
 
 use super::*;
-use crate::{hash::hash_bytes, models::VERSION};
-use std::path::PathBuf;
+use crate::{
+    commands::{Cli, Commands},
+    database::{DB_PATH, StoreOptions},
+    hash::{HashAlgorithm, hash_bytes},
+    models::VERSION,
+    paths::PathMatch,
+    verify::{VerifyReport, VerifyStatus},
+};
+use clap::Parser;
+use exn::Exn;
+use std::path::{Path, PathBuf};
 
 /// Tests basic database creation and working directory initialization.
 #[compio::test]
@@ -37,8 +46,8 @@ async fn create_file() -> Result<(), Exn<errors::IoError<PathBuf>>> {
 #[compio::test]
 async fn hash_known_input() {
     let input = b"random-input-input";
-    let h1 = hash_bytes(input);
-    let h2 = hash_bytes(input);
+    let h1 = hash_bytes(input, HashAlgorithm::Sha256);
+    let h2 = hash_bytes(input, HashAlgorithm::Sha256);
     assert_eq!(h1, h2)
 }
 /// Tests database building from scratch.
@@ -53,13 +62,13 @@ async fn build_db() -> Result<(), Exn<InitError>> {
         })
     })?;
     assert_eq!(test_db.version, VERSION);
-    assert_eq!(test_db.root_dir, PathBuf::from(current_dir));
+    assert_eq!(test_db.root_dir, current_dir);
     Ok(())
 }
 /// Verifies that a database instance can be saved to disk.
 #[compio::test]
 async fn save_db() -> Result<(), Exn<InitError>> {
-    let test_db = Database::new().or_raise(|| InitError {
+    let mut test_db = Database::new().or_raise(|| InitError {
         message: "Failed trying to create a new DB instance".into(),
     })?;
     let _ = test_db.save().await.or_raise(|| InitError {
@@ -79,19 +88,4389 @@ async fn load_db() -> Result<(), Exn<InitError>> {
     std::io::Write::write_all(&mut test_tamashii, &contents).or_raise(|| InitError {
         message: "Failed trying to create a new DB instance".into(),
     })?;
-    let db = Database::get_or_create_db(
-        test_tamashii
-            .path()
-            .to_str()
-            .expect("Path is not valid UTF-8"),
-    )
-    .await
-    .or_raise(|| InitError {
-        message: "Failed trying to create a new DB instance".into(),
-    })?;
+    let db = Database::get_or_create_db(test_tamashii.path(), &StoreOptions::default())
+        .await
+        .or_raise(|| InitError {
+            message: "Failed trying to create a new DB instance".into(),
+        })?;
     println!("DB files count: {}", db.files.len());
     println!("DB created_at: {}", db.created_at);
     assert!(db.files.is_empty());
     // let _ = test_tamashii.flush();
     Ok(())
 }
+/// Verifies that the `--report` JSON lists exactly the changed and missing files.
+#[compio::test]
+async fn verify_report_lists_problems() -> Result<(), Exn<InitError>> {
+    let tmp = tempfile::tempdir().or_raise(|| InitError {
+        message: "Failed to create temp dir".into(),
+    })?;
+    let same = tmp.path().join("same.txt");
+    let changed = tmp.path().join("changed.txt");
+    let missing = tmp.path().join("missing.txt");
+    let mut db = Database::new()?;
+    for path in [&same, &changed, &missing] {
+        std::fs::write(path, b"original").or_raise(|| InitError {
+            message: "Failed to write fixture".into(),
+        })?;
+        db.builder()
+            .with_fields(
+                path.clone(),
+                hash_bytes(b"original", HashAlgorithm::Sha256),
+                8,
+                chrono::Utc::now(),
+            )
+            .commit()
+            .or_raise(|| InitError {
+                message: "Failed to commit record".into(),
+            })?;
+    }
+    std::fs::write(&changed, b"tampered").or_raise(|| InitError {
+        message: "Failed to modify fixture".into(),
+    })?;
+    std::fs::remove_file(&missing).or_raise(|| InitError {
+        message: "Failed to remove fixture".into(),
+    })?;
+
+    let mut results = Vec::new();
+    for record in db.files.iter() {
+        results.push(
+            verify::check_record(&db.root_dir, record)
+                .await
+                .or_raise(|| InitError {
+                    message: "Failed to check record".into(),
+                })?,
+        );
+    }
+    let report_path = tmp.path().join("report.json");
+    VerifyReport::from_results(results)
+        .write(&report_path)
+        .await
+        .or_raise(|| InitError {
+            message: "Failed to write report".into(),
+        })?;
+
+    let written: VerifyReport =
+        serde_json::from_slice(&std::fs::read(&report_path).or_raise(|| InitError {
+            message: "Failed to read report".into(),
+        })?)
+        .or_raise(|| InitError {
+            message: "Report is not valid JSON".into(),
+        })?;
+    assert_eq!(written.total, 3);
+    assert_eq!(written.ok, 1);
+    assert_eq!(written.changed, vec![changed]);
+    assert_eq!(written.missing, vec![missing]);
+    Ok(())
+}
+/// The report written for a single-path verify should read back as exactly the run it records.
+#[compio::test]
+async fn single_path_report_round_trips() -> Result<(), Exn<InitError>> {
+    let tmp = tempfile::tempdir().or_raise(|| InitError {
+        message: "Failed to create temp dir".into(),
+    })?;
+    let path = tmp.path().join("single.txt");
+    std::fs::write(&path, b"original").or_raise(|| InitError {
+        message: "Failed to write fixture".into(),
+    })?;
+    let mut db = Database::new()?;
+    db.builder()
+        .with_fields(
+            path.clone(),
+            hash_bytes(b"original", HashAlgorithm::Sha256),
+            8,
+            chrono::Utc::now(),
+        )
+        .commit()
+        .or_raise(|| InitError {
+            message: "Failed to commit record".into(),
+        })?;
+
+    let (_, result) = verify::verify_record(&db.root_dir, &db.files[0]).await;
+    let report = VerifyReport::for_single_file(&path, Some(result)).or_raise(|| InitError {
+        message: "Failed to build report".into(),
+    })?;
+    let report_path = tmp.path().join("report.json");
+    report.write(&report_path).await.or_raise(|| InitError {
+        message: "Failed to write report".into(),
+    })?;
+
+    let written: VerifyReport =
+        serde_json::from_slice(&std::fs::read(&report_path).or_raise(|| InitError {
+            message: "Failed to read report".into(),
+        })?)
+        .or_raise(|| InitError {
+            message: "Report is not valid JSON".into(),
+        })?;
+    assert_eq!(written, report);
+    assert_eq!(written.total, 1);
+    assert_eq!(written.ok, 1);
+    assert_eq!(written.results[0].path, path);
+    Ok(())
+}
+/// An untracked path has nothing to report, which is an error instead of no report at all.
+#[test]
+fn single_path_report_rejects_untracked_path() {
+    let untracked = PathBuf::from("untracked.txt");
+    let err = VerifyReport::for_single_file(&untracked, None)
+        .expect_err("an untracked path should not produce a report");
+    assert!(matches!(
+        err.as_error(),
+        VerificationError::FileUntracked(path) if *path == untracked
+    ));
+}
+/// Adding an identical file with `--dedup` should leave both paths sharing one inode.
+#[cfg(unix)]
+#[compio::test]
+async fn dedup_hardlinks_identical_files() -> Result<(), Exn<InitError>> {
+    use std::os::unix::fs::MetadataExt;
+    let tmp = tempfile::tempdir().or_raise(|| InitError {
+        message: "Failed to create temp dir".into(),
+    })?;
+    let first = tmp.path().join("first.txt");
+    let second = tmp.path().join("second.txt");
+    for path in [&first, &second] {
+        std::fs::write(path, b"same content").or_raise(|| InitError {
+            message: "Failed to write fixture".into(),
+        })?;
+    }
+    let mut db = Database::new()?;
+    let original_id = db
+        .builder()
+        .with_fields(
+            first.clone(),
+            hash_bytes(b"same content", HashAlgorithm::Sha256),
+            12,
+            chrono::Utc::now(),
+        )
+        .commit()
+        .or_raise(|| InitError {
+            message: "Failed to commit record".into(),
+        })?
+        .id
+        .clone();
+
+    let linked = files::hardlink_duplicate(&first, &second)
+        .await
+        .or_raise(|| InitError {
+            message: "Failed to deduplicate".into(),
+        })?;
+    assert!(linked);
+    let record = db
+        .builder()
+        .with_fields(
+            second.clone(),
+            hash_bytes(b"same content", HashAlgorithm::Sha256),
+            12,
+            chrono::Utc::now(),
+        )
+        .with_link(original_id.clone())
+        .commit()
+        .or_raise(|| InitError {
+            message: "Failed to commit record".into(),
+        })?;
+    assert_eq!(record.linked_to, Some(original_id));
+
+    let inode = |path: &PathBuf| std::fs::metadata(path).map(|meta| meta.ino()).ok();
+    assert_eq!(inode(&first), inode(&second));
+    assert_eq!(std::fs::read(&second).ok(), Some(b"same content".to_vec()));
+    Ok(())
+}
+/// The ETA is extrapolated from the throughput observed so far.
+#[test]
+fn eta_from_throughput() {
+    use crate::progress::{estimate_remaining, format_eta};
+    use std::time::Duration;
+    // 100 bytes in 10s leaves 300 bytes, i.e. 30 more seconds
+    let remaining = estimate_remaining(Duration::from_secs(10), 100, 400);
+    assert_eq!(remaining, Some(Duration::from_secs(30)));
+    assert_eq!(format_eta(remaining), "ETA 00:30");
+    assert_eq!(
+        format_eta(estimate_remaining(Duration::from_secs(60), 1, 181)),
+        "ETA 180:00"
+    );
+    assert_eq!(
+        format_eta(estimate_remaining(Duration::ZERO, 0, 10)),
+        "ETA --:--"
+    );
+    assert_eq!(
+        estimate_remaining(Duration::from_secs(5), 10, 10),
+        Some(Duration::ZERO)
+    );
+}
+/// The status line shows the counts, a bar and the file processed last.
+#[test]
+fn progress_renders_bar_and_current_file() {
+    use crate::progress::{Progress, render_bar};
+    assert_eq!(render_bar(0), format!("[{}]", "-".repeat(20)));
+    assert_eq!(
+        render_bar(50),
+        format!("[{}{}]", "#".repeat(10), "-".repeat(10))
+    );
+    assert_eq!(render_bar(250), format!("[{}]", "#".repeat(20)));
+
+    let mut progress = Progress::new(4, 400).quiet(true);
+    progress.advance(100, Path::new("a.txt"));
+    let line = progress.render();
+    assert!(line.starts_with("[1/4] [#####---------------] 25% "));
+    assert!(line.ends_with(" a.txt"));
+    assert!(!progress.is_visible());
+
+    let mut walk = Progress::unbounded();
+    assert_eq!(walk.render(), "[0]");
+    walk.advance(0, Path::new("dir/b.txt"));
+    assert_eq!(walk.render(), "[1] dir/b.txt");
+}
+/// Canonical JSON is byte-stable regardless of record order.
+#[compio::test]
+async fn canonical_json_is_order_independent() -> Result<(), Exn<InitError>> {
+    let mut db = Database::new()?;
+    for name in ["b.txt", "a.txt", "c.txt"] {
+        db.builder()
+            .with_fields(
+                PathBuf::from(name),
+                hash_bytes(name.as_bytes(), HashAlgorithm::Sha256),
+                5,
+                chrono::Utc::now(),
+            )
+            .commit()
+            .or_raise(|| InitError {
+                message: "Failed to commit record".into(),
+            })?;
+    }
+    let canonical = |db: &Database| {
+        database::canonical_json(db).or_raise(|| InitError {
+            message: "Failed to canonicalize".into(),
+        })
+    };
+    let first = canonical(&db)?;
+    assert_eq!(first, canonical(&db)?);
+    db.files.reverse();
+    assert_eq!(first, canonical(&db)?);
+    assert!(!first.contains(char::is_whitespace));
+    assert!(first.starts_with("{\"created_at\":"));
+    assert!(!first.contains("\"history\""));
+    Ok(())
+}
+/// Adding, re-adding and removing a file each append one history entry.
+#[compio::test]
+async fn history_records_add_update_remove() -> Result<(), Exn<InitError>> {
+    use crate::add::{AddOptions, add_file};
+    use crate::models::{HistoryAction, HistoryEntry};
+    use crate::paths::PathMatch;
+    let file = NamedTempFile::new().or_raise(|| InitError {
+        message: "Failed to create temp file".into(),
+    })?;
+    let path = file.path().to_path_buf();
+    std::fs::write(&path, b"first").or_raise(|| InitError {
+        message: "Failed to write fixture".into(),
+    })?;
+    let mut db = Database::new()?;
+    db.options.timestamp_override = Some(chrono::Utc::now());
+    let force = AddOptions {
+        force: true,
+        ..AddOptions::default()
+    };
+    add_file(&mut db, &path, &force).await?;
+    std::fs::write(&path, b"second").or_raise(|| InitError {
+        message: "Failed to write fixture".into(),
+    })?;
+    add_file(&mut db, &path, &force).await?;
+    assert!(db.remove_by_path(&path, PathMatch::Lexical).is_some());
+
+    let entry = |action, content: &[u8]| HistoryEntry {
+        timestamp: db.now(),
+        action,
+        path: path.clone(),
+        hash: hash_bytes(content, HashAlgorithm::Sha256),
+    };
+    let expected = vec![
+        entry(HistoryAction::Added, b"first"),
+        entry(HistoryAction::Updated, b"second"),
+        entry(HistoryAction::Removed, b"second"),
+    ];
+    assert_eq!(db.history, expected);
+    assert_eq!(format!("{:<7}|", HistoryAction::Added), "added  |");
+
+    let json = serde_json::to_string(&db).or_raise(|| InitError {
+        message: "Failed to serialize DB".into(),
+    })?;
+    let reloaded = Database::from_reader(json.as_bytes()).or_raise(|| InitError {
+        message: "Failed to parse DB".into(),
+    })?;
+    assert_eq!(reloaded.history, expected);
+    Ok(())
+}
+/// `missing_records` previews, and `prune_missing` drops, exactly the records whose files were deleted.
+#[compio::test]
+async fn prune_missing_returns_deleted() -> Result<(), Exn<InitError>> {
+    let tmp = tempfile::tempdir().or_raise(|| InitError {
+        message: "Failed to create temp dir".into(),
+    })?;
+    let present = tmp.path().join("present.txt");
+    let deleted = tmp.path().join("deleted.txt");
+    let mut db = Database::new()?;
+    for path in [&present, &deleted] {
+        std::fs::write(path, b"data").or_raise(|| InitError {
+            message: "Failed to write fixture".into(),
+        })?;
+        db.builder()
+            .with_fields(
+                path.clone(),
+                hash_bytes(b"data", HashAlgorithm::Sha256),
+                4,
+                chrono::Utc::now(),
+            )
+            .commit()
+            .or_raise(|| InitError {
+                message: "Failed to commit record".into(),
+            })?;
+    }
+    std::fs::remove_file(&deleted).or_raise(|| InitError {
+        message: "Failed to remove fixture".into(),
+    })?;
+    let before = db.updated_at;
+    let missing = db.missing_records().await;
+    assert_eq!(missing.len(), 1);
+    assert_eq!(missing[0].path, deleted);
+    assert_eq!(db.files.len(), 2);
+    assert_eq!(db.updated_at, before);
+
+    let removed = db.prune_missing().await;
+    assert_eq!(removed.len(), 1);
+    assert_eq!(removed[0].path, deleted);
+    assert_eq!(db.files.len(), 1);
+    assert_eq!(db.files[0].path, present);
+    assert!(db.updated_at > before);
+
+    let untouched = db.updated_at;
+    assert!(db.prune_missing().await.is_empty());
+    assert_eq!(db.updated_at, untouched);
+    Ok(())
+}
+/// Uppercase hex input matches the lowercase form produced by hashing.
+#[test]
+fn uppercase_hash_matches_stored_lowercase() {
+    use crate::models::HexStirng;
+    let stored = hash_bytes(b"case-test", HashAlgorithm::Sha256);
+    let upper = stored.0.to_ascii_uppercase();
+    let parsed = HexStirng::parse(&upper).expect("uppercase hex should parse");
+    assert_eq!(parsed, stored);
+    let deserialized: HexStirng =
+        serde_json::from_str(&format!("\"{}\"", upper)).expect("uppercase hex should load");
+    assert_eq!(deserialized, stored);
+    assert!(HexStirng::parse("not-hex").is_err());
+    assert!(serde_json::from_str::<HexStirng>("\"xyz\"").is_err());
+}
+/// Concurrent checks sharing a small memory budget never exceed it, counting
+/// the one read buffer each check holds rather than the size of its file.
+#[compio::test]
+async fn mem_limit_bounds_concurrent_hashing() -> Result<(), Exn<InitError>> {
+    use crate::budget::MemoryBudget;
+    use crate::hash::HASH_CHUNK_SIZE;
+    const FILE_SIZE: usize = 256 * 1024;
+    // room for two read buffers, and less than a single file
+    const LIMIT: u64 = 2 * HASH_CHUNK_SIZE as u64 + 1000;
+    let tmp = tempfile::tempdir().or_raise(|| InitError {
+        message: "Failed to create temp dir".into(),
+    })?;
+    let mut db = Database::new()?;
+    let content = vec![7u8; FILE_SIZE];
+    for i in 0..6 {
+        let path = tmp.path().join(format!("large-{}.bin", i));
+        std::fs::write(&path, &content).or_raise(|| InitError {
+            message: "Failed to write fixture".into(),
+        })?;
+        db.builder()
+            .with_fields(
+                path,
+                hash_bytes(&content, HashAlgorithm::Sha256),
+                0,
+                chrono::Utc::now(),
+            )
+            .commit()
+            .or_raise(|| InitError {
+                message: "Failed to commit record".into(),
+            })?;
+    }
+    let budget = MemoryBudget::new(LIMIT);
+    let results = futures_util::future::join_all(
+        db.files
+            .iter()
+            .map(|record| verify::check_record_within(&db.root_dir, record, &budget)),
+    )
+    .await;
+    assert_eq!(results.len(), 6);
+    for result in results {
+        let result = result.or_raise(|| InitError {
+            message: "Failed to check record".into(),
+        })?;
+        assert_eq!(result.status, VerifyStatus::Ok);
+    }
+    assert_eq!(budget.peak(), 2 * HASH_CHUNK_SIZE as u64);
+    Ok(())
+}
+/// Path completion suggests tracked paths sharing the typed prefix.
+#[compio::test]
+async fn completes_tracked_paths_by_prefix() -> Result<(), Exn<InitError>> {
+    let mut db = Database::new()?;
+    for name in ["src/main.rs", "src/models.rs", "README.md"] {
+        db.builder()
+            .with_fields(
+                PathBuf::from(name),
+                hash_bytes(name.as_bytes(), HashAlgorithm::Sha256),
+                1,
+                chrono::Utc::now(),
+            )
+            .commit()
+            .or_raise(|| InitError {
+                message: "Failed to commit record".into(),
+            })?;
+    }
+    let matches = completions::tracked_paths_matching(&db, "src/m");
+    assert_eq!(
+        matches,
+        vec![PathBuf::from("src/main.rs"), PathBuf::from("src/models.rs")]
+    );
+    assert!(completions::tracked_paths_matching(&db, "docs/").is_empty());
+    Ok(())
+}
+/// Strict mode saves the database as 0600 and refuses to load a 0644 one.
+#[cfg(unix)]
+#[compio::test]
+async fn strict_permissions_enforced() -> Result<(), Exn<InitError>> {
+    use crate::database::write_database_file;
+    use std::os::unix::fs::PermissionsExt;
+    let tmp = tempfile::tempdir().or_raise(|| InitError {
+        message: "Failed to create temp dir".into(),
+    })?;
+    let path = tmp.path().join(DB_PATH);
+    let strict = StoreOptions {
+        strict_permissions: true,
+        ..StoreOptions::default()
+    };
+    let mut db = Database::new()?;
+    db.options = strict.clone();
+    write_database_file(&db, &path)
+        .await
+        .or_raise(|| InitError {
+            message: "Failed to save DB".into(),
+        })?;
+    let mode = |path: &PathBuf| {
+        std::fs::metadata(path)
+            .map(|meta| meta.permissions().mode() & 0o777)
+            .unwrap_or_default()
+    };
+    assert_eq!(mode(&path), 0o600);
+    assert!(Database::load(&path, &strict).await.is_ok());
+
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).or_raise(|| {
+        InitError {
+            message: "Failed to loosen permissions".into(),
+        }
+    })?;
+    assert!(Database::load(&path, &strict).await.is_err());
+    assert!(
+        Database::load(&path, &StoreOptions::default())
+            .await
+            .is_ok()
+    );
+    Ok(())
+}
+/// Saving keeps the mode of the database it replaces, so a `chmod 600` sticks.
+#[cfg(unix)]
+#[compio::test]
+async fn save_keeps_database_permissions() -> Result<(), Exn<InitError>> {
+    use crate::database::write_database_file;
+    use std::os::unix::fs::PermissionsExt;
+    let tmp = tempfile::tempdir().or_raise(|| InitError {
+        message: "Failed to create temp dir".into(),
+    })?;
+    let path = tmp.path().join(DB_PATH);
+    let db = Database::new()?;
+    write_database_file(&db, &path)
+        .await
+        .or_raise(|| InitError {
+            message: "Failed to save DB".into(),
+        })?;
+    let mode = |path: &Path| {
+        std::fs::metadata(path)
+            .map(|meta| meta.permissions().mode() & 0o777)
+            .unwrap_or_default()
+    };
+    // a new database is private from the start
+    assert_eq!(mode(&path), 0o600);
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o640)).or_raise(|| {
+        InitError {
+            message: "Failed to change permissions".into(),
+        }
+    })?;
+    write_database_file(&db, &path)
+        .await
+        .or_raise(|| InitError {
+            message: "Failed to save DB".into(),
+        })?;
+    assert_eq!(mode(&path), 0o640);
+    Ok(())
+}
+/// Grouping by extension totals counts and bytes per extension, largest first.
+#[compio::test]
+async fn stats_group_by_extension() -> Result<(), Exn<InitError>> {
+    use crate::models::{GroupBy, GroupTotal};
+    let mut db = Database::new()?;
+    for (name, size) in [
+        ("a.rs", 10),
+        ("b.rs", 20),
+        ("notes.txt", 5),
+        ("Makefile", 1),
+    ] {
+        db.builder()
+            .with_fields(
+                db_root_path(name),
+                hash_bytes(name.as_bytes(), HashAlgorithm::Sha256),
+                size,
+                chrono::Utc::now(),
+            )
+            .commit()
+            .or_raise(|| InitError {
+                message: "Failed to commit record".into(),
+            })?;
+    }
+    let group = |key: &str, count, total_size| GroupTotal {
+        key: key.to_string(),
+        count,
+        total_size,
+    };
+    assert_eq!(
+        db.group_totals(GroupBy::Extension),
+        vec![
+            group("rs", 2, 30),
+            group("txt", 1, 5),
+            group("(none)", 1, 1)
+        ]
+    );
+    assert_eq!(db.group_totals(GroupBy::Directory), vec![group(".", 4, 36)]);
+    Ok(())
+}
+
+/// Builds a path inside the current directory, which `Database::new` uses as root.
+fn db_root_path(name: &str) -> PathBuf {
+    std::env::current_dir().unwrap_or_default().join(name)
+}
+/// Durable and non-durable saves both leave a valid database and no temp file behind.
+#[compio::test]
+async fn durable_save_writes_valid_file() -> Result<(), Exn<InitError>> {
+    use crate::database::{temp_path_for, write_database_file};
+    let tmp = tempfile::tempdir().or_raise(|| InitError {
+        message: "Failed to create temp dir".into(),
+    })?;
+    let path = tmp.path().join(DB_PATH);
+    for durable in [true, false] {
+        let mut db = Database::new()?;
+        db.options.durable = durable;
+        db.builder()
+            .with_fields(
+                PathBuf::from("a.txt"),
+                hash_bytes(b"a", HashAlgorithm::Sha256),
+                1,
+                chrono::Utc::now(),
+            )
+            .commit()
+            .or_raise(|| InitError {
+                message: "Failed to commit record".into(),
+            })?;
+        write_database_file(&db, &path)
+            .await
+            .or_raise(|| InitError {
+                message: "Failed to save DB".into(),
+            })?;
+        assert!(!temp_path_for(&path).exists());
+        let loaded = Database::load(&path, &StoreOptions::default()).await?;
+        assert_eq!(loaded.files, db.files);
+    }
+    Ok(())
+}
+/// A half-written temp file left by a killed save neither corrupts the database nor blocks the next save.
+#[compio::test]
+async fn interrupted_save_leaves_database_intact() -> Result<(), Exn<InitError>> {
+    use crate::database::{temp_path_for, write_database_file};
+    let tmp = tempfile::tempdir().or_raise(|| InitError {
+        message: "Failed to create temp dir".into(),
+    })?;
+    let path = tmp.path().join(DB_PATH);
+    let mut db = Database::new()?;
+    write_database_file(&db, &path)
+        .await
+        .or_raise(|| InitError {
+            message: "Failed to save DB".into(),
+        })?;
+    // what a process killed halfway through the next save leaves behind
+    std::fs::write(temp_path_for(&path), b"{\"version\": \"1.0.0\", \"fil").or_raise(|| {
+        InitError {
+            message: "Failed to write partial temp file".into(),
+        }
+    })?;
+    assert!(
+        Database::load(&path, &StoreOptions::default())
+            .await
+            .is_ok()
+    );
+
+    db.builder()
+        .with_fields(
+            PathBuf::from("a.txt"),
+            hash_bytes(b"a", HashAlgorithm::Sha256),
+            1,
+            chrono::Utc::now(),
+        )
+        .commit()
+        .or_raise(|| InitError {
+            message: "Failed to commit record".into(),
+        })?;
+    write_database_file(&db, &path)
+        .await
+        .or_raise(|| InitError {
+            message: "Failed to save DB".into(),
+        })?;
+    assert!(!temp_path_for(&path).exists());
+    let json = std::fs::read(&path).or_raise(|| InitError {
+        message: "Failed to read DB".into(),
+    })?;
+    assert!(serde_json::from_slice::<serde_json::Value>(&json).is_ok());
+    let loaded = Database::load(&path, &StoreOptions::default()).await?;
+    assert_eq!(loaded.files, db.files);
+    Ok(())
+}
+/// Diffing a database against a modified copy lists added, removed, and changed records.
+#[compio::test]
+async fn diff_db_lists_record_changes() -> Result<(), Exn<InitError>> {
+    let mut base = Database::new()?;
+    for name in ["kept.txt", "edited.txt", "dropped.txt"] {
+        base.builder()
+            .with_fields(
+                db_root_path(name),
+                hash_bytes(name.as_bytes(), HashAlgorithm::Sha256),
+                1,
+                chrono::Utc::now(),
+            )
+            .commit()
+            .or_raise(|| InitError {
+                message: "Failed to commit record".into(),
+            })?;
+    }
+    let mut other = Database::new()?;
+    other.files = base.files.clone();
+    other
+        .files
+        .retain(|file| file.path != db_root_path("dropped.txt"));
+    other.files[1].hash = hash_bytes(b"edited contents", HashAlgorithm::Sha256);
+    other
+        .builder()
+        .with_fields(
+            db_root_path("new.txt"),
+            hash_bytes(b"new", HashAlgorithm::Sha256),
+            1,
+            chrono::Utc::now(),
+        )
+        .commit()
+        .or_raise(|| InitError {
+            message: "Failed to commit record".into(),
+        })?;
+
+    let diff = base.diff_records(&other);
+    assert_eq!(diff.added.len(), 1);
+    assert_eq!(diff.added[0].path, db_root_path("new.txt"));
+    assert_eq!(diff.removed.len(), 1);
+    assert_eq!(diff.removed[0].path, db_root_path("dropped.txt"));
+    assert_eq!(diff.changed.len(), 1);
+    assert_eq!(diff.changed[0].path, db_root_path("edited.txt"));
+    assert_eq!(
+        diff.changed[0].old_hash,
+        hash_bytes(b"edited.txt", HashAlgorithm::Sha256)
+    );
+    assert_eq!(
+        diff.changed[0].new_hash,
+        hash_bytes(b"edited contents", HashAlgorithm::Sha256)
+    );
+    assert!(base.diff_records(&base).is_empty());
+    Ok(())
+}
+/// Saving an unchanged database after a no-op prune leaves the file untouched.
+#[compio::test]
+async fn noop_save_keeps_mtime() -> Result<(), Exn<InitError>> {
+    let tmp = tempfile::tempdir().or_raise(|| InitError {
+        message: "Failed to create temp dir".into(),
+    })?;
+    let path = tmp.path().join(DB_PATH);
+    let tracked = tmp.path().join("tracked.txt");
+    std::fs::write(&tracked, b"data").or_raise(|| InitError {
+        message: "Failed to write fixture".into(),
+    })?;
+    let mut db = Database::new()?;
+    db.builder()
+        .with_fields(
+            tracked,
+            hash_bytes(b"data", HashAlgorithm::Sha256),
+            4,
+            chrono::Utc::now(),
+        )
+        .commit()
+        .or_raise(|| InitError {
+            message: "Failed to commit record".into(),
+        })?;
+    let save_failed = || InitError {
+        message: "Failed to save DB".into(),
+    };
+    db.save_to(&path).await.or_raise(save_failed)?;
+    let mtime = || {
+        std::fs::metadata(&path)
+            .and_then(|meta| meta.modified())
+            .ok()
+    };
+    let written_at = mtime();
+
+    let mut loaded = Database::load(&path, &StoreOptions::default()).await?;
+    std::thread::sleep(std::time::Duration::from_millis(20));
+    assert!(loaded.prune_missing().await.is_empty());
+    loaded.save_to(&path).await.or_raise(save_failed)?;
+    assert_eq!(mtime(), written_at);
+
+    loaded.mark_dirty();
+    loaded.save_to(&path).await.or_raise(save_failed)?;
+    assert_ne!(mtime(), written_at);
+    Ok(())
+}
+/// Re-adding unchanged files skips hashing; `--force` and real changes re-hash.
+#[compio::test]
+async fn readd_unchanged_skips_hashing() -> Result<(), Exn<InitError>> {
+    use crate::add::{AddOptions, AddOutcome, add_file};
+    let tmp = tempfile::tempdir().or_raise(|| InitError {
+        message: "Failed to create temp dir".into(),
+    })?;
+    let paths: Vec<PathBuf> = (0..3)
+        .map(|i| tmp.path().join(format!("file-{}.txt", i)))
+        .collect();
+    for path in paths.iter() {
+        std::fs::write(path, b"contents").or_raise(|| InitError {
+            message: "Failed to write fixture".into(),
+        })?;
+    }
+    let mut db = Database::new()?;
+    let mut hashed = 0;
+    for path in paths.iter() {
+        if add_file(&mut db, path, &AddOptions::default()).await? == AddOutcome::Added {
+            hashed += 1;
+        }
+    }
+    assert_eq!(hashed, 3);
+
+    hashed = 0;
+    for path in paths.iter() {
+        if add_file(&mut db, path, &AddOptions::default()).await? == AddOutcome::Added {
+            hashed += 1;
+        }
+    }
+    assert_eq!(hashed, 0);
+    assert_eq!(db.files.len(), 3);
+
+    let force = AddOptions {
+        force: true,
+        ..AddOptions::default()
+    };
+    // re-hashed, but the content is what was tracked
+    assert_eq!(
+        add_file(&mut db, &paths[0], &force).await?,
+        AddOutcome::Unchanged
+    );
+    std::fs::write(&paths[0], b"new contents").or_raise(|| InitError {
+        message: "Failed to rewrite fixture".into(),
+    })?;
+    assert_eq!(
+        add_file(&mut db, &paths[0], &force).await?,
+        AddOutcome::Updated
+    );
+    assert_eq!(db.files.len(), 3);
+    Ok(())
+}
+/// A database saved with a UTF-8 BOM loads the same as one without.
+#[compio::test]
+async fn loads_database_with_bom() -> Result<(), Exn<InitError>> {
+    let tmp = tempfile::tempdir().or_raise(|| InitError {
+        message: "Failed to create temp dir".into(),
+    })?;
+    let plain = tmp.path().join("plain.json");
+    let with_bom = tmp.path().join("bom.json");
+    let mut db = Database::new()?;
+    db.builder()
+        .with_fields(
+            db_root_path("a.txt"),
+            hash_bytes(b"a", HashAlgorithm::Sha256),
+            1,
+            chrono::Utc::now(),
+        )
+        .commit()
+        .or_raise(|| InitError {
+            message: "Failed to commit record".into(),
+        })?;
+    let json = serde_json::to_string_pretty(&db).or_raise(|| InitError {
+        message: "Failed to serialize DB".into(),
+    })?;
+    std::fs::write(&plain, &json).or_raise(|| InitError {
+        message: "Failed to write fixture".into(),
+    })?;
+    std::fs::write(&with_bom, format!("\u{feff}{}", json)).or_raise(|| InitError {
+        message: "Failed to write fixture".into(),
+    })?;
+    let options = StoreOptions::default();
+    let from_plain = Database::load(&plain, &options).await?;
+    let from_bom = Database::load(&with_bom, &options).await?;
+    assert_eq!(from_bom.files, from_plain.files);
+    assert_eq!(from_bom.created_at, from_plain.created_at);
+    assert_eq!(from_bom.version, from_plain.version);
+    Ok(())
+}
+/// Exporting with `--sort-output hash` orders records by their hash strings.
+#[compio::test]
+async fn export_sorts_by_hash() -> Result<(), Exn<InitError>> {
+    use crate::models::SortOrder;
+    let mut db = Database::new()?;
+    for name in ["a.txt", "b.txt", "c.txt", "d.txt"] {
+        db.builder()
+            .with_fields(
+                db_root_path(name),
+                hash_bytes(name.as_bytes(), HashAlgorithm::Sha256),
+                1,
+                chrono::Utc::now(),
+            )
+            .commit()
+            .or_raise(|| InitError {
+                message: "Failed to commit record".into(),
+            })?;
+    }
+    db.sort_files(SortOrder::Hash);
+    let exported: serde_json::Value = serde_json::to_value(&db).or_raise(|| InitError {
+        message: "Failed to serialize DB".into(),
+    })?;
+    let hashes: Vec<&str> = exported["files"]
+        .as_array()
+        .map(|files| files.iter().filter_map(|f| f["hash"].as_str()).collect())
+        .unwrap_or_default();
+    let mut sorted = hashes.clone();
+    sorted.sort();
+    assert_eq!(hashes.len(), 4);
+    assert_eq!(hashes, sorted);
+
+    db.sort_files(SortOrder::Path);
+    assert!(db.files.windows(2).all(|pair| pair[0].path <= pair[1].path));
+    Ok(())
+}
+/// The per-directory rollup attributes changes to the directory containing them.
+#[test]
+fn verify_rollup_by_directory() {
+    use crate::verify::{DirectoryRollup, FileResult};
+    let root = PathBuf::from("/project");
+    let result = |path: &str, status| FileResult {
+        path: root.join(path),
+        status,
+        expected: hash_bytes(path.as_bytes(), HashAlgorithm::Sha256),
+        actual: None,
+        changed_blocks: Vec::new(),
+    };
+    let report = VerifyReport::from_results(vec![
+        result("README.md", VerifyStatus::Ok),
+        result("configs/a.toml", VerifyStatus::Changed),
+        result("configs/b.toml", VerifyStatus::Changed),
+        result("configs/c.toml", VerifyStatus::Missing),
+        result("src/main.rs", VerifyStatus::Ok),
+    ]);
+    let rollup = |directory: &str, ok, changed, missing| DirectoryRollup {
+        directory: directory.to_string(),
+        ok,
+        changed,
+        missing,
+        unreadable: 0,
+    };
+    assert_eq!(
+        report.by_directory(&root),
+        vec![
+            rollup(".", 1, 0, 0),
+            rollup("configs", 0, 2, 1),
+            rollup("src", 1, 0, 0),
+        ]
+    );
+}
+/// The database file and its sidecars are never tracked.
+#[compio::test]
+async fn refuses_to_track_database_files() -> Result<(), Exn<InitError>> {
+    use crate::add::{AddOptions, add_file};
+    use crate::database::is_database_artifact;
+    let tmp = tempfile::tempdir().or_raise(|| InitError {
+        message: "Failed to create temp dir".into(),
+    })?;
+    let db_file = tmp.path().join(DB_PATH);
+    std::fs::write(&db_file, b"{}").or_raise(|| InitError {
+        message: "Failed to write fixture".into(),
+    })?;
+    let mut db = Database::new()?;
+    assert!(
+        add_file(&mut db, &db_file, &AddOptions::default())
+            .await
+            .is_err()
+    );
+    assert!(db.files.is_empty());
+
+    let db_path = Path::new(DB_PATH);
+    for name in [
+        ".tamashii.json",
+        ".tamashii.json.bak",
+        ".tamashii.json.bak.3",
+        ".tamashii.json.lock",
+        ".tamashii.json.sha256",
+        ".tamashii.json.tmp",
+    ] {
+        assert!(
+            is_database_artifact(&tmp.path().join(name), db_path),
+            "{}",
+            name
+        );
+    }
+    for name in [
+        "tamashii.json",
+        ".tamashii.json.old",
+        ".tamashii.json.bak.x",
+        "notes.txt",
+    ] {
+        assert!(
+            !is_database_artifact(&tmp.path().join(name), db_path),
+            "{}",
+            name
+        );
+    }
+    Ok(())
+}
+/// Every algorithm round-trips through `FromStr`/`Display` and serde; unknown names error.
+#[test]
+fn hash_algorithm_round_trips() {
+    for algo in HashAlgorithm::ALL {
+        assert_eq!(algo.to_string().parse::<HashAlgorithm>().ok(), Some(algo));
+        assert_eq!(
+            algo.to_string()
+                .to_ascii_uppercase()
+                .parse::<HashAlgorithm>()
+                .ok(),
+            Some(algo)
+        );
+        let json = serde_json::to_string(&algo).expect("algorithm should serialize");
+        assert_eq!(json, format!("\"{}\"", algo));
+        assert_eq!(
+            serde_json::from_str::<HashAlgorithm>(&json).ok(),
+            Some(algo)
+        );
+    }
+    let err = "md5".parse::<HashAlgorithm>().unwrap_err();
+    assert!(err.to_string().contains("sha256, sha512, blake3, xxh3"));
+    assert!(serde_json::from_str::<HashAlgorithm>("\"crc32\"").is_err());
+}
+/// `--exclude-vcs` keeps everything inside `.git` out of a recursive add.
+#[compio::test]
+async fn recursive_add_excludes_vcs() -> Result<(), Exn<InitError>> {
+    use crate::add::{AddOptions, add_file};
+    use files::{WalkOptions, walk_files};
+    let tmp = tempfile::tempdir().or_raise(|| InitError {
+        message: "Failed to create temp dir".into(),
+    })?;
+    let root = tmp.path();
+    for dir in [".git/objects", "src"] {
+        std::fs::create_dir_all(root.join(dir)).or_raise(|| InitError {
+            message: "Failed to create fixture dir".into(),
+        })?;
+    }
+    for file in [
+        ".git/config",
+        ".git/objects/ab",
+        "src/a.txt",
+        "b.txt",
+        DB_PATH,
+    ] {
+        std::fs::write(root.join(file), file).or_raise(|| InitError {
+            message: "Failed to write fixture".into(),
+        })?;
+    }
+    let walk = |exclude_vcs| {
+        walk_files(
+            root,
+            &WalkOptions {
+                db_path: PathBuf::from(DB_PATH),
+                exclude_vcs,
+                ignore: Vec::new(),
+                follow_symlinks: false,
+            },
+        )
+        .collect::<Result<Vec<_>, _>>()
+        .map(|mut paths| {
+            paths.sort();
+            paths
+        })
+        .or_raise(|| InitError {
+            message: "Failed to walk fixture".into(),
+        })
+    };
+    let paths = walk(true)?;
+    assert_eq!(paths, vec![root.join("b.txt"), root.join("src/a.txt")]);
+    assert_eq!(walk(false)?.len(), 4);
+
+    let mut db = Database::new()?;
+    for path in paths.iter() {
+        add_file(&mut db, path, &AddOptions::default()).await?;
+    }
+    assert_eq!(db.files.len(), 2);
+    assert!(
+        !db.files
+            .iter()
+            .any(|file| file.path.starts_with(root.join(".git")))
+    );
+    Ok(())
+}
+/// A missing file found under a new path with the same hash is reported, and the record moved.
+#[compio::test]
+async fn detects_renamed_file() -> Result<(), Exn<InitError>> {
+    use crate::add::{AddOptions, add_file};
+    use crate::models::FileRecord;
+    use crate::paths::PathMatch;
+    use crate::verify::{Rename, detect_renames, prompt_rename, verify_records};
+    use files::WalkOptions;
+    let tmp = tempfile::tempdir().or_raise(|| InitError {
+        message: "Failed to create temp dir".into(),
+    })?;
+    let root = tmp.path();
+    std::fs::create_dir(root.join("moved")).or_raise(|| InitError {
+        message: "Failed to create fixture dir".into(),
+    })?;
+    for (file, content) in [("a.txt", "alpha"), ("b.txt", "bravo")] {
+        std::fs::write(root.join(file), content).or_raise(|| InitError {
+            message: "Failed to write fixture".into(),
+        })?;
+    }
+    let mut db = Database::new()?;
+    db.root_dir = root.to_path_buf();
+    for file in ["a.txt", "b.txt"] {
+        add_file(&mut db, &root.join(file), &AddOptions::default()).await?;
+    }
+    let original_hash = db.files[0].hash.clone();
+    assert_eq!(db.find_by_hash(&original_hash).len(), 1);
+    std::fs::rename(root.join("a.txt"), root.join("moved/c.txt")).or_raise(|| InitError {
+        message: "Failed to move fixture".into(),
+    })?;
+    // same size, different content: must not be taken for the moved file
+    std::fs::write(root.join("decoy.txt"), "delta").or_raise(|| InitError {
+        message: "Failed to write fixture".into(),
+    })?;
+
+    let records: Vec<&FileRecord> = db.files.iter().collect();
+    let report = verify_records(&db.root_dir, &records)
+        .await
+        .or_raise(|| InitError {
+            message: "Failed to verify".into(),
+        })?;
+    assert_eq!(report.missing, vec![PathBuf::from("a.txt")]);
+    let walk = WalkOptions {
+        db_path: PathBuf::from(DB_PATH),
+        ..WalkOptions::default()
+    };
+    let renames = detect_renames(&db, &report.missing, &walk, PathMatch::Lexical)
+        .await
+        .or_raise(|| InitError {
+            message: "Failed to detect renames".into(),
+        })?;
+    let expected = Rename {
+        from: PathBuf::from("a.txt"),
+        to: PathBuf::from("moved/c.txt"),
+    };
+    assert_eq!(renames, vec![expected.clone()]);
+
+    let mut output = Vec::new();
+    assert!(!prompt_rename(&mut &b"\n"[..], &mut output, &expected).unwrap());
+    assert!(prompt_rename(&mut &b"y\n"[..], &mut output, &expected).unwrap());
+    assert!(
+        db.rename_record(&expected.from, expected.to.clone(), PathMatch::Lexical)
+            .or_raise(|| InitError {
+                message: "Failed to rename record".into(),
+            })?
+    );
+    assert_eq!(db.find_by_hash(&original_hash)[0].path, expected.to);
+    let records: Vec<&FileRecord> = db.files.iter().collect();
+    let report = verify_records(&db.root_dir, &records)
+        .await
+        .or_raise(|| InitError {
+            message: "Failed to verify".into(),
+        })?;
+    assert_eq!(report.ok, 2);
+    Ok(())
+}
+/// `diff_against_disk` sorts tracked files into unchanged, modified, missing and untracked.
+#[compio::test]
+async fn diff_against_disk_classifies_files() -> Result<(), Exn<InitError>> {
+    use crate::add::{AddOptions, add_file};
+    use files::WalkOptions;
+    let tmp = tempfile::tempdir().or_raise(|| InitError {
+        message: "Failed to create temp dir".into(),
+    })?;
+    let root = tmp.path();
+    for file in ["same.txt", "edited.txt", "deleted.txt"] {
+        std::fs::write(root.join(file), file).or_raise(|| InitError {
+            message: "Failed to write fixture".into(),
+        })?;
+    }
+    let mut db = Database::new()?;
+    db.root_dir = root.to_path_buf();
+    for file in ["same.txt", "edited.txt", "deleted.txt"] {
+        add_file(&mut db, &root.join(file), &AddOptions::default()).await?;
+    }
+    let setup = || -> std::io::Result<()> {
+        std::fs::write(root.join("edited.txt"), "new content")?;
+        std::fs::remove_file(root.join("deleted.txt"))?;
+        std::fs::write(root.join("new.txt"), "untracked")
+    };
+    setup().or_raise(|| InitError {
+        message: "Failed to change fixtures".into(),
+    })?;
+
+    let diff = db.diff_against_disk(None).await.or_raise(|| InitError {
+        message: "Failed to diff".into(),
+    })?;
+    assert_eq!(diff.unchanged, vec![PathBuf::from("same.txt")]);
+    assert_eq!(diff.modified, vec![PathBuf::from("edited.txt")]);
+    assert_eq!(diff.missing, vec![PathBuf::from("deleted.txt")]);
+    assert!(diff.unreadable.is_empty());
+    assert!(diff.untracked.is_empty());
+    assert!(!diff.is_clean());
+
+    let walk = WalkOptions {
+        db_path: PathBuf::from(DB_PATH),
+        ..WalkOptions::default()
+    };
+    let diff = db
+        .diff_against_disk(Some(&walk))
+        .await
+        .or_raise(|| InitError {
+            message: "Failed to diff".into(),
+        })?;
+    assert_eq!(diff.untracked, vec![PathBuf::from("new.txt")]);
+    Ok(())
+}
+/// Concurrent verification reports every file, whatever order the reads finish in.
+#[compio::test]
+async fn verify_many_files_concurrently() -> Result<(), Exn<InitError>> {
+    use crate::add::{AddOptions, add_file};
+    use crate::models::FileRecord;
+    use crate::verify::{VerifyStatus, default_jobs, verify_records};
+    use std::collections::BTreeSet;
+    assert!(default_jobs() >= 1);
+    let tmp = tempfile::tempdir().or_raise(|| InitError {
+        message: "Failed to create temp dir".into(),
+    })?;
+    let mut db = Database::new()?;
+    for index in 0..64 {
+        let path = tmp.path().join(format!("file-{:02}.txt", index));
+        std::fs::write(&path, format!("content {}", index)).or_raise(|| InitError {
+            message: "Failed to write fixture".into(),
+        })?;
+        add_file(&mut db, &path, &AddOptions::default()).await?;
+    }
+    let touch = || -> std::io::Result<()> {
+        std::fs::write(tmp.path().join("file-07.txt"), "edited")?;
+        std::fs::remove_file(tmp.path().join("file-42.txt"))
+    };
+    touch().or_raise(|| InitError {
+        message: "Failed to change fixtures".into(),
+    })?;
+
+    let records: Vec<&FileRecord> = db.files.iter().collect();
+    let report = verify_records(&db.root_dir, &records)
+        .await
+        .or_raise(|| InitError {
+            message: "Failed to verify".into(),
+        })?;
+    assert_eq!(report.total, 64);
+    assert_eq!(report.ok, 62);
+    assert_eq!(report.changed, vec![tmp.path().join("file-07.txt")]);
+    assert_eq!(report.missing, vec![tmp.path().join("file-42.txt")]);
+    let reported: BTreeSet<&PathBuf> = report.results.iter().map(|r| &r.path).collect();
+    let tracked: BTreeSet<&PathBuf> = db.files.iter().map(|r| &r.path).collect();
+    assert_eq!(reported, tracked);
+    assert!(
+        report
+            .results
+            .iter()
+            .zip(db.files.iter())
+            .all(|(result, record)| result.path == record.path)
+    );
+    assert_eq!(
+        report
+            .results
+            .iter()
+            .filter(|r| r.status == VerifyStatus::Ok)
+            .count(),
+        62
+    );
+    Ok(())
+}
+/// Hashing is skipped only when both the size and the mtime match the record.
+#[compio::test]
+async fn stat_fast_path_needs_size_and_mtime() -> Result<(), Exn<InitError>> {
+    use crate::files::reported_time;
+    use crate::verify::{VerifyStatus, assume_unchanged, stat_matches, stat_unchanged};
+    let mtime = chrono::DateTime::parse_from_rfc3339("2024-05-01T12:00:00Z")
+        .expect("timestamp should parse")
+        .to_utc();
+    let mut db = Database::new()?;
+    db.builder()
+        .with_fields(
+            PathBuf::from("does-not-exist.txt"),
+            hash_bytes(b"twelve bytes", HashAlgorithm::Sha256),
+            12,
+            chrono::Utc::now(),
+        )
+        .with_modified(mtime)
+        .commit()
+        .or_raise(|| InitError {
+            message: "Failed to commit record".into(),
+        })?;
+    let mut record = db.files[0].clone();
+    assert!(stat_unchanged(&record, 12, Some(mtime)));
+    let result = assume_unchanged(&record);
+    assert_eq!(result.status, VerifyStatus::Ok);
+    assert_eq!(result.actual, None);
+
+    assert!(!stat_unchanged(&record, 13, Some(mtime)));
+    let later = mtime + chrono::Duration::seconds(1);
+    assert!(!stat_unchanged(&record, 12, Some(later)));
+    assert!(!stat_unchanged(&record, 12, None));
+    assert!(!stat_matches(&db.root_dir, &record).await);
+
+    // an epoch mtime is unknown, so it matches nothing, not even an epoch record
+    let epoch = chrono::DateTime::<chrono::Utc>::from(std::time::UNIX_EPOCH);
+    record.modified_at = Some(epoch);
+    assert_eq!(reported_time(Ok(std::time::UNIX_EPOCH)), None);
+    assert!(!stat_unchanged(
+        &record,
+        12,
+        reported_time(Ok(std::time::UNIX_EPOCH))
+    ));
+    record.modified_at = None;
+    assert!(!stat_unchanged(&record, 12, None));
+    Ok(())
+}
+/// `verify_record` maps each outcome to its `VerificationError` variant and
+/// hands back the result it was drawn from.
+#[compio::test]
+async fn verify_record_outcomes() -> Result<(), Exn<InitError>> {
+    use crate::verify::verify_record;
+    let tmp = tempfile::tempdir().or_raise(|| InitError {
+        message: "Failed to create temp dir".into(),
+    })?;
+    let path = tmp.path().join("tracked.txt");
+    std::fs::write(&path, b"original").or_raise(|| InitError {
+        message: "Failed to write fixture".into(),
+    })?;
+    let mut db = Database::new()?;
+    db.builder()
+        .with_fields(
+            path.clone(),
+            hash_bytes(b"original", HashAlgorithm::Sha256),
+            8,
+            chrono::Utc::now(),
+        )
+        .commit()
+        .or_raise(|| InitError {
+            message: "Failed to commit record".into(),
+        })?;
+    let mut record = db.files[0].clone();
+    assert!(verify_record(&db.root_dir, &record).await.0.is_ok());
+
+    std::fs::write(&path, b"tampered").or_raise(|| InitError {
+        message: "Failed to write fixture".into(),
+    })?;
+    assert!(matches!(
+        verify_record(&db.root_dir, &record).await.0,
+        Err(VerificationError::HashMissMatched { path: changed }) if changed == path
+    ));
+    let (_, result) = verify_record(&db.root_dir, &record).await;
+    assert_eq!(result.status, VerifyStatus::Changed);
+    assert_eq!(
+        result.actual,
+        Some(hash_bytes(b"tampered", HashAlgorithm::Sha256))
+    );
+
+    record.path = tmp.path().join("gone.txt");
+    assert!(matches!(
+        verify_record(&db.root_dir, &record).await.0,
+        Err(VerificationError::IoFailure)
+    ));
+
+    // a directory opens fine but cannot be read as a file
+    record.path = tmp.path().to_path_buf();
+    assert!(matches!(
+        verify_record(&db.root_dir, &record).await.0,
+        Err(VerificationError::HashFailure)
+    ));
+    Ok(())
+}
+/// `short` never slices past the end of a truncated hash.
+#[test]
+fn short_hash_does_not_panic() {
+    use crate::models::HexStirng;
+    assert_eq!(HexStirng("abc".to_string()).short(), "abc");
+    assert_eq!(HexStirng(String::new()).short(), "");
+    assert_eq!(
+        HexStirng("0123456789abcdef".to_string()).short(),
+        "01234567"
+    );
+    assert_eq!(HexStirng("ééééééééé".to_string()).short(), "éééééééé");
+}
+/// Each verification outcome has its own exit code, distinct from a failed run.
+#[compio::test]
+async fn verify_exit_codes_tell_outcomes_apart() -> Result<(), Exn<InitError>> {
+    use crate::verify::{
+        CHANGED_EXIT_CODE, ERROR_EXIT_CODE, UNREADABLE_EXIT_CODE, verification_exit_code,
+        verify_record,
+    };
+    let tmp = tempfile::tempdir().or_raise(|| InitError {
+        message: "Failed to create temp dir".into(),
+    })?;
+    let path = tmp.path().join("gate.txt");
+    std::fs::write(&path, b"baseline").or_raise(|| InitError {
+        message: "Failed to write fixture".into(),
+    })?;
+    let mut db = Database::new()?;
+    db.builder()
+        .with_fields(
+            path.clone(),
+            hash_bytes(b"baseline", HashAlgorithm::Sha256),
+            8,
+            chrono::Utc::now(),
+        )
+        .commit()
+        .or_raise(|| InitError {
+            message: "Failed to commit record".into(),
+        })?;
+    let mut record = db.files[0].clone();
+    let code = |outcome: &Result<(), VerificationError>| verification_exit_code(outcome, false);
+    assert_eq!(code(&verify_record(&db.root_dir, &record).await.0), 0);
+
+    std::fs::write(&path, b"tampered").or_raise(|| InitError {
+        message: "Failed to write fixture".into(),
+    })?;
+    assert_eq!(
+        code(&verify_record(&db.root_dir, &record).await.0),
+        CHANGED_EXIT_CODE
+    );
+    record.path = tmp.path().join("gone.txt");
+    assert_eq!(
+        code(&verify_record(&db.root_dir, &record).await.0),
+        CHANGED_EXIT_CODE
+    );
+    assert_eq!(
+        code(&Err(VerificationError::FileUntracked(path.clone()))),
+        CHANGED_EXIT_CODE
+    );
+    record.path = tmp.path().to_path_buf();
+    let unreadable = verify_record(&db.root_dir, &record).await.0;
+    assert_eq!(code(&unreadable), UNREADABLE_EXIT_CODE);
+    assert_eq!(verification_exit_code(&unreadable, true), 0);
+
+    let codes = [CHANGED_EXIT_CODE, ERROR_EXIT_CODE, UNREADABLE_EXIT_CODE];
+    assert!(codes.iter().all(|code| *code != 0));
+    assert_eq!(
+        codes
+            .iter()
+            .collect::<std::collections::BTreeSet<_>>()
+            .len(),
+        3
+    );
+    let cli = Cli::try_parse_from(["tamashii", "verify", "--all", "--quiet"])
+        .expect("arguments should parse");
+    assert!(cli.quiet);
+    Ok(())
+}
+/// `restore` undoes every change made after `snapshot`.
+#[compio::test]
+async fn snapshot_restore_rolls_back() -> Result<(), Exn<InitError>> {
+    let mut db = Database::new()?;
+    db.builder()
+        .with_fields(
+            PathBuf::from("kept.txt"),
+            hash_bytes(b"kept", HashAlgorithm::Sha256),
+            1,
+            chrono::Utc::now(),
+        )
+        .commit()
+        .or_raise(|| InitError {
+            message: "Failed to commit record".into(),
+        })?;
+    let before = db.files.clone();
+    let updated_at = db.updated_at;
+    let snapshot = db.snapshot();
+
+    db.builder()
+        .with_fields(
+            PathBuf::from("discarded.txt"),
+            hash_bytes(b"discarded", HashAlgorithm::Sha256),
+            2,
+            chrono::Utc::now(),
+        )
+        .commit()
+        .or_raise(|| InitError {
+            message: "Failed to commit record".into(),
+        })?;
+    db.files[0].size = 9;
+    db.restore(snapshot);
+
+    assert_eq!(db.files, before);
+    assert_eq!(db.updated_at, updated_at);
+    Ok(())
+}
+/// With color disabled the severity helpers leave their text untouched.
+#[test]
+fn styled_helpers_plain_without_color() {
+    styled::set_color_choice(styled::ColorChoice::Never);
+    assert_eq!(styled::info("info").to_string(), "info");
+    assert_eq!(styled::success("ok").to_string(), "ok");
+    assert_eq!(styled::warn("careful").to_string(), "careful");
+    assert_eq!(styled::error("failed").to_string(), "failed");
+    styled::set_color_choice(styled::ColorChoice::Auto);
+}
+/// Verifying freshly added, unchanged files reports every one as ok.
+#[compio::test]
+async fn and_verify_reports_all_ok() -> Result<(), Exn<InitError>> {
+    use crate::add::{AddOptions, add_file};
+    let tmp = tempfile::tempdir().or_raise(|| InitError {
+        message: "Failed to create temp dir".into(),
+    })?;
+    let mut paths = Vec::new();
+    for name in ["one.txt", "two.txt"] {
+        let path = tmp.path().join(name);
+        std::fs::write(&path, name).or_raise(|| InitError {
+            message: "Failed to write fixture".into(),
+        })?;
+        paths.push(path);
+    }
+    let mut db = Database::new()?;
+    for path in paths.iter() {
+        add_file(&mut db, path, &AddOptions::default()).await?;
+    }
+    let report = verify::verify_paths(&db, &paths, crate::paths::PathMatch::default())
+        .await
+        .or_raise(|| InitError {
+            message: "Failed to verify".into(),
+        })?;
+    assert_eq!(report.total, 2);
+    assert_eq!(report.ok, 2);
+    assert!(report.changed.is_empty() && report.missing.is_empty());
+    Ok(())
+}
+/// Each `PathMatch` strategy handles `.`/`..`, trailing slashes and case as documented.
+#[test]
+fn path_match_strategies() {
+    use crate::paths::{PathMatch, paths_match};
+    let p = Path::new;
+
+    assert!(paths_match(p("dir/file"), p("dir/file/"), PathMatch::Exact));
+    assert!(!paths_match(
+        p("./dir/file"),
+        p("dir/file"),
+        PathMatch::Exact
+    ));
+    assert!(!paths_match(p("Dir/File"), p("dir/file"), PathMatch::Exact));
+
+    assert!(paths_match(
+        p("./dir/file"),
+        p("dir/file"),
+        PathMatch::Lexical
+    ));
+    assert!(paths_match(
+        p("dir/sub/../file/"),
+        p("dir/file"),
+        PathMatch::Lexical
+    ));
+    let cwd = std::env::current_dir().expect("current dir");
+    assert!(paths_match(
+        &cwd.join("dir/file"),
+        p("dir/./file"),
+        PathMatch::Lexical
+    ));
+    assert!(!paths_match(
+        p("Dir/File"),
+        p("dir/file"),
+        PathMatch::Lexical
+    ));
+
+    assert!(paths_match(
+        p("Dir/File.TXT"),
+        p("./dir/file.txt"),
+        PathMatch::CaseInsensitive
+    ));
+    assert!(!paths_match(
+        p("dir/a"),
+        p("dir/b"),
+        PathMatch::CaseInsensitive
+    ));
+
+    let tmp = tempfile::tempdir().expect("temp dir");
+    let target = tmp.path().join("target.txt");
+    std::fs::write(&target, b"x").expect("write target");
+    #[cfg(unix)]
+    {
+        let link = tmp.path().join("link.txt");
+        std::os::unix::fs::symlink(&target, &link).expect("symlink");
+        assert!(paths_match(&link, &target, PathMatch::Canonical));
+        assert!(!paths_match(&link, &target, PathMatch::Lexical));
+    }
+    let missing = tmp.path().join("missing/../gone.txt");
+    assert!(paths_match(
+        &missing,
+        &tmp.path().join("gone.txt"),
+        PathMatch::Canonical
+    ));
+
+    assert_eq!(
+        PathMatch::from_flags(PathMatch::Exact, true),
+        PathMatch::CaseInsensitive
+    );
+    assert_eq!(
+        PathMatch::from_flags(PathMatch::Canonical, false),
+        PathMatch::Canonical
+    );
+}
+/// `records_from` starts at the given path or index in path order.
+#[compio::test]
+async fn verify_start_from_skips_earlier_records() -> Result<(), Exn<InitError>> {
+    use crate::paths::PathMatch;
+    use crate::verify::{StartPoint, records_from};
+    let mut db = Database::new()?;
+    for name in ["d.txt", "b.txt", "a.txt", "c.txt"] {
+        db.builder()
+            .with_fields(
+                PathBuf::from(name),
+                hash_bytes(name.as_bytes(), HashAlgorithm::Sha256),
+                1,
+                chrono::Utc::now(),
+            )
+            .commit()
+            .or_raise(|| InitError {
+                message: "Failed to commit record".into(),
+            })?;
+    }
+    let names = |start: StartPoint| {
+        records_from(&db.files, &start, PathMatch::default())
+            .map(|records| records.iter().map(|r| r.path.clone()).collect::<Vec<_>>())
+    };
+    let from_third = Some(vec![PathBuf::from("c.txt"), PathBuf::from("d.txt")]);
+    assert_eq!(names(StartPoint::Path(PathBuf::from("c.txt"))), from_third);
+    assert_eq!(names(StartPoint::Index(2)), from_third);
+    assert_eq!(names(StartPoint::Beginning).map(|v| v.len()), Some(4));
+    assert_eq!(names(StartPoint::Index(10)), Some(vec![]));
+    assert_eq!(names(StartPoint::Path(PathBuf::from("zzz.txt"))), None);
+    Ok(())
+}
+/// A database edited after it was saved with `--write-checksum` fails `--check-checksum`.
+#[compio::test]
+async fn checksum_sidecar_detects_tampering() -> Result<(), Exn<InitError>> {
+    use crate::database::{checksum_path_for, parse_database_file, write_database_file};
+    let tmp = tempfile::tempdir().or_raise(|| InitError {
+        message: "Failed to create temp dir".into(),
+    })?;
+    let path = tmp.path().join(DB_PATH);
+    let options = StoreOptions {
+        write_checksum: true,
+        check_checksum: true,
+        ..StoreOptions::default()
+    };
+    let mut db = Database::new()?;
+    db.options = options.clone();
+    write_database_file(&db, &path)
+        .await
+        .or_raise(|| InitError {
+            message: "Failed to save DB".into(),
+        })?;
+    assert!(checksum_path_for(&path).exists());
+    assert!(parse_database_file(&path, &options).await.is_ok());
+
+    let json = std::fs::read_to_string(&path).or_raise(|| InitError {
+        message: "Failed to read DB".into(),
+    })?;
+    std::fs::write(&path, json.replace(VERSION, "1.9.9")).or_raise(|| InitError {
+        message: "Failed to tamper with DB".into(),
+    })?;
+    assert!(parse_database_file(&path, &options).await.is_err());
+    assert!(
+        parse_database_file(&path, &StoreOptions::default())
+            .await
+            .is_ok()
+    );
+    Ok(())
+}
+/// Databases load when their schema major version matches and are refused otherwise.
+#[compio::test]
+async fn schema_version_must_match_major() -> Result<(), Exn<InitError>> {
+    use crate::database::{parse_database_file, write_database_file};
+    let tmp = tempfile::tempdir().or_raise(|| InitError {
+        message: "Failed to create temp dir".into(),
+    })?;
+    let path = tmp.path().join(DB_PATH);
+    let options = StoreOptions::default();
+    let mut db = Database::new()?;
+    for (version, compatible) in [(VERSION, true), ("1.0.0-beta", true), ("0.9.0", false)] {
+        db.version = version.to_string();
+        write_database_file(&db, &path)
+            .await
+            .or_raise(|| InitError {
+                message: "Failed to save DB".into(),
+            })?;
+        let loaded = parse_database_file(&path, &options).await;
+        assert_eq!(loaded.is_ok(), compatible, "version {}", version);
+    }
+    db.version = "2.0.0".to_string();
+    write_database_file(&db, &path)
+        .await
+        .or_raise(|| InitError {
+            message: "Failed to save DB".into(),
+        })?;
+    let err = parse_database_file(&path, &options).await.unwrap_err();
+    assert!(err.to_string().contains("schema version \"2.0.0\""));
+    Ok(())
+}
+/// Rehashing a modified tracked file stores the hash of its current content.
+#[compio::test]
+async fn rehash_accepts_current_content() -> Result<(), Exn<InitError>> {
+    use crate::add::{AddOptions, add_file, rehash_file};
+    use crate::paths::PathMatch;
+    let tmp = tempfile::tempdir().or_raise(|| InitError {
+        message: "Failed to create temp dir".into(),
+    })?;
+    let path = tmp.path().join("baseline.txt");
+    std::fs::write(&path, b"before").or_raise(|| InitError {
+        message: "Failed to write fixture".into(),
+    })?;
+    let mut db = Database::new()?;
+    add_file(&mut db, &path, &AddOptions::default()).await?;
+    let id = db.files[0].id.clone();
+
+    std::fs::write(&path, b"after!!").or_raise(|| InitError {
+        message: "Failed to modify fixture".into(),
+    })?;
+    assert!(rehash_file(&mut db, &path, PathMatch::default()).await?);
+    assert_eq!(db.files.len(), 1);
+    assert_eq!(db.files[0].id, id);
+    assert_eq!(
+        db.files[0].hash,
+        hash_bytes(b"after!!", HashAlgorithm::Sha256)
+    );
+    assert_eq!(db.files[0].size, 7);
+    assert!(!rehash_file(&mut db, &path, PathMatch::default()).await?);
+    assert!(
+        rehash_file(&mut db, &tmp.path().join("untracked"), PathMatch::default())
+            .await
+            .is_err()
+    );
+    Ok(())
+}
+/// `--rebase` moves imported paths under the new root and leaves others alone.
+#[compio::test]
+async fn import_rebases_paths() -> Result<(), Exn<InitError>> {
+    use crate::paths::{PathMatch, Rebase};
+    let mut other = Database::new()?;
+    for name in [
+        "/srv/build/app/bin",
+        "/srv/build/app/lib/a.so",
+        "/etc/hosts",
+    ] {
+        other
+            .builder()
+            .with_fields(
+                PathBuf::from(name),
+                hash_bytes(name.as_bytes(), HashAlgorithm::Sha256),
+                1,
+                chrono::Utc::now(),
+            )
+            .commit()
+            .or_raise(|| InitError {
+                message: "Failed to commit record".into(),
+            })?;
+    }
+    let rebase: Rebase = "/srv/build=/home/me/checkout"
+        .parse()
+        .expect("mapping should parse");
+    assert!("no-separator".parse::<Rebase>().is_err());
+    let unchanged = other.rebase(&rebase);
+    assert_eq!(unchanged, vec![PathBuf::from("/etc/hosts")]);
+
+    let mut db = Database::new()?;
+    assert_eq!(
+        db.import_records(other.files.clone(), PathMatch::default())
+            .added,
+        3
+    );
+    assert_eq!(
+        db.import_records(other.files, PathMatch::default()).added,
+        0
+    );
+    let paths: Vec<_> = db.files.iter().map(|f| f.path.clone()).collect();
+    assert_eq!(
+        paths,
+        vec![
+            PathBuf::from("/home/me/checkout/app/bin"),
+            PathBuf::from("/home/me/checkout/app/lib/a.so"),
+            PathBuf::from("/etc/hosts"),
+        ]
+    );
+    Ok(())
+}
+/// The streaming walker visits every file of a generated tree exactly once.
+#[test]
+fn walker_covers_large_tree() {
+    use files::{WalkOptions, walk_files};
+    use std::collections::BTreeSet;
+    let tmp = tempfile::tempdir().expect("temp dir");
+    let mut expected = BTreeSet::new();
+    for a in 0..8 {
+        for b in 0..8 {
+            let dir = tmp.path().join(format!("d{}/e{}/f", a, b));
+            std::fs::create_dir_all(&dir).expect("create dir");
+            for c in 0..6 {
+                let path = dir.join(format!("file{}.bin", c));
+                std::fs::write(&path, [a, b, c]).expect("write file");
+                expected.insert(path);
+            }
+        }
+    }
+    std::fs::create_dir_all(tmp.path().join("empty/nested")).expect("create dir");
+    let options = WalkOptions {
+        db_path: PathBuf::from(DB_PATH),
+        exclude_vcs: false,
+        ignore: Vec::new(),
+        follow_symlinks: false,
+    };
+    let mut seen = BTreeSet::new();
+    for path in walk_files(tmp.path(), &options) {
+        let path = path.expect("walk entry");
+        assert!(seen.insert(path), "file yielded twice");
+    }
+    assert_eq!(seen.len(), 384);
+    assert_eq!(seen, expected);
+    assert!(
+        walk_files(&tmp.path().join("missing"), &options)
+            .next()
+            .is_some_and(|entry| entry.is_err())
+    );
+}
+/// With a timestamp override every timestamp written equals the override.
+#[compio::test]
+async fn timestamp_override_is_used_everywhere() -> Result<(), Exn<InitError>> {
+    use crate::add::{AddOptions, add_file};
+    let tmp = tempfile::tempdir().or_raise(|| InitError {
+        message: "Failed to create temp dir".into(),
+    })?;
+    let fixed: chrono::DateTime<chrono::Utc> = "2020-01-02T03:04:05Z"
+        .parse()
+        .expect("timestamp should parse");
+    let options = StoreOptions {
+        timestamp_override: Some(fixed),
+        ..StoreOptions::default()
+    };
+    let db_path = tmp.path().join(DB_PATH);
+    let mut db = Database::get_or_create_db(&db_path, &options).await?;
+    for name in ["one.txt", "two.txt"] {
+        let path = tmp.path().join(name);
+        std::fs::write(&path, name).or_raise(|| InitError {
+            message: "Failed to write fixture".into(),
+        })?;
+        add_file(&mut db, &path, &AddOptions::default()).await?;
+    }
+    assert_eq!(db.created_at, fixed);
+    assert_eq!(db.updated_at, fixed);
+    assert!(db.files.iter().all(|file| file.time_stamp == fixed));
+    Ok(())
+}
+/// `--exit-on-change` succeeds only when something changed.
+#[test]
+fn exit_on_change_inverts_exit_code() {
+    use crate::verify::FileResult;
+    let result = |name: &str, status| FileResult {
+        path: PathBuf::from(name),
+        status,
+        expected: hash_bytes(name.as_bytes(), HashAlgorithm::Sha256),
+        actual: None,
+        changed_blocks: Vec::new(),
+    };
+    let clean = VerifyReport::from_results(vec![result("a", VerifyStatus::Ok)]);
+    let changed = VerifyReport::from_results(vec![
+        result("a", VerifyStatus::Ok),
+        result("b", VerifyStatus::Changed),
+    ]);
+    assert_eq!(changed.exit_code(true, false), 0);
+    assert_ne!(clean.exit_code(true, false), 0);
+    assert_eq!(clean.exit_code(false, false), 0);
+    assert_ne!(changed.exit_code(false, false), 0);
+}
+/// A record older than `--max-age` is stale regardless of its hash.
+#[compio::test]
+async fn old_record_is_stale() -> Result<(), Exn<InitError>> {
+    use crate::models::parse_max_age;
+    let mut db = Database::new()?;
+    let long_ago = chrono::Utc::now() - chrono::Duration::days(40);
+    db.builder()
+        .with_fields(
+            PathBuf::from("old.txt"),
+            hash_bytes(b"old", HashAlgorithm::Sha256),
+            3,
+            long_ago,
+        )
+        .commit()
+        .or_raise(|| InitError {
+            message: "Failed to commit record".into(),
+        })?;
+    let record = &db.files[0];
+    assert!(record.age() >= chrono::Duration::days(40));
+    assert!(record.is_stale(parse_max_age("30d").expect("30d should parse")));
+    assert!(!record.is_stale(parse_max_age("6w").expect("6w should parse")));
+    assert!(record.stale_notice().contains("40 day(s)"));
+
+    assert_eq!(parse_max_age("90s"), Ok(chrono::Duration::seconds(90)));
+    assert_eq!(parse_max_age("12h"), Ok(chrono::Duration::hours(12)));
+    assert!(parse_max_age("ten days").is_err());
+    assert!(parse_max_age("5y").is_err());
+    Ok(())
+}
+/// Relative timestamps pick the largest whole unit and handle recent and future instants.
+#[test]
+fn relative_time_formatting() {
+    use crate::models::format_relative;
+    let now = chrono::Utc::now();
+    assert_eq!(
+        format_relative(now - chrono::Duration::seconds(20)),
+        "just now"
+    );
+    assert_eq!(
+        format_relative(now - chrono::Duration::minutes(5)),
+        "5m ago"
+    );
+    assert_eq!(format_relative(now - chrono::Duration::hours(7)), "7h ago");
+    assert_eq!(format_relative(now - chrono::Duration::days(3)), "3d ago");
+    assert_eq!(format_relative(now - chrono::Duration::days(800)), "2y ago");
+    assert_eq!(
+        format_relative(now + chrono::Duration::hours(1)),
+        "in the future"
+    );
+    assert!(format_relative(chrono::DateTime::<chrono::Utc>::MIN_UTC).ends_with("y ago"));
+}
+/// `--readonly` rejects `add`, allows `status`, and makes any save an error.
+#[compio::test]
+async fn readonly_refuses_writes() -> Result<(), Exn<InitError>> {
+    use crate::commands::Cli;
+    use crate::database::{parse_database_file, write_database_file};
+    let parse = |args: &[&str]| Cli::try_parse_from(args).expect("arguments should parse");
+    let add = parse(&["tamashii", "--readonly", "add", "notes.txt"]);
+    assert!(add.readonly && add.command.mutates_database());
+    let status = parse(&["tamashii", "status", "--readonly"]);
+    assert!(status.readonly && !status.command.mutates_database());
+
+    let tmp = tempfile::tempdir().or_raise(|| InitError {
+        message: "Failed to create temp dir".into(),
+    })?;
+    let path = tmp.path().join(DB_PATH);
+    write_database_file(&Database::new()?, &path)
+        .await
+        .or_raise(|| InitError {
+            message: "Failed to save DB".into(),
+        })?;
+    let before = std::fs::read(&path).or_raise(|| InitError {
+        message: "Failed to read DB".into(),
+    })?;
+    let readonly = StoreOptions {
+        readonly: true,
+        ..StoreOptions::default()
+    };
+    let mut db = parse_database_file(&path, &readonly)
+        .await
+        .or_raise(|| InitError {
+            message: "Failed to load DB".into(),
+        })?;
+    db.mark_dirty();
+    assert!(db.save_to(&path).await.is_err());
+    let after = std::fs::read(&path).or_raise(|| InitError {
+        message: "Failed to read DB".into(),
+    })?;
+    assert_eq!(before, after);
+    Ok(())
+}
+/// Verifying a directory checks every tracked file under it and nothing else.
+#[compio::test]
+async fn verify_directory_is_scoped() -> Result<(), Exn<InitError>> {
+    use crate::add::{AddOptions, add_file};
+    use crate::paths::PathMatch;
+    let tmp = tempfile::tempdir().or_raise(|| InitError {
+        message: "Failed to create temp dir".into(),
+    })?;
+    let src = tmp.path().join("src");
+    std::fs::create_dir_all(&src).or_raise(|| InitError {
+        message: "Failed to create fixture dir".into(),
+    })?;
+    let mut db = Database::new()?;
+    for path in [
+        src.join("a.rs"),
+        src.join("b.rs"),
+        tmp.path().join("README"),
+    ] {
+        std::fs::write(&path, b"content").or_raise(|| InitError {
+            message: "Failed to write fixture".into(),
+        })?;
+        add_file(&mut db, &path, &AddOptions::default()).await?;
+    }
+    std::fs::write(src.join("b.rs"), b"edited").or_raise(|| InitError {
+        message: "Failed to modify fixture".into(),
+    })?;
+    let scoped = db.records_under(&src.join("."), PathMatch::default());
+    assert_eq!(scoped.len(), 2);
+    let report = verify::verify_records(&db.root_dir, &scoped)
+        .await
+        .or_raise(|| InitError {
+            message: "Failed to verify".into(),
+        })?;
+    assert_eq!(report.total, 2);
+    assert_eq!(report.ok, 1);
+    assert_eq!(report.changed, vec![src.join("b.rs")]);
+    Ok(())
+}
+/// Under profiling the largest file is among the slowest to hash.
+#[compio::test]
+async fn profile_lists_largest_file_as_slowest() -> Result<(), Exn<InitError>> {
+    use crate::add::{AddOptions, add_file};
+    use crate::profile::Profiler;
+    use std::time::Instant;
+    let tmp = tempfile::tempdir().or_raise(|| InitError {
+        message: "Failed to create temp dir".into(),
+    })?;
+    let mut db = Database::new()?;
+    // record paths are relative to the root, not to the current directory
+    db.root_dir = tmp.path().to_path_buf();
+    for (name, size) in [
+        ("tiny.txt", 1),
+        ("large.bin", 16 * 1024 * 1024),
+        ("small.txt", 64),
+    ] {
+        let path = tmp.path().join(name);
+        std::fs::write(&path, vec![7u8; size]).or_raise(|| InitError {
+            message: "Failed to write fixture".into(),
+        })?;
+        add_file(&mut db, &path, &AddOptions::default()).await?;
+    }
+    let mut profiler = Profiler::default();
+    for record in db.files.iter() {
+        let started = Instant::now();
+        verify::check_record(&db.root_dir, record)
+            .await
+            .or_raise(|| InitError {
+                message: "Failed to check record".into(),
+            })?;
+        profiler.record(&record.path, record.size, started.elapsed());
+    }
+    let slowest = profiler.slowest(1);
+    assert_eq!(slowest[0].path, PathBuf::from("large.bin"));
+    assert_eq!(slowest[0].bytes, 16 * 1024 * 1024);
+    assert!(slowest[0].to_string().contains("MB/s"));
+    assert_eq!(profiler.slowest(10).len(), 3);
+    Ok(())
+}
+/// A database written to an in-memory buffer reads back identically.
+#[compio::test]
+async fn database_round_trips_through_buffer() -> Result<(), Exn<InitError>> {
+    let mut db = Database::new()?;
+    db.builder()
+        .with_fields(
+            PathBuf::from("buffer.txt"),
+            hash_bytes(b"buffer", HashAlgorithm::Sha256),
+            6,
+            chrono::Utc::now(),
+        )
+        .with_modified(chrono::Utc::now())
+        .commit()
+        .or_raise(|| InitError {
+            message: "Failed to commit record".into(),
+        })?;
+    let to_bytes = |db: &Database| {
+        let mut buffer = Vec::new();
+        db.to_writer(&mut buffer).or_raise(|| InitError {
+            message: "Failed to write DB".into(),
+        })?;
+        Ok::<_, Exn<InitError>>(buffer)
+    };
+    let buffer = to_bytes(&db)?;
+    let read_back = Database::from_reader(buffer.as_slice()).or_raise(|| InitError {
+        message: "Failed to read DB".into(),
+    })?;
+    assert_eq!(read_back.files, db.files);
+    assert_eq!(read_back.created_at, db.created_at);
+    assert_eq!(to_bytes(&read_back)?, buffer);
+    assert!(Database::from_reader(&b"{not json"[..]).is_err());
+    Ok(())
+}
+/// Answering "accept" at the mismatch prompt stores the file's current hash.
+#[compio::test]
+async fn interactive_accept_updates_hash() -> Result<(), Exn<InitError>> {
+    use crate::add::{AddOptions, add_file, rehash_file};
+    use crate::paths::PathMatch;
+    use crate::verify::{Remediation, prompt_remediation};
+    let tmp = tempfile::tempdir().or_raise(|| InitError {
+        message: "Failed to create temp dir".into(),
+    })?;
+    let path = tmp.path().join("audited.txt");
+    std::fs::write(&path, b"original").or_raise(|| InitError {
+        message: "Failed to write fixture".into(),
+    })?;
+    let mut db = Database::new()?;
+    add_file(&mut db, &path, &AddOptions::default()).await?;
+    std::fs::write(&path, b"approved change").or_raise(|| InitError {
+        message: "Failed to modify fixture".into(),
+    })?;
+
+    let mut input = std::io::Cursor::new("maybe\na\n");
+    let mut output = Vec::new();
+    let answer = prompt_remediation(&mut input, &mut output, &path).or_raise(|| InitError {
+        message: "Failed to prompt".into(),
+    })?;
+    assert_eq!(answer, Remediation::Accept);
+    assert_eq!(
+        String::from_utf8_lossy(&output).matches("[a]ccept").count(),
+        2
+    );
+    rehash_file(&mut db, &path, PathMatch::default()).await?;
+    assert_eq!(
+        db.files[0].hash,
+        hash_bytes(b"approved change", HashAlgorithm::Sha256)
+    );
+
+    let mut eof = std::io::Cursor::new("");
+    let answer = prompt_remediation(&mut eof, &mut Vec::new(), &path).or_raise(|| InitError {
+        message: "Failed to prompt".into(),
+    })?;
+    assert_eq!(answer, Remediation::Skip);
+    Ok(())
+}
+/// A fixed seed gives the same shuffled order, and the report is still sorted by path.
+#[compio::test]
+async fn shuffle_is_seeded_and_report_sorted() -> Result<(), Exn<InitError>> {
+    use crate::paths::PathMatch;
+    use crate::verify::{FileResult, StartPoint, records_from, shuffle_records};
+    let mut db = Database::new()?;
+    for i in 0..20 {
+        let name = format!("file{:02}.txt", i);
+        db.builder()
+            .with_fields(
+                PathBuf::from(&name),
+                hash_bytes(name.as_bytes(), HashAlgorithm::Sha256),
+                1,
+                chrono::Utc::now(),
+            )
+            .commit()
+            .or_raise(|| InitError {
+                message: "Failed to commit record".into(),
+            })?;
+    }
+    let order = |seed| {
+        let mut records = records_from(&db.files, &StartPoint::Beginning, PathMatch::default())
+            .expect("beginning always exists");
+        shuffle_records(&mut records, seed);
+        records
+            .iter()
+            .map(|record| record.path.clone())
+            .collect::<Vec<_>>()
+    };
+    let shuffled = order(42);
+    assert_eq!(shuffled, order(42));
+    let mut sorted = shuffled.clone();
+    sorted.sort();
+    assert_ne!(shuffled, sorted);
+
+    let results = shuffled
+        .iter()
+        .map(|path| FileResult {
+            path: path.clone(),
+            status: VerifyStatus::Ok,
+            expected: hash_bytes(b"", HashAlgorithm::Sha256),
+            actual: None,
+            changed_blocks: Vec::new(),
+        })
+        .collect();
+    let report = VerifyReport::from_results_sorted(results);
+    let reported: Vec<_> = report.results.iter().map(|r| r.path.clone()).collect();
+    assert_eq!(reported, sorted);
+    Ok(())
+}
+/// `hash --stdin` prints the SHA-256 of whatever is read from its input.
+#[test]
+fn hashes_piped_bytes() {
+    use crate::hash::print_reader_hash;
+    let mut input = std::io::Cursor::new(b"abc".to_vec());
+    let mut output = Vec::new();
+    let digest = print_reader_hash(&mut input, &mut output, HashAlgorithm::Sha256)
+        .expect("hashing should succeed");
+    let expected = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+    assert_eq!(digest.0, expected);
+    assert_eq!(
+        String::from_utf8(output).unwrap(),
+        format!("{}\n", expected)
+    );
+    let parse = |args: &[&str]| match Cli::try_parse_from(args) {
+        Ok(Cli {
+            command: Commands::Hash { path, stdin },
+            ..
+        }) => (path, stdin),
+        other => panic!("unexpected parse: {:?}", other),
+    };
+    assert_eq!(parse(&["tamashii", "hash"]), (None, false));
+    assert_eq!(parse(&["tamashii", "hash", "--stdin"]), (None, true));
+    assert_eq!(
+        parse(&["tamashii", "hash", "-"]),
+        (Some(PathBuf::from("-")), false)
+    );
+}
+/// Loading a large database reads every record into a vector sized up front.
+#[compio::test]
+async fn load_preallocates_files() -> Result<(), Exn<InitError>> {
+    let mut db = Database::new()?;
+    let now = chrono::Utc::now();
+    for index in 0..2_000 {
+        let content = format!("record {}", index);
+        db.builder()
+            .with_fields(
+                PathBuf::from(format!("f{}", index)),
+                hash_bytes(content.as_bytes(), HashAlgorithm::Sha256),
+                content.len() as u64,
+                now,
+            )
+            .commit()
+            .or_raise(|| InitError {
+                message: "Failed to commit record".into(),
+            })?;
+    }
+    let mut buffer = Vec::new();
+    db.to_writer(&mut buffer).or_raise(|| InitError {
+        message: "Failed to write DB".into(),
+    })?;
+    let read_back = Database::from_reader(buffer.as_slice()).or_raise(|| InitError {
+        message: "Failed to read DB".into(),
+    })?;
+    assert_eq!(read_back.files, db.files);
+    assert_eq!(read_back.root_dir, db.root_dir);
+    assert!(read_back.files.capacity() >= read_back.files.len());
+    assert!(read_back.files.capacity() <= crate::database::estimated_record_count(buffer.len()));
+    assert!(Database::from_reader(&br#"{"version": "1.0.0"}"#[..]).is_err());
+    assert!(Database::from_reader(&buffer[..buffer.len() - 1]).is_err());
+    Ok(())
+}
+/// Even a database of the shortest possible records is not estimated to hold fewer.
+#[test]
+fn record_estimate_covers_shortest_records() -> Result<(), Exn<InitError>> {
+    let mut db = Database::new()?;
+    db.hash_algo = HashAlgorithm::Xxh3;
+    let whole_second = chrono::DateTime::parse_from_rfc3339("2024-01-01T00:00:00Z")
+        .expect("timestamp should parse")
+        .to_utc();
+    for name in 'a'..='z' {
+        db.builder()
+            .with_fields(
+                PathBuf::from(name.to_string()),
+                hash_bytes(name.to_string().as_bytes(), HashAlgorithm::Xxh3),
+                0,
+                whole_second,
+            )
+            .commit()
+            .or_raise(|| InitError {
+                message: "Failed to commit record".into(),
+            })?;
+    }
+    db.history.clear();
+    let mut buffer = Vec::new();
+    db.to_writer(&mut buffer).or_raise(|| InitError {
+        message: "Failed to write DB".into(),
+    })?;
+    assert!(crate::database::estimated_record_count(buffer.len()) >= db.files.len());
+    Ok(())
+}
+/// A `status --watch --check` frame shows the header, the check summary and each record.
+#[compio::test]
+async fn status_frame_renders_check_summary() -> Result<(), Exn<InitError>> {
+    use crate::verify::{FileResult, VerifyReport, VerifyStatus};
+    styled::set_color_choice(styled::ColorChoice::Never);
+    let mut db = Database::new()?;
+    assert!(
+        db.status_frame(None, None)
+            .starts_with("No files tracked yet.")
+    );
+    let now = chrono::Utc::now();
+    let hash = hash_bytes(b"watched", HashAlgorithm::Sha256);
+    db.builder()
+        .with_fields(PathBuf::from("watched.txt"), hash.clone(), 7, now)
+        .commit()
+        .or_raise(|| InitError {
+            message: "Failed to commit record".into(),
+        })?;
+    db.created_at = now;
+    db.updated_at = now;
+    let report = VerifyReport::from_results(vec![FileResult {
+        path: PathBuf::from("watched.txt"),
+        status: VerifyStatus::Changed,
+        expected: hash.clone(),
+        actual: Some(hash_bytes(b"edited", HashAlgorithm::Sha256)),
+        changed_blocks: Vec::new(),
+    }]);
+    let frame = db.status_frame(None, Some(&report));
+    let expected = format!(
+        "======= Database Status =======\n\
+         Total files: 1\n\
+         Total size: 7 B\n\
+         Created: just now ({now})\n\
+         Last updated: just now ({now})\n\
+         Check: 0 ok, 1 changed, 0 missing, 0 unreadable\n\
+         File: watched.txt Hash: ({}...)\n\
+         ======= Database Status =======\n",
+        hash.short()
+    );
+    assert_eq!(frame, expected);
+    assert!(!db.status_frame(None, None).contains("Check:"));
+    styled::set_color_choice(styled::ColorChoice::Auto);
+    Ok(())
+}
+/// A frozen database refuses `add` and saves until it is unfrozen.
+#[compio::test]
+async fn frozen_database_rejects_add() -> Result<(), Exn<InitError>> {
+    use crate::add::{AddOptions, add_file};
+    use crate::commands::Cli;
+    let tmp = tempfile::tempdir().or_raise(|| InitError {
+        message: "Failed to create temp dir".into(),
+    })?;
+    let db_path = tmp.path().join(DB_PATH);
+    let path = tmp.path().join("baseline.txt");
+    std::fs::write(&path, b"baseline").or_raise(|| InitError {
+        message: "Failed to write fixture".into(),
+    })?;
+    let mut db = Database::new()?;
+    db.set_frozen(true, &db_path).await.or_raise(|| InitError {
+        message: "Failed to freeze DB".into(),
+    })?;
+    let mut db = Database::load(&db_path, &StoreOptions::default()).await?;
+    assert!(db.frozen);
+    let err = add_file(&mut db, &path, &AddOptions::default())
+        .await
+        .expect_err("a frozen database should refuse add");
+    assert!(format!("{:?}", err).contains("frozen"));
+    assert!(db.files.is_empty());
+    db.mark_dirty();
+    assert!(db.save_to(&db_path).await.is_err());
+
+    db.set_frozen(false, &db_path)
+        .await
+        .or_raise(|| InitError {
+            message: "Failed to unfreeze DB".into(),
+        })?;
+    add_file(&mut db, &path, &AddOptions::default()).await?;
+    assert_eq!(db.files.len(), 1);
+
+    let parse = |args: &[&str]| Cli::try_parse_from(args).expect("arguments should parse");
+    assert!(
+        parse(&["tamashii", "freeze", "--unfreeze"])
+            .command
+            .mutates_database()
+    );
+    Ok(())
+}
+/// Parallel verify output comes out block by block in record order, however checks finish.
+#[compio::test]
+async fn parallel_verify_output_is_ordered() -> Result<(), Exn<InitError>> {
+    use crate::add::{AddOptions, add_file};
+    use crate::output::OrderedOutput;
+    use crate::verify::{check_record, render_result};
+    use futures_util::StreamExt;
+    let tmp = tempfile::tempdir().or_raise(|| InitError {
+        message: "Failed to create temp dir".into(),
+    })?;
+    let mut db = Database::new()?;
+    for index in 0..12 {
+        let path = tmp.path().join(format!("file{:02}.txt", index));
+        // vary the sizes so the checks finish out of order
+        let content = vec![b'a' + index as u8; (12 - index) * 40_000];
+        std::fs::write(&path, content).or_raise(|| InitError {
+            message: "Failed to write fixture".into(),
+        })?;
+        add_file(&mut db, &path, &AddOptions::default()).await?;
+    }
+    std::fs::write(tmp.path().join("file03.txt"), b"changed").or_raise(|| InitError {
+        message: "Failed to modify fixture".into(),
+    })?;
+    std::fs::remove_file(tmp.path().join("file07.txt")).or_raise(|| InitError {
+        message: "Failed to remove fixture".into(),
+    })?;
+
+    let mut buffer = Vec::new();
+    let mut output = OrderedOutput::new(&mut buffer);
+    let root = &db.root_dir;
+    let mut checks = futures_util::stream::iter(db.files.iter().enumerate())
+        .map(|(index, record)| async move { (index, record, check_record(root, record).await) })
+        .buffer_unordered(4);
+    let mut expected = String::new();
+    while let Some((index, record, result)) = checks.next().await {
+        let result = result.or_raise(|| InitError {
+            message: "Failed to check record".into(),
+        })?;
+        output
+            .push(index, render_result(record, &result, None))
+            .or_raise(|| InitError {
+                message: "Failed to write output".into(),
+            })?;
+    }
+    for record in db.files.iter() {
+        let result = check_record(&db.root_dir, record)
+            .await
+            .or_raise(|| InitError {
+                message: "Failed to check record".into(),
+            })?;
+        expected.push_str(&render_result(record, &result, None));
+    }
+    assert_eq!(String::from_utf8_lossy(&buffer), expected);
+    let files: Vec<_> = expected
+        .lines()
+        .filter_map(|line| line.strip_prefix("File: "))
+        .collect();
+    assert_eq!(files.len(), 12);
+    assert!(files.windows(2).all(|pair| pair[0] < pair[1]));
+
+    let mut buffer = Vec::new();
+    let mut output = OrderedOutput::new(&mut buffer);
+    for (index, block) in [(2, "c\n"), (0, "a\n"), (1, "b\n")] {
+        output
+            .push(index, block.to_string())
+            .expect("writing to a Vec succeeds");
+    }
+    assert_eq!(buffer, b"a\nb\nc\n");
+    Ok(())
+}
+/// `commit_owned` hands back the record so the database can be saved right away.
+#[compio::test]
+async fn commit_owned_then_save() -> Result<(), Exn<InitError>> {
+    let tmp = tempfile::tempdir().or_raise(|| InitError {
+        message: "Failed to create temp dir".into(),
+    })?;
+    let db_path = tmp.path().join(DB_PATH);
+    let mut db = Database::new()?;
+    let record = db
+        .builder()
+        .with_fields(
+            PathBuf::from("owned.txt"),
+            hash_bytes(b"owned", HashAlgorithm::Sha256),
+            5,
+            chrono::Utc::now(),
+        )
+        .commit_owned()
+        .or_raise(|| InitError {
+            message: "Failed to commit record".into(),
+        })?;
+    db.save_to(&db_path).await.or_raise(|| InitError {
+        message: "Failed to save DB".into(),
+    })?;
+    assert_eq!(record, db.files[0]);
+    let loaded = Database::load(&db_path, &StoreOptions::default()).await?;
+    assert_eq!(loaded.files, vec![record]);
+    Ok(())
+}
+/// A tracked file that cannot be read fails the check with code 3 unless `--ignore-unreadable`.
+#[compio::test]
+async fn unreadable_file_sets_exit_code() -> Result<(), Exn<InitError>> {
+    use crate::add::{AddOptions, add_file};
+    use crate::verify::{UNREADABLE_EXIT_CODE, verify_records};
+    let tmp = tempfile::tempdir().or_raise(|| InitError {
+        message: "Failed to create temp dir".into(),
+    })?;
+    let good = tmp.path().join("good.txt");
+    let blocked = tmp.path().join("blocked");
+    for path in [&good, &blocked] {
+        std::fs::write(path, b"content").or_raise(|| InitError {
+            message: "Failed to write fixture".into(),
+        })?;
+    }
+    let mut db = Database::new()?;
+    add_file(&mut db, &good, &AddOptions::default()).await?;
+    add_file(&mut db, &blocked, &AddOptions::default()).await?;
+    // a directory opens but fails to read, even for root
+    std::fs::remove_file(&blocked).or_raise(|| InitError {
+        message: "Failed to remove fixture".into(),
+    })?;
+    std::fs::create_dir(&blocked).or_raise(|| InitError {
+        message: "Failed to create directory".into(),
+    })?;
+
+    let records: Vec<_> = db.files.iter().collect();
+    let report = verify_records(&db.root_dir, &records)
+        .await
+        .or_raise(|| InitError {
+            message: "Failed to verify".into(),
+        })?;
+    assert_eq!(report.ok, 1);
+    assert_eq!(report.unreadable, vec![blocked]);
+    assert!(report.missing.is_empty() && report.changed.is_empty());
+    assert_eq!(report.exit_code(false, false), UNREADABLE_EXIT_CODE);
+    assert_eq!(report.exit_code(false, true), 0);
+    Ok(())
+}
+/// `export --split-by-dir` puts each top-level directory's records in its own part.
+#[test]
+fn split_export_partitions_by_top_dir() -> Result<(), Exn<InitError>> {
+    use crate::models::ROOT_GROUP;
+    let mut db = Database::new()?;
+    let root = db.root_dir.clone();
+    let now = chrono::Utc::now();
+    for path in ["docs/a.md", "docs/guide/b.md", "src/main.rs", "README.md"] {
+        // records may store paths relative to the root as well as absolute ones
+        let stored = match path.starts_with("src") {
+            true => PathBuf::from(path),
+            false => root.join(path),
+        };
+        db.builder()
+            .with_fields(
+                stored,
+                hash_bytes(path.as_bytes(), HashAlgorithm::Sha256),
+                1,
+                now,
+            )
+            .commit()
+            .or_raise(|| InitError {
+                message: "Failed to commit record".into(),
+            })?;
+    }
+    let parts = db.split_by_top_dir();
+    let names: Vec<_> = parts.iter().map(|(name, _)| name.as_str()).collect();
+    assert_eq!(names, vec![ROOT_GROUP, "docs", "src"]);
+    let paths = |part: &Database| {
+        part.files
+            .iter()
+            .map(|record| {
+                let path = record.path.strip_prefix(&root).unwrap_or(&record.path);
+                path.to_path_buf()
+            })
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(paths(&parts[0].1), vec![PathBuf::from("README.md")]);
+    assert_eq!(
+        paths(&parts[1].1),
+        vec![PathBuf::from("docs/a.md"), PathBuf::from("docs/guide/b.md")]
+    );
+    assert_eq!(paths(&parts[2].1), vec![PathBuf::from("src/main.rs")]);
+    assert!(parts.iter().all(|(_, part)| part.root_dir == root));
+    Ok(())
+}
+/// `dupes --min-size` drops small duplicate groups and sorts the rest by wasted space.
+#[test]
+fn dupes_filters_by_min_size() -> Result<(), Exn<InitError>> {
+    let mut db = Database::new()?;
+    let now = chrono::Utc::now();
+    let records = [
+        ("stub/a.conf", "stub", 4),
+        ("stub/b.conf", "stub", 4),
+        ("stub/c.conf", "stub", 4),
+        ("big/a.iso", "image", 200),
+        ("big/b.iso", "image", 200),
+        ("mid/a.bin", "blob", 90),
+        ("mid/b.bin", "blob", 90),
+        ("unique.txt", "unique", 150),
+    ];
+    for (path, content, size) in records {
+        db.builder()
+            .with_fields(
+                PathBuf::from(path),
+                hash_bytes(content.as_bytes(), HashAlgorithm::Sha256),
+                size,
+                now,
+            )
+            .commit()
+            .or_raise(|| InitError {
+                message: "Failed to commit record".into(),
+            })?;
+    }
+    let all = db.duplicate_groups(0);
+    let wasted: Vec<_> = all.iter().map(|group| group.wasted()).collect();
+    assert_eq!(wasted, vec![200, 90, 8]);
+
+    let large = db.duplicate_groups(100);
+    assert_eq!(large.len(), 1);
+    assert_eq!(
+        large[0].paths,
+        vec![PathBuf::from("big/a.iso"), PathBuf::from("big/b.iso")]
+    );
+    assert_eq!(large[0].hash, hash_bytes(b"image", HashAlgorithm::Sha256));
+    Ok(())
+}
+/// Every save keeps the previous database as a rotating backup, and a
+/// corrupt database is restored from the newest valid one under `--recover`.
+#[compio::test]
+async fn recover_restores_corrupt_database() -> Result<(), Exn<InitError>> {
+    use crate::database::{backup_paths, parse_database_file};
+    let tmp = tempfile::tempdir().or_raise(|| InitError {
+        message: "Failed to create temp dir".into(),
+    })?;
+    let path = tmp.path().join(DB_PATH);
+    let mut db = Database::init_at(&path, &StoreOptions::default(), false).await?;
+    let mut saved = Vec::new();
+    for name in ["one.txt", "two.txt", "three.txt", "four.txt"] {
+        db.builder()
+            .with_fields(
+                PathBuf::from(name),
+                hash_bytes(name.as_bytes(), HashAlgorithm::Sha256),
+                1,
+                chrono::Utc::now(),
+            )
+            .commit()
+            .or_raise(|| InitError {
+                message: "Failed to commit record".into(),
+            })?;
+        db.save().await.or_raise(|| InitError {
+            message: "Failed to save DB".into(),
+        })?;
+        saved.push(db.files.clone());
+    }
+    // the empty initial database and the first save have rotated out
+    let backups = backup_paths(&path);
+    let names: Vec<_> = backups
+        .iter()
+        .map(|backup| backup.file_name().unwrap().to_string_lossy().into_owned())
+        .collect();
+    assert_eq!(
+        names,
+        [
+            ".tamashii.json.bak",
+            ".tamashii.json.bak.1",
+            ".tamashii.json.bak.2"
+        ]
+    );
+    let oldest = Database::load(&backups[2], &StoreOptions::default()).await?;
+    assert_eq!(oldest.files, saved[0]);
+
+    std::fs::write(&path, br#"{"version": "1.0.0", "files": [{"#).or_raise(|| InitError {
+        message: "Failed to corrupt DB".into(),
+    })?;
+    let err = parse_database_file(&path, &StoreOptions::default())
+        .await
+        .expect_err("a corrupt database should not load");
+    assert!(format!("{:?}", err).contains("--recover"));
+
+    let recover = StoreOptions {
+        recover: true,
+        ..StoreOptions::default()
+    };
+    let recovered = parse_database_file(&path, &recover)
+        .await
+        .or_raise(|| InitError {
+            message: "Failed to recover DB".into(),
+        })?;
+    // the newest backup is the database as it was before the last save
+    assert_eq!(recovered.files, saved[2]);
+    // the restored database was written back, so it now loads normally
+    let reloaded = Database::load(&path, &StoreOptions::default()).await?;
+    assert_eq!(reloaded.files.len(), 3);
+    Ok(())
+}
+/// A database that is only read, like an `import` source, is never
+/// recovered or written back, whatever the primary store's flags say.
+#[compio::test]
+async fn read_only_store_leaves_other_database_alone() -> Result<(), Exn<InitError>> {
+    let tmp = tempfile::tempdir().or_raise(|| InitError {
+        message: "Failed to create temp dir".into(),
+    })?;
+    let other = tmp.path().join("other.json");
+    let mut db = Database::init_at(&other, &StoreOptions::default(), false).await?;
+    db.mark_dirty();
+    db.save().await.or_raise(|| InitError {
+        message: "Failed to save DB".into(),
+    })?;
+    let corrupt = br#"{"version": "1.0.0", "files": [{"#;
+    std::fs::write(&other, corrupt).or_raise(|| InitError {
+        message: "Failed to corrupt DB".into(),
+    })?;
+
+    assert!(
+        Database::load(&other, &StoreOptions::read_only())
+            .await
+            .is_err()
+    );
+    assert_eq!(std::fs::read(&other).unwrap(), corrupt);
+
+    let mut loaded = Database::load(
+        &tmp.path().join("other.json.bak"),
+        &StoreOptions::read_only(),
+    )
+    .await?;
+    loaded.mark_dirty();
+    assert!(loaded.save().await.is_err());
+    Ok(())
+}
+/// `init` creates the database file, refuses to replace one, and replaces it with `--force`.
+#[compio::test]
+async fn init_creates_and_guards_database() -> Result<(), Exn<InitError>> {
+    let tmp = tempfile::tempdir().or_raise(|| InitError {
+        message: "Failed to create temp dir".into(),
+    })?;
+    let path = tmp.path().join(DB_PATH);
+    let options = StoreOptions::default();
+
+    let created = Database::init_at(&path, &options, false).await?;
+    assert!(path.exists());
+    let loaded = Database::load(&path, &options).await?;
+    assert_eq!(loaded.version, VERSION);
+    assert_eq!(loaded.created_at, created.created_at);
+    assert!(loaded.files.is_empty());
+
+    let mut tracked = loaded;
+    tracked
+        .builder()
+        .with_fields(
+            PathBuf::from("kept.txt"),
+            hash_bytes(b"kept", HashAlgorithm::Sha256),
+            4,
+            chrono::Utc::now(),
+        )
+        .commit()
+        .or_raise(|| InitError {
+            message: "Failed to commit record".into(),
+        })?;
+    tracked.save_to(&path).await.or_raise(|| InitError {
+        message: "Failed to save DB".into(),
+    })?;
+    let err = Database::init_at(&path, &options, false)
+        .await
+        .expect_err("init should refuse an existing database");
+    assert!(format!("{:?}", err).contains("already exists"));
+    assert_eq!(Database::load(&path, &options).await?.files.len(), 1);
+
+    Database::init_at(&path, &options, true).await?;
+    assert!(Database::load(&path, &options).await?.files.is_empty());
+    Ok(())
+}
+/// `verify --all` classifies each failed file and fails the run when any file changed or vanished.
+#[compio::test]
+async fn verify_all_classifies_failures() -> Result<(), Exn<InitError>> {
+    use crate::add::{AddOptions, add_file};
+    use crate::errors::VerificationError;
+    use crate::verify::verify_records;
+    let tmp = tempfile::tempdir().or_raise(|| InitError {
+        message: "Failed to create temp dir".into(),
+    })?;
+    let mut db = Database::new()?;
+    for name in ["same.txt", "edited.txt", "deleted.txt"] {
+        let path = tmp.path().join(name);
+        std::fs::write(&path, name).or_raise(|| InitError {
+            message: "Failed to write fixture".into(),
+        })?;
+        add_file(&mut db, &path, &AddOptions::default()).await?;
+    }
+    std::fs::write(tmp.path().join("edited.txt"), b"new content").or_raise(|| InitError {
+        message: "Failed to modify fixture".into(),
+    })?;
+    std::fs::remove_file(tmp.path().join("deleted.txt")).or_raise(|| InitError {
+        message: "Failed to remove fixture".into(),
+    })?;
+
+    let records: Vec<_> = db.files.iter().collect();
+    let report = verify_records(&db.root_dir, &records)
+        .await
+        .or_raise(|| InitError {
+            message: "Failed to verify".into(),
+        })?;
+    let failures: Vec<_> = report.results.iter().map(|r| r.failure()).collect();
+    assert!(failures[0].is_none());
+    assert!(matches!(
+        &failures[1],
+        Some(VerificationError::HashMissMatched { path }) if path.ends_with("edited.txt")
+    ));
+    assert!(matches!(failures[2], Some(VerificationError::IoFailure)));
+    assert_eq!(
+        (report.ok, report.changed.len(), report.missing.len()),
+        (1, 1, 1)
+    );
+    assert_eq!(report.exit_code(false, false), 1);
+    Ok(())
+}
+/// `remove` drops the matching record and leaves the others tracked.
+#[test]
+fn remove_by_path_drops_record() -> Result<(), Exn<InitError>> {
+    use crate::paths::PathMatch;
+    let mut db = Database::new()?;
+    let earlier = chrono::DateTime::<chrono::Utc>::UNIX_EPOCH;
+    for name in ["keep.txt", "drop.txt"] {
+        db.builder()
+            .with_fields(
+                PathBuf::from(name),
+                hash_bytes(name.as_bytes(), HashAlgorithm::Sha256),
+                1,
+                earlier,
+            )
+            .commit()
+            .or_raise(|| InitError {
+                message: "Failed to commit record".into(),
+            })?;
+    }
+    db.updated_at = earlier;
+    let removed = db
+        .remove_by_path(Path::new("drop.txt"), PathMatch::default())
+        .expect("drop.txt is tracked");
+    assert_eq!(removed.path, PathBuf::from("drop.txt"));
+    assert_eq!(db.files.len(), 1);
+    assert_eq!(db.files[0].path, PathBuf::from("keep.txt"));
+    assert!(db.updated_at > earlier);
+
+    db.updated_at = earlier;
+    assert!(
+        db.remove_by_path(Path::new("drop.txt"), PathMatch::default())
+            .is_none()
+    );
+    assert_eq!(db.updated_at, earlier);
+    Ok(())
+}
+/// `list --json` prints records that parse back into the same `Vec<FileRecord>`.
+#[test]
+fn list_json_round_trips() -> Result<(), Exn<InitError>> {
+    let mut db = Database::new()?;
+    for name in ["a.txt", "nested/b.txt"] {
+        db.builder()
+            .with_fields(
+                PathBuf::from(name),
+                hash_bytes(name.as_bytes(), HashAlgorithm::Sha256),
+                3,
+                chrono::Utc::now(),
+            )
+            .commit()
+            .or_raise(|| InitError {
+                message: "Failed to commit record".into(),
+            })?;
+    }
+    let json = db.files_json().or_raise(|| InitError {
+        message: "Failed to list records".into(),
+    })?;
+    let parsed: Vec<crate::models::FileRecord> =
+        serde_json::from_str(&json).or_raise(|| InitError {
+            message: "Failed to parse listed records".into(),
+        })?;
+    assert_eq!(parsed, db.files);
+    Ok(())
+}
+/// Hashing a file chunk by chunk gives the same digest as hashing it whole.
+#[compio::test]
+async fn streaming_hash_matches_whole_file_hash() {
+    use crate::hash::{HASH_CHUNK_SIZE, hash_file_streaming};
+    let tmp = tempfile::tempdir().expect("temp dir");
+    let path = tmp.path().join("large.bin");
+    let contents: Vec<u8> = (0..3 * 1024 * 1024 + HASH_CHUNK_SIZE / 3)
+        .map(|i| (i % 251) as u8)
+        .collect();
+    std::fs::write(&path, &contents).expect("write large file");
+    let file = compio::fs::File::open(&path)
+        .await
+        .expect("open large file");
+    let streamed = hash_file_streaming(&file, HashAlgorithm::Sha256)
+        .await
+        .expect("hash large file");
+    assert_eq!(streamed, hash_bytes(&contents, HashAlgorithm::Sha256));
+}
+/// Each algorithm gives its published digest for `abc`, whole or streamed.
+#[compio::test]
+async fn hash_algorithms_are_stable() {
+    let expected = [
+        (
+            HashAlgorithm::Sha256,
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+        ),
+        (
+            HashAlgorithm::Sha512,
+            "ddaf35a193617abacc417349ae20413112e6fa4e89a97ea20a9eeee64b55d39a\
+             2192992a274fc1a836ba3c23a3feebbd454d4423643ce80e2a9ac94fa54ca49f",
+        ),
+        (
+            HashAlgorithm::Blake3,
+            "6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85",
+        ),
+        (HashAlgorithm::Xxh3, "78af5f94892f3950"),
+    ];
+    let tmp = tempfile::tempdir().expect("temp dir");
+    let path = tmp.path().join("abc.txt");
+    std::fs::write(&path, b"abc").expect("write file");
+    let file = compio::fs::File::open(&path).await.expect("open file");
+    for (algo, digest) in expected {
+        assert_eq!(hash_bytes(b"abc", algo).0, digest, "{}", algo);
+        let streamed = crate::hash::hash_file_streaming(&file, algo)
+            .await
+            .expect("hash file");
+        assert_eq!(streamed.0, digest, "{}", algo);
+    }
+}
+/// A database keeps the algorithm it was created with, and `--algo` must match it to load.
+#[compio::test]
+async fn database_rejects_mismatched_hash_algo() -> Result<(), Exn<InitError>> {
+    use crate::database::StoreOptions;
+    let tmp = tempfile::tempdir().or_raise(|| InitError {
+        message: "Failed to create temp dir".into(),
+    })?;
+    let db_path = tmp.path().join(".tamashii.json");
+    let blake3 = StoreOptions {
+        hash_algo: Some(HashAlgorithm::Blake3),
+        ..StoreOptions::default()
+    };
+    let db = Database::init_at(&db_path, &blake3, false).await?;
+    assert_eq!(db.hash_algo, HashAlgorithm::Blake3);
+
+    let loaded = Database::load(&db_path, &StoreOptions::default()).await?;
+    assert_eq!(loaded.hash_algo, HashAlgorithm::Blake3);
+    assert!(Database::load(&db_path, &blake3).await.is_ok());
+    let sha256 = StoreOptions {
+        hash_algo: Some(HashAlgorithm::Sha256),
+        ..StoreOptions::default()
+    };
+    let err = Database::load(&db_path, &sha256).await.unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("was hashed with blake3, not sha256")
+    );
+
+    // databases written before the field existed are SHA-256
+    let legacy: Database = serde_json::from_str(
+        r#"{"version":"1.0.0","root_dir":"/","created_at":"2024-01-01T00:00:00Z","updated_at":"2024-01-01T00:00:00Z","files":[]}"#,
+    )
+    .or_raise(|| InitError {
+        message: "Failed to parse legacy database".into(),
+    })?;
+    assert_eq!(legacy.hash_algo, HashAlgorithm::Sha256);
+    Ok(())
+}
+/// Re-adding a tree updates changed tracked files in place; an empty tree adds nothing.
+#[compio::test]
+async fn recursive_readd_updates_tracked_files() -> Result<(), Exn<InitError>> {
+    use crate::add::{AddOptions, AddOutcome, add_file};
+    use files::{WalkOptions, walk_files};
+    let tmp = tempfile::tempdir().or_raise(|| InitError {
+        message: "Failed to create temp dir".into(),
+    })?;
+    let root = tmp.path().join("tree");
+    std::fs::create_dir_all(root.join("nested")).or_raise(|| InitError {
+        message: "Failed to create fixture dir".into(),
+    })?;
+    std::fs::create_dir_all(tmp.path().join("empty/inner")).or_raise(|| InitError {
+        message: "Failed to create fixture dir".into(),
+    })?;
+    for file in ["a.txt", "nested/b.txt"] {
+        std::fs::write(root.join(file), file).or_raise(|| InitError {
+            message: "Failed to write fixture".into(),
+        })?;
+    }
+    let walk = WalkOptions {
+        db_path: PathBuf::from(DB_PATH),
+        exclude_vcs: false,
+        ignore: Vec::new(),
+        follow_symlinks: false,
+    };
+    let mut db = Database::new()?;
+    assert!(
+        walk_files(&tmp.path().join("empty"), &walk)
+            .next()
+            .is_none()
+    );
+
+    let add_tree = async |db: &mut Database| -> Result<Vec<AddOutcome>, Exn<InitError>> {
+        let mut outcomes = Vec::new();
+        for path in walk_files(&root, &walk) {
+            let path = path.or_raise(|| InitError {
+                message: "Failed to walk fixture".into(),
+            })?;
+            outcomes.push(add_file(db, &path, &AddOptions::default()).await?);
+        }
+        Ok(outcomes)
+    };
+    assert_eq!(add_tree(&mut db).await?, vec![AddOutcome::Added; 2]);
+    let id = db.files[0].id.clone();
+
+    std::fs::write(&db.files[0].path, "rewritten contents").or_raise(|| InitError {
+        message: "Failed to rewrite fixture".into(),
+    })?;
+    let mut outcomes = add_tree(&mut db).await?;
+    outcomes.sort_by_key(|outcome| *outcome == AddOutcome::Unchanged);
+    assert_eq!(outcomes, vec![AddOutcome::Updated, AddOutcome::Unchanged]);
+    assert_eq!(db.files.len(), 2);
+    assert_eq!(db.files[0].id, id);
+    assert_eq!(
+        db.files[0].hash,
+        hash_bytes(b"rewritten contents", HashAlgorithm::Sha256)
+    );
+    Ok(())
+}
+/// Adding the same path twice keeps a single record holding the latest hash.
+#[compio::test]
+async fn readd_same_path_keeps_one_record() -> Result<(), Exn<InitError>> {
+    use crate::add::{AddOptions, add_file};
+    let tmp = tempfile::tempdir().or_raise(|| InitError {
+        message: "Failed to create temp dir".into(),
+    })?;
+    let path = tmp.path().join("foo.txt");
+    let mut db = Database::new()?;
+    for contents in ["first", "second version"] {
+        std::fs::write(&path, contents).or_raise(|| InitError {
+            message: "Failed to write fixture".into(),
+        })?;
+        add_file(&mut db, &path, &AddOptions::default()).await?;
+    }
+    assert_eq!(db.files.len(), 1);
+    assert_eq!(
+        db.files[0].hash,
+        hash_bytes(b"second version", HashAlgorithm::Sha256)
+    );
+
+    let id = db.files[0].id.clone();
+    db.builder()
+        .with_fields(
+            path.clone(),
+            hash_bytes(b"third", HashAlgorithm::Sha256),
+            5,
+            chrono::Utc::now(),
+        )
+        .commit()
+        .or_raise(|| InitError {
+            message: "Failed to commit record".into(),
+        })?;
+    assert_eq!(db.files.len(), 1);
+    assert_eq!(db.files[0].id, id);
+    assert_eq!(
+        db.files[0].hash,
+        hash_bytes(b"third", HashAlgorithm::Sha256)
+    );
+    Ok(())
+}
+/// A database created at a custom path is saved there and loads back from it.
+#[compio::test]
+async fn database_at_custom_path() -> Result<(), Exn<InitError>> {
+    use crate::database::StoreOptions;
+    let tmp = tempfile::tempdir().or_raise(|| InitError {
+        message: "Failed to create temp dir".into(),
+    })?;
+    let db_path = tmp.path().join("custom-db.json");
+    let options = StoreOptions {
+        db_path: db_path.clone(),
+        ..StoreOptions::default()
+    };
+    let mut db = Database::get_or_create_db(&db_path, &options).await?;
+    db.builder()
+        .with_fields(
+            PathBuf::from("tracked.txt"),
+            hash_bytes(b"tracked", HashAlgorithm::Sha256),
+            7,
+            chrono::Utc::now(),
+        )
+        .commit()
+        .or_raise(|| InitError {
+            message: "Failed to commit record".into(),
+        })?;
+    db.save().await.or_raise(|| InitError {
+        message: "Failed to save database".into(),
+    })?;
+    assert!(db_path.exists());
+
+    let mut loaded = Database::load(&db_path, &StoreOptions::default()).await?;
+    assert_eq!(loaded.files, db.files);
+    assert_eq!(loaded.options.db_path, db_path);
+    assert!(
+        loaded
+            .remove_by_path(Path::new("tracked.txt"), PathMatch::default())
+            .is_some()
+    );
+    loaded.save().await.or_raise(|| InitError {
+        message: "Failed to save database".into(),
+    })?;
+    let reloaded = Database::load(&db_path, &StoreOptions::default()).await?;
+    assert!(reloaded.files.is_empty());
+    Ok(())
+}
+/// Each record is verified with the algorithm it was hashed with, not the database default.
+#[compio::test]
+async fn verify_uses_each_records_algorithm() -> Result<(), Exn<InitError>> {
+    use crate::add::{AddOptions, add_file};
+    use crate::verify::verify_records;
+    let tmp = tempfile::tempdir().or_raise(|| InitError {
+        message: "Failed to create temp dir".into(),
+    })?;
+    let old = tmp.path().join("old.txt");
+    let new = tmp.path().join("new.txt");
+    for path in [&old, &new] {
+        std::fs::write(path, path.to_string_lossy().as_bytes()).or_raise(|| InitError {
+            message: "Failed to write fixture".into(),
+        })?;
+    }
+    let mut db = Database::new()?;
+    add_file(&mut db, &old, &AddOptions::default()).await?;
+    db.hash_algo = HashAlgorithm::Blake3;
+    add_file(&mut db, &new, &AddOptions::default()).await?;
+    assert_eq!(db.files[0].algo, HashAlgorithm::Sha256);
+    assert_eq!(db.files[1].algo, HashAlgorithm::Blake3);
+    assert_ne!(
+        db.files[1].hash,
+        hash_bytes(new.to_string_lossy().as_bytes(), HashAlgorithm::Sha256)
+    );
+
+    let records: Vec<_> = db.files.iter().collect();
+    let report = verify_records(&db.root_dir, &records)
+        .await
+        .or_raise(|| InitError {
+            message: "Failed to verify records".into(),
+        })?;
+    assert_eq!(report.ok, 2);
+
+    let mut legacy = serde_json::to_value(&db.files[0]).or_raise(|| InitError {
+        message: "Failed to serialize record".into(),
+    })?;
+    legacy
+        .as_object_mut()
+        .expect("record is an object")
+        .remove("algo");
+    let legacy: crate::models::FileRecord =
+        serde_json::from_value(legacy).or_raise(|| InitError {
+            message: "Failed to parse legacy record".into(),
+        })?;
+    assert_eq!(legacy.algo, HashAlgorithm::Sha256);
+    Ok(())
+}
+/// `rehash --all` updates only changed files and lists the ones already current.
+#[compio::test]
+async fn rehash_changed_updates_only_changed() -> Result<(), Exn<InitError>> {
+    use crate::add::{AddOptions, add_file, rehash_changed};
+    let tmp = tempfile::tempdir().or_raise(|| InitError {
+        message: "Failed to create temp dir".into(),
+    })?;
+    let paths: Vec<PathBuf> = ["edited.txt", "kept.txt", "deleted.txt"]
+        .iter()
+        .map(|name| tmp.path().join(name))
+        .collect();
+    let mut db = Database::new()?;
+    for path in paths.iter() {
+        std::fs::write(path, b"original").or_raise(|| InitError {
+            message: "Failed to write fixture".into(),
+        })?;
+        add_file(&mut db, path, &AddOptions::default()).await?;
+    }
+    std::fs::write(&paths[0], b"edited").or_raise(|| InitError {
+        message: "Failed to modify fixture".into(),
+    })?;
+    std::fs::remove_file(&paths[2]).or_raise(|| InitError {
+        message: "Failed to delete fixture".into(),
+    })?;
+    let before = db.files[2].clone();
+
+    let (updated, unchanged) = rehash_changed(&mut db).await?;
+    assert_eq!(updated, vec![paths[0].clone()]);
+    assert_eq!(unchanged, vec![paths[1].clone()]);
+    assert_eq!(
+        db.files[0].hash,
+        hash_bytes(b"edited", HashAlgorithm::Sha256)
+    );
+    assert_eq!(db.files[2], before);
+
+    let (updated, unchanged) = rehash_changed(&mut db).await?;
+    assert!(updated.is_empty());
+    assert_eq!(unchanged, paths[..2]);
+    Ok(())
+}
+/// Records carry the file's mtime, falling back to the index time when it is unavailable.
+#[compio::test]
+async fn records_modification_time() -> Result<(), Exn<InitError>> {
+    use crate::add::{AddOptions, add_file, modified_time};
+    let indexed_at = chrono::DateTime::parse_from_rfc3339("2024-05-01T12:00:00Z")
+        .expect("timestamp should parse")
+        .to_utc();
+    let unsupported = std::io::Error::new(std::io::ErrorKind::Unsupported, "no mtime");
+    assert_eq!(modified_time(Err(unsupported), indexed_at), indexed_at);
+    assert_eq!(
+        modified_time(Ok(std::time::UNIX_EPOCH), indexed_at),
+        indexed_at
+    );
+    let mtime = std::time::UNIX_EPOCH + std::time::Duration::from_secs(86_400);
+    assert_eq!(
+        modified_time(Ok(mtime), indexed_at),
+        chrono::DateTime::<chrono::Utc>::from(mtime)
+    );
+
+    let tmp = tempfile::tempdir().or_raise(|| InitError {
+        message: "Failed to create temp dir".into(),
+    })?;
+    let path = tmp.path().join("dated.txt");
+    std::fs::write(&path, b"dated").or_raise(|| InitError {
+        message: "Failed to write fixture".into(),
+    })?;
+    let mut db = Database::new()?;
+    add_file(&mut db, &path, &AddOptions::default()).await?;
+    let modified_at = db.files[0].modified_at.expect("every record has an mtime");
+    let shown = modified_at.format("%Y-%m-%d %H:%M:%S").to_string();
+    assert!(
+        db.files[0]
+            .to_string()
+            .contains(&format!("Modified: {}", shown))
+    );
+    assert!(
+        db.status_frame(None, None)
+            .contains(&format!("Modified: {}", shown))
+    );
+    Ok(())
+}
+/// A record is stamped with the time it was indexed, not the file's own times.
+#[compio::test]
+async fn add_stamps_index_time() -> Result<(), Exn<InitError>> {
+    use crate::add::{AddOptions, add_file};
+    let tmp = tempfile::tempdir().or_raise(|| InitError {
+        message: "Failed to create temp dir".into(),
+    })?;
+    let path = tmp.path().join("old.txt");
+    std::fs::write(&path, b"old").or_raise(|| InitError {
+        message: "Failed to write fixture".into(),
+    })?;
+    let mtime = std::time::UNIX_EPOCH + std::time::Duration::from_secs(86_400);
+    let set_mtime = |path: &Path| {
+        std::fs::File::options()
+            .write(true)
+            .open(path)
+            .and_then(|file| file.set_modified(mtime))
+            .or_raise(|| InitError {
+                message: "Failed to set mtime".into(),
+            })
+    };
+    set_mtime(&path)?;
+    let mut db = Database::new()?;
+    db.root_dir = tmp.path().to_path_buf();
+    let before = chrono::Utc::now();
+    add_file(&mut db, &path, &AddOptions::default()).await?;
+    // the file's own times are decades older
+    assert!(db.files[0].time_stamp >= before);
+
+    // an update is stamped anew as well
+    std::fs::write(&path, b"new").or_raise(|| InitError {
+        message: "Failed to write fixture".into(),
+    })?;
+    set_mtime(&path)?;
+    let first = db.files[0].time_stamp;
+    let outcome = add_file(
+        &mut db,
+        &path,
+        &AddOptions {
+            force: true,
+            ..AddOptions::default()
+        },
+    )
+    .await?;
+    assert_eq!(outcome, crate::add::AddOutcome::Updated);
+    assert!(db.files[0].time_stamp >= first);
+    Ok(())
+}
+/// `status --json` emits the database summary with one entry per tracked file.
+#[test]
+fn status_json_reports_files() -> Result<(), Exn<InitError>> {
+    let mut db = Database::new()?;
+    for name in ["a.txt", "b.txt"] {
+        db.builder()
+            .with_fields(
+                PathBuf::from(name),
+                hash_bytes(name.as_bytes(), HashAlgorithm::Sha256),
+                5,
+                chrono::Utc::now(),
+            )
+            .commit()
+            .or_raise(|| InitError {
+                message: "Failed to commit record".into(),
+            })?;
+    }
+    let json = db.status_json().or_raise(|| InitError {
+        message: "Failed to render status".into(),
+    })?;
+    let parsed: serde_json::Value = serde_json::from_str(&json).or_raise(|| InitError {
+        message: "Failed to parse status".into(),
+    })?;
+    assert_eq!(parsed["file_count"], 2);
+    assert_eq!(parsed["version"], VERSION);
+    assert_eq!(parsed["files"].as_array().map(Vec::len), Some(2));
+    assert_eq!(parsed["files"][1]["path"], "b.txt");
+    assert_eq!(parsed["files"][1]["hash"], db.files[1].hash.0.as_str());
+    assert_eq!(parsed["files"][1]["size"], 5);
+    assert_eq!(parsed["total_size"], 10);
+    assert!(parsed["created_at"].is_string() && parsed["root_dir"].is_string());
+    Ok(())
+}
+/// `ct_eq` agrees with `==` on equal, unequal and differently sized hashes.
+#[test]
+fn hex_string_ct_eq() {
+    let a = hash_bytes(b"a", HashAlgorithm::Sha256);
+    let b = hash_bytes(b"b", HashAlgorithm::Sha256);
+    assert!(a.ct_eq(&a.clone()));
+    assert!(!a.ct_eq(&b));
+    let mut last_differs = a.clone();
+    last_differs.0.pop();
+    last_differs
+        .0
+        .push(if a.0.ends_with('0') { '1' } else { '0' });
+    assert!(!a.ct_eq(&last_differs));
+    assert!(!a.ct_eq(&hash_bytes(b"a", HashAlgorithm::Sha512)));
+    assert!(!a.ct_eq(&crate::models::HexStirng(a.0[..8].to_string())));
+}
+/// `.tamashiiignore` rules skip matching files and directories during a walk.
+#[test]
+fn walk_honors_ignore_file() -> Result<(), Exn<InitError>> {
+    use files::{WalkOptions, is_ignored, load_ignore_patterns, parse_ignore_patterns, walk_files};
+    let tmp = tempfile::tempdir().or_raise(|| InitError {
+        message: "Failed to create temp dir".into(),
+    })?;
+    let root = tmp.path();
+    for dir in ["src/build", "build", "docs/drafts"] {
+        std::fs::create_dir_all(root.join(dir)).or_raise(|| InitError {
+            message: "Failed to create fixture dir".into(),
+        })?;
+    }
+    for file in [
+        "main.rs",
+        "debug.log",
+        "keep.log",
+        "src/lib.rs",
+        "src/trace.log",
+        "src/build/out.o",
+        "build/out.o",
+        "docs/guide.md",
+        "docs/drafts/todo.md",
+    ] {
+        std::fs::write(root.join(file), file).or_raise(|| InitError {
+            message: "Failed to write fixture".into(),
+        })?;
+    }
+    std::fs::write(
+        root.join(files::IGNORE_FILE),
+        "# build output\n*.log\n!keep.log\n\nbuild/\n/docs/drafts\n",
+    )
+    .or_raise(|| InitError {
+        message: "Failed to write ignore file".into(),
+    })?;
+    let options = WalkOptions {
+        db_path: PathBuf::from(DB_PATH),
+        exclude_vcs: false,
+        ignore: load_ignore_patterns(root).or_raise(|| InitError {
+            message: "Failed to load ignore rules".into(),
+        })?,
+        follow_symlinks: false,
+    };
+    let mut paths = walk_files(root, &options)
+        .collect::<Result<Vec<_>, _>>()
+        .or_raise(|| InitError {
+            message: "Failed to walk fixture".into(),
+        })?;
+    paths.sort();
+    let expected: Vec<PathBuf> = [
+        files::IGNORE_FILE,
+        "docs/guide.md",
+        "keep.log",
+        "main.rs",
+        "src/lib.rs",
+    ]
+    .iter()
+    .map(|file| root.join(file))
+    .collect();
+    assert_eq!(paths, expected);
+
+    // a trailing slash only matches directories, and rules without one match both
+    let rules = parse_ignore_patterns("cache/\nnotes").or_raise(|| InitError {
+        message: "Failed to parse ignore rules".into(),
+    })?;
+    assert!(is_ignored(&rules, Path::new("a/cache"), true));
+    assert!(!is_ignored(&rules, Path::new("a/cache"), false));
+    assert!(is_ignored(&rules, Path::new("notes"), false));
+    assert!(is_ignored(&rules, Path::new("deep/notes"), true));
+    assert!(load_ignore_patterns(&root.join("src")).is_ok_and(|rules| rules.is_empty()));
+    assert!(parse_ignore_patterns("a[").is_err());
+    Ok(())
+}
+/// An exported database imports back unchanged, and conflicting hashes take the imported record.
+#[test]
+fn export_import_round_trip_and_conflicts() -> Result<(), Exn<InitError>> {
+    use crate::models::ImportSummary;
+    let mut db = Database::new()?;
+    db.root_dir = PathBuf::from("/srv/project");
+    for (name, contents) in [("notes.txt", "notes"), ("/etc/hosts", "hosts")] {
+        db.builder()
+            .with_fields(
+                PathBuf::from(name),
+                hash_bytes(contents.as_bytes(), HashAlgorithm::Sha256),
+                5,
+                chrono::Utc::now(),
+            )
+            .commit()
+            .or_raise(|| InitError {
+                message: "Failed to commit record".into(),
+            })?;
+    }
+    db.absolutize_paths();
+    assert_eq!(db.files[0].path, PathBuf::from("/srv/project/notes.txt"));
+    assert_eq!(db.files[1].path, PathBuf::from("/etc/hosts"));
+    let mut exported = Vec::new();
+    db.to_writer(&mut exported).or_raise(|| InitError {
+        message: "Failed to export database".into(),
+    })?;
+    let other = Database::from_reader(exported.as_slice()).or_raise(|| InitError {
+        message: "Failed to read export".into(),
+    })?;
+
+    let mut target = Database::new()?;
+    let summary = target.import_records(other.files.clone(), PathMatch::default());
+    assert_eq!(summary.added, 2);
+    assert_eq!(target.files, db.files);
+
+    let mut changed = other.files.clone();
+    changed[0].hash = hash_bytes(b"edited notes", HashAlgorithm::Sha256);
+    let summary = target.import_records(changed.clone(), PathMatch::default());
+    assert_eq!(
+        summary,
+        ImportSummary {
+            added: 0,
+            replaced: vec![changed[0].path.clone()],
+            unchanged: 1,
+        }
+    );
+    assert_eq!(target.files.len(), 2);
+    assert_eq!(target.files[0].hash, changed[0].hash);
+    Ok(())
+}
+/// Record ids are distinct RFC 4122 version 4 UUIDs.
+#[test]
+fn record_ids_are_uuid_v4() -> Result<(), Exn<InitError>> {
+    let mut db = Database::new()?;
+    for name in ["a.txt", "b.txt"] {
+        db.builder()
+            .with_fields(
+                PathBuf::from(name),
+                hash_bytes(name.as_bytes(), HashAlgorithm::Sha256),
+                5,
+                chrono::Utc::now(),
+            )
+            .commit()
+            .or_raise(|| InitError {
+                message: "Failed to commit record".into(),
+            })?;
+    }
+    for record in db.files.iter() {
+        let groups: Vec<&str> = record.id.split('-').collect();
+        assert_eq!(
+            groups.iter().map(|group| group.len()).collect::<Vec<_>>(),
+            vec![8, 4, 4, 4, 12],
+            "{}",
+            record.id
+        );
+        assert!(
+            groups
+                .iter()
+                .all(|group| group.chars().all(|c| matches!(c, '0'..='9' | 'a'..='f')))
+        );
+        assert!(groups[2].starts_with('4'), "{}", record.id);
+        assert!(groups[3].starts_with(['8', '9', 'a', 'b']), "{}", record.id);
+    }
+    assert_ne!(db.files[0].id, db.files[1].id);
+    Ok(())
+}
+
+/// `add_paths` adds every path it is given and carries on past the ones that fail.
+#[compio::test]
+async fn add_paths_adds_many_files_at_once() -> Result<(), Exn<InitError>> {
+    use crate::add::{AddOptions, AddOutcome, add_paths};
+    use crate::progress::Progress;
+    let tmp = tempfile::tempdir().or_raise(|| InitError {
+        message: "Failed to create temp dir".into(),
+    })?;
+    let mut paths: Vec<PathBuf> = (0..3)
+        .map(|i| tmp.path().join(format!("file-{}.txt", i)))
+        .collect();
+    for (i, path) in paths.iter().enumerate() {
+        std::fs::write(path, format!("contents {}", i)).or_raise(|| InitError {
+            message: "Failed to write fixture".into(),
+        })?;
+    }
+    let missing = tmp.path().join("missing.txt");
+    paths.insert(1, missing.clone());
+
+    let mut db = Database::new()?;
+    let mut added = Vec::new();
+    let failed = add_paths(
+        &mut db,
+        &paths,
+        &AddOptions::default(),
+        None,
+        &mut Progress::unbounded().quiet(true),
+        |path, outcome| {
+            assert_eq!(outcome, AddOutcome::Added);
+            added.push(path.to_path_buf());
+        },
+    )
+    .await;
+    assert_eq!(added.len(), 3);
+    assert_eq!(db.files.len(), 3);
+    assert_eq!(failed.len(), 1);
+    assert_eq!(failed[0].0, missing);
+
+    // a directory without walk options is refused, not walked
+    let failed = add_paths(
+        &mut db,
+        &[tmp.path().to_path_buf()],
+        &AddOptions::default(),
+        None,
+        &mut Progress::unbounded().quiet(true),
+        |_, _| {},
+    )
+    .await;
+    assert_eq!(failed.len(), 1);
+    assert_eq!(db.files.len(), 3);
+    Ok(())
+}
+
+/// A flag beats `tamashii.toml`, which beats the built-in default.
+#[compio::test]
+async fn config_precedence() -> Result<(), Exn<InitError>> {
+    use crate::config::Config;
+    use crate::hash::HashAlgorithm;
+    let tmp = tempfile::tempdir().or_raise(|| InitError {
+        message: "Failed to create temp dir".into(),
+    })?;
+    let path = tmp.path().join("tamashii.toml");
+
+    // no file: built-in defaults
+    let config = Config::load(&path)?;
+    assert_eq!(config, Config::default());
+    assert_eq!(config.db_path(None), PathBuf::from(database::DB_PATH));
+    assert_eq!(config.hash_algo(None), None);
+
+    let mut config = Config::default();
+    config.set("algo", &["blake3".into()])?;
+    config.set("db", &["custom.json".into()])?;
+    config.set("ignore", &["*.log".into(), "build/".into()])?;
+    config.save(&path)?;
+    let config = Config::load(&path)?;
+    assert_eq!(config.get("algo")?.as_deref(), Some("blake3"));
+    assert_eq!(config.get("ignore")?.as_deref(), Some("*.log\nbuild/"));
+    assert_eq!(config.ignore_patterns()?.len(), 2);
+
+    // config beats default
+    assert_eq!(config.db_path(None), PathBuf::from("custom.json"));
+    assert_eq!(config.hash_algo(None), Some(HashAlgorithm::Blake3));
+    // flag beats config
+    assert_eq!(
+        config.db_path(Some(PathBuf::from("flag.json"))),
+        PathBuf::from("flag.json")
+    );
+    assert_eq!(
+        config.hash_algo(Some(HashAlgorithm::Sha512)),
+        Some(HashAlgorithm::Sha512)
+    );
+
+    let mut config = config;
+    assert!(config.set("algo", &["md5".into()]).is_err());
+    assert!(config.set("colour", &["never".into()]).is_err());
+    assert!(config.get("colour").is_err());
+    std::fs::write(&path, "unknown = 1\n").or_raise(|| InitError {
+        message: "Failed to write fixture".into(),
+    })?;
+    assert!(Config::load(&path).is_err());
+    Ok(())
+}
+
+/// Records are stored relative to `root_dir`, so a database moved along with
+/// its tree still verifies, and absolute paths from older databases are converted.
+#[compio::test]
+async fn database_survives_moving_the_tree() -> Result<(), Exn<InitError>> {
+    use crate::add::{AddOptions, add_file};
+    use crate::models::FileRecord;
+    use crate::verify::verify_records;
+    let tmp = tempfile::tempdir().or_raise(|| InitError {
+        message: "Failed to create temp dir".into(),
+    })?;
+    let before = tmp.path().join("before");
+    std::fs::create_dir_all(before.join("sub")).or_raise(|| InitError {
+        message: "Failed to create fixture dir".into(),
+    })?;
+    for file in ["a.txt", "sub/b.txt", "legacy.txt"] {
+        std::fs::write(before.join(file), file).or_raise(|| InitError {
+            message: "Failed to write fixture".into(),
+        })?;
+    }
+    let mut db = Database::new()?;
+    db.root_dir = before.clone();
+    for file in ["a.txt", "sub/b.txt", "legacy.txt"] {
+        add_file(&mut db, &before.join(file), &AddOptions::default()).await?;
+    }
+    assert_eq!(db.files[1].path, PathBuf::from("sub/b.txt"));
+    // written by a version that stored the path as it was given
+    db.files[2].path = before.join("legacy.txt");
+    let db_path = before.join(DB_PATH);
+    db.save_to(&db_path).await.or_raise(|| InitError {
+        message: "Failed to save DB".into(),
+    })?;
+
+    let after = tmp.path().join("after");
+    std::fs::rename(&before, &after).or_raise(|| InitError {
+        message: "Failed to move the tree".into(),
+    })?;
+    let db = Database::load(&after.join(DB_PATH), &StoreOptions::default()).await?;
+    assert_eq!(db.root_dir, after);
+    assert_eq!(db.files[2].path, PathBuf::from("legacy.txt"));
+    let records: Vec<&FileRecord> = db.files.iter().collect();
+    let report = verify_records(&db.root_dir, &records)
+        .await
+        .or_raise(|| InitError {
+            message: "Failed to verify".into(),
+        })?;
+    assert_eq!(report.ok, 3);
+    assert_eq!(
+        db.tracked_path(&after.join("sub/b.txt")),
+        PathBuf::from("sub/b.txt")
+    );
+    Ok(())
+}
+
+/// A copy of a tree checks its own files, not the original's that still exist.
+#[compio::test]
+async fn copied_database_checks_the_copy() -> Result<(), Exn<InitError>> {
+    use crate::add::{AddOptions, add_file};
+    use crate::models::FileRecord;
+    use crate::verify::verify_records;
+    let tmp = tempfile::tempdir().or_raise(|| InitError {
+        message: "Failed to create temp dir".into(),
+    })?;
+    let original = tmp.path().join("original");
+    let copy = tmp.path().join("copy");
+    for dir in [&original, &copy] {
+        std::fs::create_dir_all(dir).or_raise(|| InitError {
+            message: "Failed to create fixture dir".into(),
+        })?;
+    }
+    std::fs::write(original.join("a.txt"), "original").or_raise(|| InitError {
+        message: "Failed to write fixture".into(),
+    })?;
+    let mut db = Database::new()?;
+    db.root_dir = original.clone();
+    add_file(&mut db, &original.join("a.txt"), &AddOptions::default()).await?;
+    db.save_to(&original.join(DB_PATH))
+        .await
+        .or_raise(|| InitError {
+            message: "Failed to save DB".into(),
+        })?;
+
+    for file in ["a.txt", DB_PATH] {
+        std::fs::copy(original.join(file), copy.join(file)).or_raise(|| InitError {
+            message: "Failed to copy the tree".into(),
+        })?;
+    }
+    std::fs::write(copy.join("a.txt"), "tampered").or_raise(|| InitError {
+        message: "Failed to tamper with the copy".into(),
+    })?;
+    let db = Database::load(&copy.join(DB_PATH), &StoreOptions::default()).await?;
+    assert_eq!(db.root_dir, copy);
+    let records: Vec<&FileRecord> = db.files.iter().collect();
+    let report = verify_records(&db.root_dir, &records)
+        .await
+        .or_raise(|| InitError {
+            message: "Failed to verify".into(),
+        })?;
+    assert_eq!(report.changed, vec![PathBuf::from("a.txt")]);
+    assert_eq!(report.ok, 0);
+    Ok(())
+}
+
+/// Symlinks are skipped and listed by default; followed on request, a cycle is cut short.
+#[cfg(unix)]
+#[test]
+fn walk_skips_or_follows_symlinks() {
+    use files::{WalkOptions, walk_files};
+    use std::os::unix::fs::symlink;
+    let tmp = tempfile::tempdir().expect("temp dir");
+    let root = tmp.path();
+    std::fs::create_dir(root.join("dir")).expect("create dir");
+    std::fs::write(root.join("dir/target.txt"), b"x").expect("write target");
+    symlink(root.join("dir/target.txt"), root.join("link.txt")).expect("file symlink");
+    // points back at its own parent, so following it naively never ends
+    symlink(root, root.join("dir/loop")).expect("cycle symlink");
+
+    let walk = |follow_symlinks| {
+        let options = WalkOptions {
+            follow_symlinks,
+            ..WalkOptions::default()
+        };
+        let mut walker = walk_files(root, &options);
+        let mut found: Vec<PathBuf> = walker.by_ref().map(|path| path.expect("walk")).collect();
+        let mut skipped = walker.skipped().to_vec();
+        found.sort();
+        skipped.sort();
+        (found, skipped)
+    };
+
+    let (found, skipped) = walk(false);
+    assert_eq!(found, vec![root.join("dir/target.txt")]);
+    assert_eq!(skipped, vec![root.join("dir/loop"), root.join("link.txt")]);
+
+    let (found, skipped) = walk(true);
+    assert_eq!(
+        found,
+        vec![root.join("dir/target.txt"), root.join("link.txt")]
+    );
+    assert_eq!(skipped, vec![root.join("dir/loop")]);
+}
+
+/// `total_size` sums every record, and `format_size` picks a binary unit for it.
+#[test]
+fn total_size_sums_records() -> Result<(), Exn<InitError>> {
+    use crate::models::format_size;
+    let mut db = Database::new()?;
+    assert_eq!(db.total_size(), 0);
+    for (name, size) in [("a.txt", 200), ("b.txt", 255), ("c.txt", 45)] {
+        db.builder()
+            .with_fields(
+                PathBuf::from(name),
+                hash_bytes(name.as_bytes(), HashAlgorithm::Sha256),
+                size,
+                chrono::Utc::now(),
+            )
+            .commit()
+            .or_raise(|| InitError {
+                message: "Failed to commit record".into(),
+            })?;
+    }
+    assert_eq!(db.total_size(), 500);
+
+    assert_eq!(format_size(500), "500 B");
+    assert_eq!(format_size(1536), "1.5 KiB");
+    assert_eq!(format_size(3 * 1024 * 1024), "3.0 MiB");
+    assert_eq!(format_size(5 * 1024 * 1024 * 1024), "5.0 GiB");
+    assert_eq!(format_size(u64::MAX), "16777216.0 TiB");
+    Ok(())
+}
+
+/// Files over 255 bytes should keep their full size in the record and the total.
+#[compio::test]
+async fn large_file_sizes_are_not_truncated() -> Result<(), Exn<InitError>> {
+    use crate::add::{AddOptions, add_file};
+    let tmp = tempfile::tempdir().or_raise(|| InitError {
+        message: "Failed to create temp dir".into(),
+    })?;
+    let mut db = Database::new()?;
+    for (name, len) in [("big.bin", 70_000), ("small.bin", 300)] {
+        let path = tmp.path().join(name);
+        std::fs::write(&path, vec![7u8; len]).or_raise(|| InitError {
+            message: "Failed to write fixture".into(),
+        })?;
+        add_file(&mut db, &path, &AddOptions::default()).await?;
+    }
+    let sizes: Vec<u64> = db.files.iter().map(|record| record.size).collect();
+    assert_eq!(sizes, vec![70_000, 300]);
+    assert_eq!(db.total_size(), 70_300);
+    Ok(())
+}
+
+/// `--since` takes a timestamp or a duration ago, and can filter every record out.
+#[test]
+fn since_filters_by_time_stamp() -> Result<(), Exn<InitError>> {
+    use crate::models::parse_since;
+    use chrono::{Duration, TimeZone, Utc};
+    assert_eq!(
+        parse_since("2024-05-01T12:00:00+02:00"),
+        Ok(Utc.with_ymd_and_hms(2024, 5, 1, 10, 0, 0).unwrap())
+    );
+    let before = Utc::now();
+    let since = parse_since("3d").expect("relative duration");
+    assert!(since >= before - Duration::days(3));
+    assert!(since <= Utc::now() - Duration::days(3));
+    assert!(parse_since("2w").is_ok() && parse_since("1h").is_ok());
+    assert!(parse_since("yesterday").is_err());
+    assert!(parse_since("2024-13-01T00:00:00Z").is_err());
+
+    let mut db = Database::new()?;
+    let now = Utc::now();
+    for (name, age) in [("old.txt", 10), ("recent.txt", 1)] {
+        db.builder()
+            .with_fields(
+                PathBuf::from(name),
+                hash_bytes(name.as_bytes(), HashAlgorithm::Sha256),
+                1,
+                now - Duration::days(age),
+            )
+            .commit()
+            .or_raise(|| InitError {
+                message: "Failed to commit record".into(),
+            })?;
+    }
+    let snapshot = db.snapshot();
+    db.retain_since(now - Duration::days(3));
+    assert_eq!(db.files.len(), 1);
+    assert_eq!(db.files[0].path, PathBuf::from("recent.txt"));
+    db.restore(snapshot);
+    db.retain_since(now);
+    assert!(db.files.is_empty());
+    Ok(())
+}
+
+/// A filename that is not valid UTF-8 survives a save and load byte for byte.
+#[cfg(unix)]
+#[compio::test]
+async fn non_utf8_path_round_trips() -> Result<(), Exn<InitError>> {
+    use crate::add::{AddOptions, add_file};
+    use crate::models::FileRecord;
+    use crate::verify::verify_records;
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+    let tmp = tempfile::tempdir().or_raise(|| InitError {
+        message: "Failed to create temp dir".into(),
+    })?;
+    let name = OsStr::from_bytes(b"caf\xe9.txt");
+    let file = tmp.path().join(name);
+    std::fs::write(&file, "latin-1").or_raise(|| InitError {
+        message: "Failed to write fixture".into(),
+    })?;
+    let mut db = Database::new()?;
+    db.root_dir = tmp.path().to_path_buf();
+    add_file(&mut db, &file, &AddOptions::default()).await?;
+    let db_path = tmp.path().join(DB_PATH);
+    db.save_to(&db_path).await.or_raise(|| InitError {
+        message: "Failed to save DB".into(),
+    })?;
+    let json = std::fs::read_to_string(&db_path).or_raise(|| InitError {
+        message: "Failed to read DB".into(),
+    })?;
+    assert!(json.contains("\"bytes\": \"636166e92e747874\""));
+
+    let db = Database::load(&db_path, &StoreOptions::default()).await?;
+    assert_eq!(db.files[0].path.as_os_str(), name);
+    assert_eq!(db.history[0].path.as_os_str(), name);
+    let records: Vec<&FileRecord> = db.files.iter().collect();
+    let report = verify_records(&db.root_dir, &records)
+        .await
+        .or_raise(|| InitError {
+            message: "Failed to verify".into(),
+        })?;
+    assert_eq!(report.ok, 1);
+    Ok(())
+}
+
+/// The manifest matches what coreutils `sha256sum` prints for the same file.
+#[compio::test]
+async fn manifest_matches_sha256sum() -> Result<(), Exn<InitError>> {
+    use crate::add::{AddOptions, add_file};
+    use crate::database::sha256sum_manifest;
+    let tmp = tempfile::tempdir().or_raise(|| InitError {
+        message: "Failed to create temp dir".into(),
+    })?;
+    for name in ["hello.txt", "back\\slash.txt"] {
+        std::fs::write(tmp.path().join(name), "hello\n").or_raise(|| InitError {
+            message: "Failed to write fixture".into(),
+        })?;
+    }
+    let mut db = Database::new()?;
+    db.root_dir = tmp.path().to_path_buf();
+    add_file(
+        &mut db,
+        &tmp.path().join("hello.txt"),
+        &AddOptions::default(),
+    )
+    .await?;
+    let manifest = sha256sum_manifest(&db).or_raise(|| InitError {
+        message: "Failed to build manifest".into(),
+    })?;
+    // `printf 'hello\n' > hello.txt && sha256sum hello.txt`
+    assert_eq!(
+        String::from_utf8_lossy(&manifest),
+        "5891b5b522d5df086d0ff0b110fbd9d21bb4fc7163af34d08286a2e846f6be03  hello.txt\n"
+    );
+
+    add_file(
+        &mut db,
+        &tmp.path().join("back\\slash.txt"),
+        &AddOptions::default(),
+    )
+    .await?;
+    let manifest = sha256sum_manifest(&db).or_raise(|| InitError {
+        message: "Failed to build manifest".into(),
+    })?;
+    assert!(String::from_utf8_lossy(&manifest).starts_with(
+        "\\5891b5b522d5df086d0ff0b110fbd9d21bb4fc7163af34d08286a2e846f6be03  back\\\\slash.txt\n"
+    ));
+
+    db.files[0].algo = HashAlgorithm::Blake3;
+    assert!(sha256sum_manifest(&db).is_err());
+    Ok(())
+}
+
+/// A `sha256sum` file becomes records, reporting the lines it cannot parse.
+#[compio::test]
+async fn import_sums_builds_records() -> Result<(), Exn<InitError>> {
+    use crate::add::read_sha256sums;
+    use crate::database::parse_sha256sum_line;
+    let tmp = tempfile::tempdir().or_raise(|| InitError {
+        message: "Failed to create temp dir".into(),
+    })?;
+    std::fs::write(tmp.path().join("hello.txt"), "hello\n").or_raise(|| InitError {
+        message: "Failed to write fixture".into(),
+    })?;
+    let hello = "5891b5b522d5df086d0ff0b110fbd9d21bb4fc7163af34d08286a2e846f6be03";
+    let sums = format!(
+        "{hello}  hello.txt\nnot a checksum line\n{}\n\\{hello} *gone\\\\file\n",
+        &hello[..10]
+    );
+    let mut db = Database::new()?;
+    db.root_dir = tmp.path().to_path_buf();
+    let (records, invalid) = read_sha256sums(&db, sums.as_bytes(), tmp.path()).await;
+    assert_eq!(
+        invalid.iter().map(|(line, _)| *line).collect::<Vec<_>>(),
+        [2, 3]
+    );
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[0].path, PathBuf::from("hello.txt"));
+    assert_eq!(records[0].hash.to_string(), hello);
+    assert_eq!(records[0].algo, HashAlgorithm::Sha256);
+    assert_eq!(records[0].size, 6);
+    assert!(records[0].modified_at.is_none());
+    assert_eq!(records[1].path, PathBuf::from("gone\\file"));
+    assert_eq!((records[1].size, records[1].modified_at), (0, None));
+
+    assert!(parse_sha256sum_line(format!("{hello} hello.txt").as_bytes()).is_err());
+    assert!(parse_sha256sum_line(format!("\\{hello}  a\\q").as_bytes()).is_err());
+    let summary = db.import_records(records, PathMatch::Lexical);
+    assert_eq!(summary.added, 2);
+    Ok(())
+}
+
+/// A wrong imported digest must not be skipped by the size and mtime fast
+/// path of `verify --all`, so the first verify reports the file as changed.
+#[compio::test]
+async fn imported_wrong_digest_is_reported_changed() -> Result<(), Exn<InitError>> {
+    use crate::add::read_sha256sums;
+    let tmp = tempfile::tempdir().or_raise(|| InitError {
+        message: "Failed to create temp dir".into(),
+    })?;
+    std::fs::write(tmp.path().join("data.txt"), "actual content\n").or_raise(|| InitError {
+        message: "Failed to write fixture".into(),
+    })?;
+    let wrong = hash_bytes(b"something else", HashAlgorithm::Sha256);
+    let mut db = Database::new()?;
+    db.root_dir = tmp.path().to_path_buf();
+    let sums = format!("{}  data.txt\n", wrong);
+    let (records, _) = read_sha256sums(&db, sums.as_bytes(), tmp.path()).await;
+    db.import_records(records, PathMatch::Lexical);
+
+    let record = &db.files[0];
+    assert!(!verify::stat_matches(&db.root_dir, record).await);
+    let result = verify::check_record(&db.root_dir, record)
+        .await
+        .or_raise(|| InitError {
+            message: "Failed to check record".into(),
+        })?;
+    assert_eq!(result.status, VerifyStatus::Changed);
+    Ok(())
+}
+
+/// `--color auto` turns color off under `NO_COLOR` and when stdout is not a terminal.
+#[test]
+fn auto_color_honors_no_color() -> Result<(), Exn<InitError>> {
+    use std::ffi::OsString;
+    let env = |vars: &'static [(&'static str, &'static str)]| {
+        move |name: &str| {
+            vars.iter()
+                .find(|(key, _)| *key == name)
+                .map(|(_, value)| OsString::from(value))
+        }
+    };
+    assert!(styled::auto_color(env(&[]), true));
+    assert!(!styled::auto_color(env(&[]), false));
+    assert!(!styled::auto_color(
+        env(&[("NO_COLOR", "1"), ("CLICOLOR_FORCE", "1")]),
+        true
+    ));
+    assert!(styled::auto_color(env(&[("NO_COLOR", "")]), true));
+    assert!(styled::auto_color(env(&[("CLICOLOR_FORCE", "1")]), false));
+    assert!(!styled::auto_color(env(&[("CLICOLOR", "0")]), true));
+
+    colored::control::set_override(styled::auto_color(env(&[("NO_COLOR", "1")]), true));
+    let mut db = Database::new()?;
+    db.builder()
+        .with_fields(
+            PathBuf::from("plain.txt"),
+            hash_bytes(b"plain", HashAlgorithm::Sha256),
+            5,
+            chrono::Utc::now(),
+        )
+        .commit()
+        .or_raise(|| InitError {
+            message: "Failed to commit record".into(),
+        })?;
+    let output = format!(
+        "{}{}{}",
+        db.status_frame(None, None),
+        colored::Colorize::bold(styled::error("failed")),
+        styled::info("info")
+    );
+    assert!(!output.contains('\x1b'));
+    styled::set_color_choice(styled::ColorChoice::Auto);
+    Ok(())
+}
+
+/// `validate_integrity` reports duplicate IDs and paths, bad hashes and future timestamps.
+#[test]
+fn validate_integrity_finds_problems() -> Result<(), Exn<InitError>> {
+    let mut db = Database::new()?;
+    let now = chrono::Utc::now();
+    for name in ["a.txt", "b.txt", "c.txt"] {
+        db.builder()
+            .with_fields(
+                PathBuf::from(name),
+                hash_bytes(name.as_bytes(), HashAlgorithm::Sha256),
+                1,
+                now,
+            )
+            .commit()
+            .or_raise(|| InitError {
+                message: "Failed to commit record".into(),
+            })?;
+    }
+    assert!(db.validate_integrity().is_empty());
+
+    db.files[1].id = db.files[0].id.clone();
+    db.files[2].hash = HexStirng("abc123".into());
+    let problems = db.validate_integrity();
+    assert_eq!(problems.len(), 2);
+    assert!(problems[0].starts_with("duplicate id"));
+    assert!(problems[0].contains("a.txt") && problems[0].contains("b.txt"));
+    assert!(problems[1].contains("c.txt") && problems[1].contains("expected 64 hex digits"));
+
+    db.files[2].path = PathBuf::from("a.txt");
+    db.files[2].hash = hash_bytes(b"c", HashAlgorithm::Sha256);
+    db.files[0].time_stamp = now + chrono::Duration::days(1);
+    let problems = db.validate_integrity();
+    assert_eq!(problems.len(), 3);
+    assert!(problems[0].contains("in the future"));
+    assert!(problems[2].starts_with("a.txt is tracked twice"));
+    Ok(())
+}
+
+/// A file tracked with blocks reports which block changed when verified.
+#[compio::test]
+async fn verify_reports_changed_block() -> Result<(), Exn<InitError>> {
+    use crate::add::{AddOptions, add_file};
+    use crate::verify::check_record;
+    let tmp = tempfile::tempdir().or_raise(|| InitError {
+        message: "Failed to create temp dir".into(),
+    })?;
+    let path = tmp.path().join("disk.img");
+    // four full blocks and a short fifth one
+    let mut content = vec![0u8; 4 * 1024 + 100];
+    std::fs::write(&path, &content).or_raise(|| InitError {
+        message: "Failed to write fixture".into(),
+    })?;
+    let mut db = Database::new()?;
+    db.root_dir = tmp.path().to_path_buf();
+    let options = AddOptions {
+        block_size: Some(1024),
+        ..AddOptions::default()
+    };
+    add_file(&mut db, &path, &options).await?;
+    let blocks = db.files[0].blocks.clone().expect("blocks are stored");
+    assert_eq!(blocks.hashes.len(), 5);
+    let whole = hash_file_streaming(
+        &crate::files::get_file(&path).await.or_raise(|| InitError {
+            message: "Failed to open fixture".into(),
+        })?,
+        HashAlgorithm::Sha256,
+    )
+    .await
+    .or_raise(|| InitError {
+        message: "Failed to hash fixture".into(),
+    })?;
+    assert_eq!(db.files[0].hash, whole);
+
+    content[2 * 1024 + 7] = 1;
+    std::fs::write(&path, &content).or_raise(|| InitError {
+        message: "Failed to write fixture".into(),
+    })?;
+    let result = check_record(&db.root_dir, &db.files[0])
+        .await
+        .or_raise(|| InitError {
+            message: "Failed to verify".into(),
+        })?;
+    assert_eq!(result.status, VerifyStatus::Changed);
+    assert_eq!(result.changed_blocks, [2]);
+    assert_eq!(
+        crate::verify::changed_blocks_line(&db.files[0], &result.changed_blocks).as_deref(),
+        Some("Changed block(s) of 1.0 KiB: 2")
+    );
+
+    // growing the file changes the last block and adds a new one
+    content.extend_from_slice(&[0u8; 1024]);
+    std::fs::write(&path, &content).or_raise(|| InitError {
+        message: "Failed to write fixture".into(),
+    })?;
+    let result = check_record(&db.root_dir, &db.files[0])
+        .await
+        .or_raise(|| InitError {
+            message: "Failed to verify".into(),
+        })?;
+    assert_eq!(result.changed_blocks, [2, 4, 5]);
+    Ok(())
+}
+
+/// Re-committing a tracked path replaces its block digests along with its hash.
+#[test]
+fn recommit_updates_blocks() -> Result<(), Exn<InitError>> {
+    use crate::hash::BlockHashes;
+    let mut db = Database::new()?;
+    let path = PathBuf::from("disk.img");
+    db.builder()
+        .with_fields(
+            path.clone(),
+            hash_bytes(b"original", HashAlgorithm::Sha256),
+            8,
+            chrono::Utc::now(),
+        )
+        .commit()
+        .or_raise(|| InitError {
+            message: "Failed to commit record".into(),
+        })?;
+    assert_eq!(db.files[0].blocks, None);
+
+    let blocks = BlockHashes {
+        block_size: 4,
+        hashes: vec![
+            hash_bytes(b"upda", HashAlgorithm::Sha256),
+            hash_bytes(b"ted!", HashAlgorithm::Sha256),
+        ],
+        root: hash_bytes(b"root", HashAlgorithm::Sha256),
+    };
+    db.builder()
+        .with_fields(
+            path,
+            hash_bytes(b"updated!", HashAlgorithm::Sha256),
+            8,
+            chrono::Utc::now(),
+        )
+        .with_blocks(blocks.clone())
+        .commit()
+        .or_raise(|| InitError {
+            message: "Failed to commit record".into(),
+        })?;
+    assert_eq!(db.files.len(), 1);
+    assert_eq!(db.files[0].blocks, Some(blocks));
+    Ok(())
+}
+
+/// `format_records` renders plain blocks, a JSON array, or quoted CSV rows.
+#[test]
+fn format_records_in_each_format() -> Result<(), Exn<InitError>> {
+    use crate::models::{FileRecord, OutputFormat, format_records};
+    use chrono::TimeZone;
+    let mut db = Database::new()?;
+    let time_stamp = chrono::Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap();
+    for name in ["plain.txt", "a, \"quoted\" name.txt"] {
+        db.builder()
+            .with_fields(
+                PathBuf::from(name),
+                hash_bytes(name.as_bytes(), HashAlgorithm::Sha256),
+                3,
+                time_stamp,
+            )
+            .commit()
+            .or_raise(|| InitError {
+                message: "Failed to commit record".into(),
+            })?;
+    }
+    let format = |format| {
+        format_records(&db.files, format).or_raise(|| InitError {
+            message: "Failed to format records".into(),
+        })
+    };
+    let (plain_hash, quoted_hash) = (&db.files[0].hash, &db.files[1].hash);
+
+    assert_eq!(
+        format(OutputFormat::Plain)?,
+        format!(
+            "File name: plain.txt\nHash: {plain_hash}\nSize: 3 bytes\nCreated: 2024-05-01 12:00:00\n\n\
+             File name: a, \"quoted\" name.txt\nHash: {quoted_hash}\nSize: 3 bytes\nCreated: 2024-05-01 12:00:00\n"
+        )
+    );
+
+    let parsed: Vec<FileRecord> =
+        serde_json::from_str(&format(OutputFormat::Json)?).or_raise(|| InitError {
+            message: "Failed to parse JSON listing".into(),
+        })?;
+    assert_eq!(parsed, db.files);
+
+    assert_eq!(
+        format(OutputFormat::Csv)?,
+        format!(
+            "path,hash,size,time_stamp\n\
+             plain.txt,{plain_hash},3,2024-05-01T12:00:00+00:00\n\
+             \"a, \"\"quoted\"\" name.txt\",{quoted_hash},3,2024-05-01T12:00:00+00:00\n"
+        )
+    );
+    assert_eq!(
+        format_records(&[], OutputFormat::Csv).ok().as_deref(),
+        Some("path,hash,size,time_stamp\n")
+    );
+    assert_eq!(
+        format_records(&[], OutputFormat::Plain).ok().as_deref(),
+        Some("")
+    );
+    Ok(())
+}
+
+/// `add_opened` takes the size and times from the metadata it is given instead of
+/// stat'ing the file again.
+#[compio::test]
+async fn add_opened_reuses_given_metadata() -> Result<(), Exn<InitError>> {
+    use crate::add::{AddOptions, AddOutcome, add_opened};
+    let tmp = tempfile::tempdir().or_raise(|| InitError {
+        message: "Failed to create temp dir".into(),
+    })?;
+    let (small, large) = (tmp.path().join("small.txt"), tmp.path().join("large.txt"));
+    for (path, content) in [(&small, "abc"), (&large, "a much longer file")] {
+        std::fs::write(path, content).or_raise(|| InitError {
+            message: "Failed to write fixture".into(),
+        })?;
+    }
+    let open = |path| async move {
+        crate::files::get_file(path).await.or_raise(|| InitError {
+            message: "Failed to open fixture".into(),
+        })
+    };
+    let file = open(&small).await?;
+    let other_meta = crate::files::get_meta(&open(&large).await?)
+        .await
+        .or_raise(|| InitError {
+            message: "Failed to stat fixture".into(),
+        })?;
+    let mut db = Database::new()?;
+    db.root_dir = tmp.path().to_path_buf();
+    let outcome = add_opened(&mut db, &small, &file, &other_meta, &AddOptions::default()).await?;
+    assert_eq!(outcome, AddOutcome::Added);
+    assert_eq!(db.files[0].path, PathBuf::from("small.txt"));
+    assert_eq!(db.files[0].hash, hash_bytes(b"abc", HashAlgorithm::Sha256));
+    assert_eq!(db.files[0].size, 18);
+    Ok(())
+}
+
+/// A file added with `--backup` can be restored after it is corrupted or deleted.
+#[compio::test]
+async fn restore_from_backup() -> Result<(), Exn<InitError>> {
+    use crate::add::{AddOptions, add_file};
+    use crate::files::{WalkOptions, walk_files};
+    use crate::objects::{object_path, restore_object};
+    use crate::verify::check_record;
+    let tmp = tempfile::tempdir().or_raise(|| InitError {
+        message: "Failed to create temp dir".into(),
+    })?;
+    let path = tmp.path().join("notes.txt");
+    std::fs::write(&path, "original").or_raise(|| InitError {
+        message: "Failed to write fixture".into(),
+    })?;
+    let mut db = Database::new()?;
+    db.root_dir = tmp.path().to_path_buf();
+    let backup = AddOptions {
+        backup: true,
+        ..AddOptions::default()
+    };
+    add_file(&mut db, &path, &backup).await?;
+    let record = db.files[0].clone();
+    assert!(object_path(&db.root_dir, &record.hash).is_file());
+
+    let restore = || async {
+        restore_object(&db.root_dir, &record.hash, record.algo, &path)
+            .await
+            .or_raise(|| InitError {
+                message: "Failed to restore".into(),
+            })
+    };
+    std::fs::write(&path, "corrupted").or_raise(|| InitError {
+        message: "Failed to corrupt fixture".into(),
+    })?;
+    restore().await?;
+    assert_eq!(
+        std::fs::read_to_string(&path).ok().as_deref(),
+        Some("original")
+    );
+    std::fs::remove_file(&path).or_raise(|| InitError {
+        message: "Failed to delete fixture".into(),
+    })?;
+    restore().await?;
+    let result = check_record(&db.root_dir, &record)
+        .await
+        .or_raise(|| InitError {
+            message: "Failed to verify".into(),
+        })?;
+    assert_eq!(result.status, VerifyStatus::Ok);
+
+    // the object store is never walked into
+    let walk = WalkOptions {
+        db_path: PathBuf::from(DB_PATH),
+        exclude_vcs: false,
+        ignore: Vec::new(),
+        follow_symlinks: false,
+    };
+    let walked: Vec<_> = walk_files(tmp.path(), &walk)
+        .collect::<Result<_, _>>()
+        .or_raise(|| InitError {
+            message: "Failed to walk".into(),
+        })?;
+    assert_eq!(walked, std::slice::from_ref(&path));
+
+    // a damaged backup is reported rather than restored
+    std::fs::write(object_path(&db.root_dir, &record.hash), "damaged").or_raise(|| InitError {
+        message: "Failed to damage backup".into(),
+    })?;
+    std::fs::write(&path, "corrupted").or_raise(|| InitError {
+        message: "Failed to corrupt fixture".into(),
+    })?;
+    assert!(restore().await.is_err());
+    assert_eq!(
+        std::fs::read_to_string(&path).ok().as_deref(),
+        Some("corrupted")
+    );
+    Ok(())
+}
+
+/// An `IoError` without a path says so instead of printing `None`.
+#[test]
+fn io_error_displays_missing_path() {
+    let unknown: IoError<PathBuf> = IoError {
+        path: None,
+        message: "Unable to read file".into(),
+    };
+    assert_eq!(
+        unknown.to_string(),
+        "IO error on unknown path: Unable to read file"
+    );
+    let known = IoError {
+        path: Some(PathBuf::from("notes.txt")),
+        message: "Unable to read file".into(),
+    };
+    assert_eq!(
+        known.to_string(),
+        "IO error on \"notes.txt\": Unable to read file"
+    );
+}
+
+/// `Database::new` should report a deleted current directory as `CurrentDirUnavailable`.
+///
+/// The current directory is process-wide, so the check re-runs this test in a
+/// child process instead of pulling the directory out from under other tests.
+#[cfg(unix)]
+#[test]
+fn new_database_reports_missing_current_dir() {
+    const CHILD: &str = "TAMASHII_TEST_DELETED_CWD";
+    if std::env::var_os(CHILD).is_none() {
+        let status = std::process::Command::new(std::env::current_exe().unwrap())
+            .args(["test::new_database_reports_missing_current_dir", "--exact"])
+            .env(CHILD, "1")
+            .status()
+            .unwrap();
+        assert!(status.success());
+        return;
+    }
+    let tmp = tempfile::tempdir().unwrap();
+    let cwd = tmp.path().join("gone");
+    std::fs::create_dir(&cwd).unwrap();
+    std::env::set_current_dir(&cwd).unwrap();
+    std::fs::remove_dir(&cwd).unwrap();
+
+    let err = models::Database::new().unwrap_err();
+    let cause = err.as_frame().children()[0]
+        .as_any()
+        .downcast_ref::<errors::NewDatabaseError>();
+    assert!(matches!(
+        cause,
+        Some(errors::NewDatabaseError::CurrentDirUnavailable(_))
+    ));
+}
+
+/// Each event should restart a file's quiet period, and files should be released
+/// one at a time as their own windows run out.
+#[test]
+fn watch_debounces_rapid_changes() {
+    use std::time::{Duration, Instant};
+    let start = Instant::now();
+    let at = |ms| start + Duration::from_millis(ms);
+    let mut debouncer = watch::Debouncer::new(Duration::from_millis(250));
+    debouncer.record(PathBuf::from("a.txt"), at(0));
+    debouncer.record(PathBuf::from("b.txt"), at(100));
+    debouncer.record(PathBuf::from("a.txt"), at(200));
+
+    assert!(debouncer.ready(at(300)).is_empty());
+    assert_eq!(debouncer.ready(at(350)), vec![PathBuf::from("b.txt")]);
+    assert!(debouncer.ready(at(400)).is_empty());
+    assert_eq!(debouncer.ready(at(450)), vec![PathBuf::from("a.txt")]);
+    assert!(debouncer.ready(at(1000)).is_empty());
+}
+
+/// Events for tracked files reach the debouncer, events for other files in a
+/// watched directory are dropped, and a settled file maps back to its record.
+#[test]
+fn watch_dispatches_tracked_events_only() {
+    use std::time::{Duration, Instant};
+    let root = PathBuf::from("/project");
+    let mut db = models::Database::new().unwrap();
+    db.root_dir = root.clone();
+    for name in ["a.txt", "docs/b.txt"] {
+        db.builder()
+            .with_fields(
+                PathBuf::from(name),
+                hash_bytes(name.as_bytes(), HashAlgorithm::Sha256),
+                1,
+                chrono::Utc::now(),
+            )
+            .commit()
+            .unwrap();
+    }
+    let watched = watch::WatchedRecords::new(&db.root_dir, &db.files);
+    assert_eq!(watched.directories(), vec![root.clone(), root.join("docs")]);
+
+    let start = Instant::now();
+    let at = |ms| start + Duration::from_millis(ms);
+    let mut debouncer = watch::Debouncer::new(Duration::from_millis(250));
+    assert!(watched.dispatch(&mut debouncer, root.join("a.txt"), at(0)));
+    assert!(!watched.dispatch(&mut debouncer, root.join("a.txt.swp"), at(10)));
+    assert!(watched.dispatch(&mut debouncer, root.join("docs/b.txt"), at(50)));
+    assert!(watched.dispatch(&mut debouncer, root.join("a.txt"), at(100)));
+
+    assert_eq!(debouncer.ready(at(300)), vec![root.join("docs/b.txt")]);
+    let settled = debouncer.ready(at(350));
+    assert_eq!(settled, vec![root.join("a.txt")]);
+    let record = watched.get(&settled[0]).expect("a settled file is tracked");
+    assert_eq!(record.path, PathBuf::from("a.txt"));
+    let in_docs: Vec<_> = watched
+        .in_directory(&root.join("docs"))
+        .iter()
+        .map(|record| record.path.clone())
+        .collect();
+    assert_eq!(in_docs, vec![PathBuf::from("docs/b.txt")]);
+}
+
+/// inotify reports a write to a file in a watched directory.
+#[cfg(target_os = "linux")]
+#[test]
+fn watch_receives_os_events() {
+    let tmp = tempfile::tempdir().unwrap();
+    let path = tmp.path().join("watched.txt");
+    std::fs::write(&path, b"original").unwrap();
+    let gone = tmp.path().join("gone");
+    let (events, unwatched) =
+        watch::watch_directories(&[gone.clone(), tmp.path().to_path_buf()]).unwrap();
+    // a directory that cannot be watched is skipped, not fatal
+    assert_eq!(unwatched.len(), 1);
+    assert_eq!(unwatched[0].0, gone);
+    std::fs::write(&path, b"tampered").unwrap();
+    let (location, _) = events
+        .recv_timeout(std::time::Duration::from_secs(5))
+        .expect("the write should be reported");
+    assert_eq!(location, path);
+}
@@ -0,0 +1,732 @@
+use crate::budget::MemoryBudget;
+use crate::errors::{IoError, VerificationError};
+use crate::files;
+use crate::hash;
+use crate::models::{self, Database, FileRecord, HexStirng, directory_key};
+use crate::paths::{PathMatch, paths_match};
+use crate::styled;
+use chrono::{DateTime, Utc};
+use exn::{Exn, ResultExt};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// The outcome of checking a single tracked file against its stored hash.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum VerifyStatus {
+    /// The current content hash matches the stored hash
+    Ok,
+    /// The file exists but its content hash differs from the stored hash
+    Changed,
+    /// The file could not be opened at its tracked path
+    Missing,
+    /// The file exists but could not be opened or read, e.g. for lack of permission
+    Unreadable,
+}
+
+/// The result of verifying one `FileRecord`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FileResult {
+    /// Path of the tracked file
+    pub path: PathBuf,
+    /// Outcome of the check
+    pub status: VerifyStatus,
+    /// Hash stored in the database
+    pub expected: HexStirng,
+    /// Hash of the file's current content, if it could be read
+    pub actual: Option<HexStirng>,
+    /// Indexes of the blocks that changed, for records tracked with blocks
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub changed_blocks: Vec<u64>,
+}
+
+impl FileResult {
+    /// Classifies a failed check, or returns `None` if the hash matched.
+    ///
+    /// A missing file is an `IoFailure` and an unreadable one a `HashFailure`.
+    pub fn failure(&self) -> Option<VerificationError> {
+        match self.status {
+            VerifyStatus::Ok => None,
+            VerifyStatus::Changed => Some(VerificationError::HashMissMatched {
+                path: self.path.clone(),
+            }),
+            VerifyStatus::Missing => Some(VerificationError::IoFailure),
+            VerifyStatus::Unreadable => Some(VerificationError::HashFailure),
+        }
+    }
+}
+
+/// Verification counts for the tracked files in one directory.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct DirectoryRollup {
+    /// Directory relative to the database root
+    pub directory: String,
+    /// Files whose hash matched
+    pub ok: usize,
+    /// Files whose content changed
+    pub changed: usize,
+    /// Files that could not be found
+    pub missing: usize,
+    /// Files that exist but could not be read
+    #[serde(default)]
+    pub unreadable: usize,
+}
+
+/// Where in the path-sorted record list a `verify --all` run begins.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum StartPoint {
+    /// Verify every record
+    #[default]
+    Beginning,
+    /// Start at the record tracking this path
+    Path(PathBuf),
+    /// Start at this zero-based position
+    Index(usize),
+}
+
+/// Exit code of a check that found changed, missing or untracked files.
+pub const CHANGED_EXIT_CODE: i32 = 1;
+
+/// Exit code of a run that failed outright, e.g. because the database could not be loaded.
+pub const ERROR_EXIT_CODE: i32 = 2;
+
+/// Exit code of a check that could not read some tracked files.
+pub const UNREADABLE_EXIT_CODE: i32 = 3;
+
+/// Returns the process exit code for the outcome of `verify_record`.
+///
+/// Follows `VerifyReport::exit_code`: `0` if the file matched,
+/// `CHANGED_EXIT_CODE` if it changed, went missing or is not tracked, and
+/// `UNREADABLE_EXIT_CODE` if it could not be read, unless `ignore_unreadable`
+/// is set.
+pub fn verification_exit_code(
+    outcome: &Result<(), VerificationError>,
+    ignore_unreadable: bool,
+) -> i32 {
+    match outcome {
+        Ok(()) => 0,
+        Err(VerificationError::HashFailure) if ignore_unreadable => 0,
+        Err(VerificationError::HashFailure) => UNREADABLE_EXIT_CODE,
+        Err(_) => CHANGED_EXIT_CODE,
+    }
+}
+
+/// A summary of a full verification run over the database.
+///
+/// Serialized as the JSON artifact written by `verify --report <PATH>`, for
+/// `--all` as well as for a single file or directory.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct VerifyReport {
+    /// Time the report was produced
+    pub checked_at: DateTime<Utc>,
+    /// Number of records checked
+    pub total: usize,
+    /// Number of records whose hash matched
+    pub ok: usize,
+    /// Paths of files whose content changed
+    pub changed: Vec<PathBuf>,
+    /// Paths of tracked files that could not be found
+    pub missing: Vec<PathBuf>,
+    /// Paths of tracked files that exist but could not be read
+    #[serde(default)]
+    pub unreadable: Vec<PathBuf>,
+    /// Per-file results, in verification order
+    pub results: Vec<FileResult>,
+}
+
+impl VerifyReport {
+    /// Builds a report from per-file results, computing the counts and problem lists.
+    pub fn from_results(results: Vec<FileResult>) -> Self {
+        Self::build(results)
+    }
+
+    /// Builds the report of a single-file verify of `path`.
+    ///
+    /// `result` is `None` when `path` is not tracked. There is no record to
+    /// report on then, so this is an error rather than an empty report.
+    pub fn for_single_file(
+        path: &Path,
+        result: Option<FileResult>,
+    ) -> Result<Self, Exn<VerificationError>> {
+        match result {
+            Some(result) => Ok(Self::build(vec![result])),
+            None => Err(Exn::new(VerificationError::FileUntracked(
+                path.to_path_buf(),
+            ))),
+        }
+    }
+
+    /// Like `from_results`, but orders the results (and problem lists) by path.
+    pub fn from_results_sorted(mut results: Vec<FileResult>) -> Self {
+        results.sort_by(|a, b| a.path.cmp(&b.path));
+        Self::build(results)
+    }
+
+    /// Computes the counts and problem lists for `results`, keeping their order.
+    fn build(results: Vec<FileResult>) -> Self {
+        let paths_with = |status: VerifyStatus| {
+            results
+                .iter()
+                .filter(|r| r.status == status)
+                .map(|r| r.path.clone())
+                .collect::<Vec<_>>()
+        };
+        let changed = paths_with(VerifyStatus::Changed);
+        let missing = paths_with(VerifyStatus::Missing);
+        let unreadable = paths_with(VerifyStatus::Unreadable);
+        let ok = results
+            .iter()
+            .filter(|r| r.status == VerifyStatus::Ok)
+            .count();
+        Self {
+            checked_at: Utc::now(),
+            total: results.len(),
+            ok,
+            changed,
+            missing,
+            unreadable,
+            results,
+        }
+    }
+
+    /// Returns `true` if any checked file changed or went missing.
+    pub fn has_changes(&self) -> bool {
+        !self.changed.is_empty() || !self.missing.is_empty()
+    }
+
+    /// Returns the process exit code for this run.
+    ///
+    /// The code is `1` if any file changed or went missing and `0` if every
+    /// file matched, so a failed verification fails a CI job. With
+    /// `exit_on_change` the meaning is inverted for CI pipelines that run a
+    /// follow-up step only when something changed: the code is `0` if changes
+    /// were found and `1` if every file matched.
+    ///
+    /// Files that could not be read make the run fail with
+    /// `UNREADABLE_EXIT_CODE` either way, unless `ignore_unreadable` is set.
+    pub fn exit_code(&self, exit_on_change: bool, ignore_unreadable: bool) -> i32 {
+        if !ignore_unreadable && !self.unreadable.is_empty() {
+            return UNREADABLE_EXIT_CODE;
+        }
+        match (exit_on_change, self.has_changes()) {
+            (true, true) => 0,
+            (true, false) => CHANGED_EXIT_CODE,
+            (false, true) => CHANGED_EXIT_CODE,
+            (false, false) => 0,
+        }
+    }
+
+    /// Rolls the per-file results up by directory relative to `root`.
+    ///
+    /// # Returns
+    ///
+    /// One entry per directory containing tracked files, sorted by directory.
+    pub fn by_directory(&self, root: &Path) -> Vec<DirectoryRollup> {
+        let mut rollups: std::collections::BTreeMap<String, DirectoryRollup> =
+            std::collections::BTreeMap::new();
+        for result in self.results.iter() {
+            let directory = directory_key(root, &result.path);
+            let rollup = rollups
+                .entry(directory.clone())
+                .or_insert_with(|| DirectoryRollup {
+                    directory,
+                    ..DirectoryRollup::default()
+                });
+            match result.status {
+                VerifyStatus::Ok => rollup.ok += 1,
+                VerifyStatus::Changed => rollup.changed += 1,
+                VerifyStatus::Missing => rollup.missing += 1,
+                VerifyStatus::Unreadable => rollup.unreadable += 1,
+            }
+        }
+        rollups.into_values().collect()
+    }
+
+    /// Writes the report as pretty-printed JSON to `path`.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - The report was written
+    /// * `Err(Exn<IoError<PathBuf>>)` - If serialization or the write fails
+    pub async fn write(&self, path: &Path) -> Result<(), Exn<IoError<PathBuf>>> {
+        let json_data = serde_json::to_string_pretty(self).or_raise(|| IoError {
+            path: Some(path.to_path_buf()),
+            message: "Failed to serialize verification report".into(),
+        })?;
+        compio::fs::write(path, json_data)
+            .await
+            .0
+            .or_raise(|| IoError {
+                path: Some(path.to_path_buf()),
+                message: "Failed to write verification report".into(),
+            })?;
+        Ok(())
+    }
+}
+
+/// What to do about a changed file, as chosen at a `verify --interactive` prompt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Remediation {
+    /// Store the file's current hash as its new baseline
+    Accept,
+    /// Leave the record as it is
+    Skip,
+    /// Stop verifying
+    Quit,
+}
+
+/// Asks whether to accept, skip or quit for the changed file at `path`.
+///
+/// Unrecognized answers repeat the question; end of input counts as skip.
+///
+/// # Arguments
+///
+/// * `input` - Where answers are read from, normally stdin
+/// * `output` - Where the prompt is written, normally stdout
+/// * `path` - The changed file
+///
+/// # Returns
+///
+/// * `Ok(Remediation)` - The chosen action
+/// * `Err(std::io::Error)` - If reading or writing fails
+pub fn prompt_remediation(
+    input: &mut impl std::io::BufRead,
+    output: &mut impl std::io::Write,
+    path: &Path,
+) -> std::io::Result<Remediation> {
+    loop {
+        write!(output, "{}: [a]ccept / [s]kip / [q]uit? ", path.display())?;
+        output.flush()?;
+        let mut answer = String::new();
+        if input.read_line(&mut answer)? == 0 {
+            return Ok(Remediation::Skip);
+        }
+        match answer.trim().to_ascii_lowercase().as_str() {
+            "a" | "accept" => return Ok(Remediation::Accept),
+            "s" | "skip" | "" => return Ok(Remediation::Skip),
+            "q" | "quit" => return Ok(Remediation::Quit),
+            _ => continue,
+        }
+    }
+}
+
+/// Asks whether a likely rename should be recorded.
+///
+/// Anything but `y` or `yes`, including end of input, declines.
+///
+/// # Returns
+///
+/// * `Ok(bool)` - Whether to move the record to the new path
+/// * `Err(std::io::Error)` - If reading or writing fails
+pub fn prompt_rename(
+    input: &mut impl std::io::BufRead,
+    output: &mut impl std::io::Write,
+    rename: &Rename,
+) -> std::io::Result<bool> {
+    write!(
+        output,
+        "Update the record of {} to {}? [y/N] ",
+        rename.from.display(),
+        rename.to.display()
+    )?;
+    output.flush()?;
+    let mut answer = String::new();
+    input.read_line(&mut answer)?;
+    Ok(matches!(
+        answer.trim().to_ascii_lowercase().as_str(),
+        "y" | "yes"
+    ))
+}
+
+/// A missing tracked file whose content was found at an untracked path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rename {
+    /// The tracked path that no longer exists
+    pub from: PathBuf,
+    /// The untracked path holding the same content
+    pub to: PathBuf,
+}
+
+/// Looks for the content of missing tracked files among the untracked files under `db.root_dir`.
+///
+/// Only files whose size matches a missing record are hashed, each with the
+/// algorithm of the records it could match. Every missing record and every
+/// untracked file is paired at most once. The new path is stored the way
+/// `add` stores it, relative to `root_dir`.
+///
+/// # Arguments
+///
+/// * `db` - The database holding the records
+/// * `missing` - Tracked paths reported as missing
+/// * `walk` - Which entries under `root_dir` to skip
+/// * `matching` - How walked paths are matched against tracked records
+///
+/// # Returns
+///
+/// * `Ok(Vec<Rename>)` - The likely renames, in walk order
+/// * `Err(Exn<IoError<PathBuf>>)` - If a directory cannot be walked or an opened file cannot be hashed
+pub async fn detect_renames(
+    db: &Database,
+    missing: &[PathBuf],
+    walk: &files::WalkOptions,
+    matching: PathMatch,
+) -> Result<Vec<Rename>, Exn<IoError<PathBuf>>> {
+    let mut unmatched: Vec<&FileRecord> = missing
+        .iter()
+        .filter_map(|path| db.find_record(path, matching))
+        .collect();
+    let mut renames = Vec::new();
+    for candidate in files::walk_files(&db.root_dir, walk) {
+        if unmatched.is_empty() {
+            break;
+        }
+        let candidate = db.tracked_path(&candidate?);
+        if db.find_record(&candidate, matching).is_some() {
+            continue;
+        }
+        let Ok(file) = files::get_file(&db.resolve(&candidate)).await else {
+            continue;
+        };
+        let Ok(meta) = files::get_meta(&file).await else {
+            continue;
+        };
+        let mut algos: Vec<hash::HashAlgorithm> = Vec::new();
+        for record in unmatched.iter() {
+            if record.size == meta.len() && !algos.contains(&record.algo) {
+                algos.push(record.algo);
+            }
+        }
+        for algo in algos {
+            let digest = hash::hash_file_streaming(&file, algo).await?;
+            let found = db.find_by_hash(&digest).into_iter().find(|record| {
+                record.algo == algo && unmatched.iter().any(|other| other.id == record.id)
+            });
+            if let Some(record) = found {
+                unmatched.retain(|other| other.id != record.id);
+                renames.push(Rename {
+                    from: record.path.clone(),
+                    to: candidate.clone(),
+                });
+                break;
+            }
+        }
+    }
+    Ok(renames)
+}
+
+/// Sorts `files` by path and drops the records before `start`.
+///
+/// # Arguments
+///
+/// * `files` - The tracked records
+/// * `start` - The first record to keep
+/// * `matching` - How a `StartPoint::Path` is matched against tracked paths
+///
+/// # Returns
+///
+/// * `Some(Vec<&FileRecord>)` - The records from `start` onward, sorted by path
+/// * `None` - If `start` names a path that is not tracked
+pub fn records_from<'a>(
+    files: &'a [FileRecord],
+    start: &StartPoint,
+    matching: PathMatch,
+) -> Option<Vec<&'a FileRecord>> {
+    let mut sorted: Vec<&FileRecord> = files.iter().collect();
+    sorted.sort_by(|a, b| a.path.cmp(&b.path));
+    let skip = match start {
+        StartPoint::Beginning => 0,
+        StartPoint::Path(path) => sorted
+            .iter()
+            .position(|record| paths_match(&record.path, path, matching))?,
+        StartPoint::Index(index) => (*index).min(sorted.len()),
+    };
+    Some(sorted.split_off(skip))
+}
+
+/// Randomizes the order `records` are verified in, reproducibly for a given `seed`.
+///
+/// Spreading reads across the disk keeps repeated runs from always hitting
+/// the same regions first.
+pub fn shuffle_records(records: &mut [&FileRecord], seed: u64) {
+    use rand::SeedableRng;
+    use rand::seq::SliceRandom;
+    let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+    records.shuffle(&mut rng);
+}
+
+/// Re-hashes the file behind `record` and compares it with the stored hash.
+///
+/// A file that cannot be opened is reported as `Missing`; a failure while
+/// hashing an opened file is returned as an error.
+///
+/// # Arguments
+///
+/// * `root` - The database's `root_dir`, which relative record paths are resolved against
+/// * `record` - The tracked record to check, re-hashed with its own algorithm
+///
+/// # Returns
+///
+/// * `Ok(FileResult)` - The outcome of the check; a file that exists but
+///   cannot be opened or read is reported as `Unreadable`
+/// * `Err(Exn<IoError<PathBuf>>)` - Reserved for failures outside the file itself
+pub async fn check_record(
+    root: &Path,
+    record: &FileRecord,
+) -> Result<FileResult, Exn<IoError<PathBuf>>> {
+    let failed = |status| FileResult {
+        path: record.path.clone(),
+        status,
+        expected: record.hash.clone(),
+        actual: None,
+        changed_blocks: Vec::new(),
+    };
+    let location = root.join(&record.path);
+    let file = match files::get_file(&location).await {
+        Ok(file) => file,
+        // the file is there, so the open failed for another reason, e.g. permissions
+        Err(_) if std::fs::symlink_metadata(&location).is_ok() => {
+            return Ok(failed(VerifyStatus::Unreadable));
+        }
+        Err(_) => return Ok(failed(VerifyStatus::Missing)),
+    };
+    let hashed = match &record.blocks {
+        Some(blocks) => hash::hash_file_blocks(&file, record.algo, blocks.block_size)
+            .await
+            .map(|(hash, current)| (hash, blocks.changed_blocks(&current))),
+        None => hash::hash_file_streaming(&file, record.algo)
+            .await
+            .map(|hash| (hash, Vec::new())),
+    };
+    let Ok((current_hash, changed_blocks)) = hashed else {
+        return Ok(failed(VerifyStatus::Unreadable));
+    };
+    let status = if current_hash.ct_eq(&record.hash) {
+        VerifyStatus::Ok
+    } else {
+        VerifyStatus::Changed
+    };
+    Ok(FileResult {
+        path: record.path.clone(),
+        status,
+        expected: record.hash.clone(),
+        actual: Some(current_hash),
+        changed_blocks,
+    })
+}
+
+/// Returns `true` if a file of `len` bytes last modified at `mtime` still looks like `record`.
+///
+/// Both the size and the modification time must match. An unknown `mtime`,
+/// see `files::reported_time`, never matches.
+pub fn stat_unchanged(record: &FileRecord, len: u64, mtime: Option<DateTime<Utc>>) -> bool {
+    mtime.is_some() && record.size == len && record.modified_at == mtime
+}
+
+/// Returns `true` if the file behind `record` still has the recorded size and mtime.
+///
+/// `verify --all` then assumes the content is unchanged and skips hashing it.
+pub async fn stat_matches(root: &Path, record: &FileRecord) -> bool {
+    match compio::fs::metadata(root.join(&record.path)).await {
+        Ok(meta) => stat_unchanged(record, meta.len(), files::reported_time(meta.modified())),
+        Err(_) => false,
+    }
+}
+
+/// Reports `record` as unchanged without reading its file.
+///
+/// Used for files skipped by `stat_matches`; `actual` stays empty since
+/// nothing was hashed.
+pub fn assume_unchanged(record: &FileRecord) -> FileResult {
+    FileResult {
+        path: record.path.clone(),
+        status: VerifyStatus::Ok,
+        expected: record.hash.clone(),
+        actual: None,
+        changed_blocks: Vec::new(),
+    }
+}
+
+/// Re-hashes the file behind `record` and reports whether it still matches.
+///
+/// This is what single-file `verify` runs: `check_record` together with its
+/// verdict, the same classification `verify --all` reports through
+/// `FileResult::failure`. A failure to hash is reported as `Unreadable`.
+///
+/// # Returns
+///
+/// The verdict, and the `FileResult` it was drawn from. The verdict is:
+///
+/// * `Ok(())` - The content hash matches the stored hash
+/// * `Err(VerificationError::HashMissMatched)` - The content changed
+/// * `Err(VerificationError::IoFailure)` - The file could not be found
+/// * `Err(VerificationError::HashFailure)` - The file exists but could not be read
+pub async fn verify_record(
+    root: &Path,
+    record: &FileRecord,
+) -> (Result<(), VerificationError>, FileResult) {
+    let result = check_record(root, record)
+        .await
+        .unwrap_or_else(|_| FileResult {
+            path: record.path.clone(),
+            status: VerifyStatus::Unreadable,
+            expected: record.hash.clone(),
+            actual: None,
+            changed_blocks: Vec::new(),
+        });
+    (result.failure().map_or(Ok(()), Err), result)
+}
+
+/// Like `check_record`, but first reserves the bytes it buffers from `budget`.
+///
+/// Hashing reads a file through a single `HASH_CHUNK_SIZE` buffer whatever
+/// its size, so that is what is reserved, and held while the file is read
+/// and hashed. Concurrent checks sharing one budget therefore never buffer
+/// more than its limit in total.
+pub async fn check_record_within(
+    root: &Path,
+    record: &FileRecord,
+    budget: &MemoryBudget,
+) -> Result<FileResult, Exn<IoError<PathBuf>>> {
+    let _reservation = budget.acquire(hash::HASH_CHUNK_SIZE as u64).await;
+    check_record(root, record).await
+}
+
+/// Renders what `verify --all` prints for one checked record.
+///
+/// The block is built whole so parallel checks can emit it in one write.
+///
+/// # Arguments
+///
+/// * `record` - The tracked record that was checked
+/// * `result` - The outcome of checking it
+/// * `max_age` - Flag the record if its baseline is older than this
+pub fn render_result(
+    record: &FileRecord,
+    result: &FileResult,
+    max_age: Option<chrono::Duration>,
+) -> String {
+    use colored::Colorize;
+    use std::fmt::Write;
+    let mut block = String::new();
+    match (&result.status, &result.actual) {
+        (VerifyStatus::Ok, _) => {
+            let good = "--- GOOD ---".bold();
+            let good_msg = styled::success("Hashes match,");
+            let no_change = "the files have not changed";
+            let _ = writeln!(block, "{}", good);
+            let _ = writeln!(block, "{} {}", good_msg, no_change);
+            let _ = writeln!(block, "File: {}", record.path.display());
+            let _ = writeln!(block, "Tracked on:\n\t {}", record.time_stamp);
+        }
+        (VerifyStatus::Changed, Some(current_hash)) => {
+            let warning = "--- WARNING ---".bold();
+            let warning_msg = styled::error("Hash mismatch the files have changed.");
+            let _ = writeln!(block, "{}", warning);
+            let _ = writeln!(block, "{}", warning_msg);
+            let _ = writeln!(block, "File: {}", record.path.display());
+            let _ = writeln!(
+                block,
+                "From ({}...) -> To ({}..)\n Updated on:\n\t {}",
+                current_hash.short(),
+                record.hash.short(),
+                record.time_stamp,
+            );
+            if let Some(changed) = changed_blocks_line(record, &result.changed_blocks) {
+                let _ = writeln!(block, "{}", changed);
+            }
+        }
+        (VerifyStatus::Unreadable, _) => {
+            let unreadable = "--- UNREADABLE ---".bold();
+            let unreadable_msg = styled::error("The tracked file could not be read.");
+            let _ = writeln!(block, "{}", unreadable);
+            let _ = writeln!(block, "{}", unreadable_msg);
+            let _ = writeln!(block, "File: {}", record.path.display());
+        }
+        _ => {
+            let missing = "--- MISSING ---".bold();
+            let missing_msg = styled::error("The tracked file could not be found.");
+            let _ = writeln!(block, "{}", missing);
+            let _ = writeln!(block, "{}", missing_msg);
+            let _ = writeln!(block, "File: {}", record.path.display());
+        }
+    }
+    if max_age.is_some_and(|max_age| record.is_stale(max_age)) {
+        let _ = writeln!(block, "{}", styled::warn(&record.stale_notice()));
+    }
+    block
+}
+
+/// Describes which blocks of `record` changed, e.g. `Changed block(s) of 1.0 MiB: 0, 3`.
+///
+/// Returns `None` if the record is not tracked with blocks or none changed.
+pub fn changed_blocks_line(record: &FileRecord, changed_blocks: &[u64]) -> Option<String> {
+    let blocks = record.blocks.as_ref()?;
+    if changed_blocks.is_empty() {
+        return None;
+    }
+    let indexes: Vec<String> = changed_blocks
+        .iter()
+        .map(|index| index.to_string())
+        .collect();
+    Some(format!(
+        "Changed block(s) of {}: {}",
+        models::format_size(blocks.block_size),
+        indexes.join(", ")
+    ))
+}
+
+/// Returns how many files `verify --all` hashes at once unless `--jobs` says otherwise.
+///
+/// This is the available parallelism of the machine, or `1` if it is unknown.
+pub fn default_jobs() -> usize {
+    std::thread::available_parallelism().map_or(1, std::num::NonZero::get)
+}
+
+/// Verifies `records` with a fresh read of each file.
+///
+/// Up to `default_jobs()` files are read at once; the results still come
+/// back in record order.
+///
+/// # Returns
+///
+/// * `Ok(VerifyReport)` - The outcome for each record, in order
+/// * `Err(Exn<IoError<PathBuf>>)` - If an opened file could not be hashed
+pub async fn verify_records(
+    root: &Path,
+    records: &[&FileRecord],
+) -> Result<VerifyReport, Exn<IoError<PathBuf>>> {
+    use futures_util::StreamExt;
+    let results: Vec<_> = futures_util::stream::iter(records)
+        .map(|record| check_record(root, record))
+        .buffered(default_jobs())
+        .collect()
+        .await;
+    let results = results.into_iter().collect::<Result<Vec<_>, _>>()?;
+    Ok(VerifyReport::from_results(results))
+}
+
+/// Verifies the records tracking `paths` with a fresh read of each file.
+///
+/// Paths that are not tracked by `db` are ignored.
+///
+/// # Arguments
+///
+/// * `db` - The database holding the records
+/// * `paths` - Tracked files, as given on the command line
+/// * `matching` - How `paths` are matched against tracked records
+///
+/// # Returns
+///
+/// * `Ok(VerifyReport)` - The outcome for each tracked path, in `paths` order
+/// * `Err(Exn<IoError<PathBuf>>)` - If an opened file could not be hashed
+pub async fn verify_paths(
+    db: &Database,
+    paths: &[PathBuf],
+    matching: PathMatch,
+) -> Result<VerifyReport, Exn<IoError<PathBuf>>> {
+    let records: Vec<&FileRecord> = paths
+        .iter()
+        .filter_map(|path| db.find_record(&db.tracked_path(path), matching))
+        .collect();
+    verify_records(&db.root_dir, &records).await
+}
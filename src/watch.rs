@@ -0,0 +1,186 @@
+use crate::models::FileRecord;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Receiver;
+use std::time::{Duration, Instant};
+
+/// A change the OS reported for a file, and when it was seen.
+pub type Event = (PathBuf, Instant);
+
+/// The tracked records of a `watch` run, looked up by where their file is on disk.
+pub struct WatchedRecords<'db> {
+    /// Each record, keyed by its path joined onto the database root
+    by_location: HashMap<PathBuf, &'db FileRecord>,
+}
+
+impl<'db> WatchedRecords<'db> {
+    /// Indexes `records` by their location under `root`.
+    pub fn new(root: &Path, records: &'db [FileRecord]) -> Self {
+        let by_location = records
+            .iter()
+            .map(|record| (root.join(&record.path), record))
+            .collect();
+        Self { by_location }
+    }
+
+    /// Returns the record tracking the file at `location`, if any.
+    pub fn get(&self, location: &Path) -> Option<&'db FileRecord> {
+        self.by_location.get(location).copied()
+    }
+
+    /// Returns the directories holding tracked files, each once and in path order.
+    ///
+    /// The directories are watched rather than the files, so a file that an
+    /// editor saves by renaming a new copy over it is still seen.
+    pub fn directories(&self) -> Vec<PathBuf> {
+        let mut directories: Vec<PathBuf> = self
+            .by_location
+            .keys()
+            .filter_map(|location| location.parent().map(Path::to_path_buf))
+            .collect();
+        directories.sort();
+        directories.dedup();
+        directories
+    }
+
+    /// Returns the records whose file lies directly in `directory`, in path order.
+    pub fn in_directory(&self, directory: &Path) -> Vec<&'db FileRecord> {
+        let mut records: Vec<&FileRecord> = self
+            .by_location
+            .iter()
+            .filter(|(location, _)| location.parent() == Some(directory))
+            .map(|(_, record)| *record)
+            .collect();
+        records.sort_by(|a, b| a.path.cmp(&b.path));
+        records
+    }
+
+    /// Hands the change of `location` seen at `at` to `debouncer`, if a
+    /// tracked file is there; events for other files in a watched directory
+    /// are dropped.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the event was passed on.
+    pub fn dispatch(&self, debouncer: &mut Debouncer, location: PathBuf, at: Instant) -> bool {
+        let tracked = self.by_location.contains_key(&location);
+        if tracked {
+            debouncer.record(location, at);
+        }
+        tracked
+    }
+}
+
+/// Holds back change events until a file has been quiet for a while.
+///
+/// Saving a file often touches it several times in quick succession, and
+/// verifying it halfway through would report a change that is still being
+/// written. Every event restarts the file's quiet period, and a file is only
+/// released once no event arrived for the whole `window`.
+pub struct Debouncer {
+    /// How long a file must go without events before it is released
+    window: Duration,
+    /// Time of the latest event for each file waiting to be released
+    pending: HashMap<PathBuf, Instant>,
+}
+
+impl Debouncer {
+    /// Creates a debouncer that waits `window` after the last event of a file.
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            pending: HashMap::new(),
+        }
+    }
+
+    /// Records a change of `path` seen at `at`, restarting its quiet period.
+    pub fn record(&mut self, path: PathBuf, at: Instant) {
+        self.pending.insert(path, at);
+    }
+
+    /// Removes and returns the files that have been quiet for the whole window
+    /// at `now`, in path order.
+    pub fn ready(&mut self, now: Instant) -> Vec<PathBuf> {
+        let window = self.window;
+        let mut ready: Vec<PathBuf> = self
+            .pending
+            .iter()
+            .filter(|(_, last)| now.saturating_duration_since(**last) >= window)
+            .map(|(path, _)| path.clone())
+            .collect();
+        for path in ready.iter() {
+            self.pending.remove(path);
+        }
+        ready.sort();
+        ready
+    }
+}
+
+/// Directories `watch_directories` could not watch, with the reason for each.
+pub type Unwatched = Vec<(PathBuf, std::io::Error)>;
+
+/// Asks the OS to report changes to the files in `directories`.
+///
+/// The events are read with inotify on a background thread and sent as the
+/// path of the changed file. A directory that cannot be watched, e.g.
+/// because it was removed, is skipped and returned instead. The thread blocks
+/// waiting for the next event, so it only ends once an event arrives after
+/// the receiver was dropped, or with the process.
+///
+/// # Returns
+///
+/// * `Ok((Receiver<Event>, Unwatched))` - The changes, as they happen, and
+///   the directories that are not watched
+/// * `Err(std::io::Error)` - If the watcher cannot be created
+#[cfg(target_os = "linux")]
+pub fn watch_directories(directories: &[PathBuf]) -> std::io::Result<(Receiver<Event>, Unwatched)> {
+    use rustix::fs::inotify::{self, CreateFlags, WatchFlags};
+    use std::os::unix::ffi::OsStrExt;
+    let fd = inotify::init(CreateFlags::CLOEXEC)?;
+    let flags = WatchFlags::CLOSE_WRITE
+        | WatchFlags::MODIFY
+        | WatchFlags::ATTRIB
+        | WatchFlags::CREATE
+        | WatchFlags::DELETE
+        | WatchFlags::MOVED_FROM
+        | WatchFlags::MOVED_TO;
+    let mut watched = HashMap::new();
+    let mut unwatched = Vec::new();
+    for directory in directories {
+        match inotify::add_watch(&fd, directory.as_path(), flags) {
+            Ok(wd) => {
+                watched.insert(wd, directory.clone());
+            }
+            Err(err) => unwatched.push((directory.clone(), err.into())),
+        }
+    }
+    let (sender, receiver) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        let mut buffer = [std::mem::MaybeUninit::uninit(); 4096];
+        let mut reader = inotify::Reader::new(&fd, &mut buffer);
+        while let Ok(event) = reader.next() {
+            let (Some(directory), Some(name)) = (watched.get(&event.wd()), event.file_name())
+            else {
+                continue;
+            };
+            let location = directory.join(std::ffi::OsStr::from_bytes(name.to_bytes()));
+            if sender.send((location, Instant::now())).is_err() {
+                break;
+            }
+        }
+    });
+    Ok((receiver, unwatched))
+}
+
+/// Asks the OS to report changes to the files in `directories`.
+///
+/// Only Linux's inotify is supported so far; other platforms get an error.
+#[cfg(not(target_os = "linux"))]
+pub fn watch_directories(
+    _directories: &[PathBuf],
+) -> std::io::Result<(Receiver<Event>, Unwatched)> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "watching files is only supported on Linux",
+    ))
+}